@@ -0,0 +1,361 @@
+
+use crate::config::ProviderMirror;
+use crate::download;
+use crate::error::{JvmsError, Result};
+use serde::Deserialize;
+use std::cmp::Reverse;
+
+const ADOPTIUM_API_URL: &str = "https://api.adoptium.net/v3";
+
+///
+/// Name of the provider in the mirrors of the configuration.
+///
+pub const PROVIDER_NAME: &str = "adoptium";
+
+///
+/// Implementor names used in the release file of JDKs built by Adoptium and its predecessor AdoptOpenJDK.
+///
+static ADOPTIUM_VENDORS: [&str; 2] = ["Eclipse Adoptium", "AdoptOpenJDK"];
+
+#[derive(Deserialize)]
+struct AdoptiumAsset {
+    binary: AdoptiumBinary,
+    release_name: String,
+    version: AdoptiumVersion
+}
+
+#[derive(Deserialize)]
+struct AdoptiumBinary {
+    package: AdoptiumPackage
+}
+
+#[derive(Deserialize)]
+struct AdoptiumPackage {
+    name: String,
+    link: String,
+    size: u64,
+    checksum: Option<String>,
+    signature_link: Option<String>
+}
+
+#[derive(Deserialize)]
+struct AdoptiumVersion {
+    semver: String
+}
+
+#[derive(Deserialize)]
+struct AdoptiumAvailableReleases {
+    available_releases: Vec<u32>,
+    available_lts_releases: Vec<u32>
+}
+
+#[derive(Deserialize)]
+struct AdoptiumReleaseVersions {
+    versions: Vec<AdoptiumVersion>
+}
+
+///
+/// Number of versions requested when listing the releases of a major version, the most the API returns at once.
+///
+const RELEASE_VERSIONS_PAGE_SIZE: &str = "50";
+
+#[cfg(target_os="windows")]
+const ADOPTIUM_OS: &str = "windows";
+
+#[cfg(target_os="macos")]
+const ADOPTIUM_OS: &str = "mac";
+
+#[cfg(not(any(target_os="windows", target_os="macos")))]
+const ADOPTIUM_OS: &str = "linux";
+
+///
+/// A JDK release resolved from the Adoptium API, describing the archive to download without fetching it.
+///
+pub struct AdoptiumRelease {
+    pub release_name: String,
+    pub version: String,
+    pub file_name: String,
+    pub url: String,
+    pub size: u64,
+    pub checksum: Option<String>,
+    pub signature_url: Option<String>
+}
+
+///
+/// Maps the architecture jvms was built for to the name used by the Adoptium API.
+///
+pub fn get_current_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "x86" => "x86",
+        "powerpc64" => "ppc64le",
+        arch => arch
+    }
+}
+
+pub fn is_adoptium_vendor(vendor: &str) -> bool {
+    ADOPTIUM_VENDORS.contains(&vendor)
+}
+
+fn get_api_url(mirror: Option<&ProviderMirror>) -> &str {
+    mirror
+        .and_then(|mirror| mirror.api_url.as_deref())
+        .map(|api_url| api_url.trim_end_matches('/'))
+        .unwrap_or(ADOPTIUM_API_URL)
+}
+
+///
+/// Points an archive link returned by the API at the download url of the mirror, if it has one, by replacing the
+/// scheme and host of the link.
+///
+fn get_download_url(link: String, mirror: Option<&ProviderMirror>) -> String {
+    let download_url = match mirror.and_then(|mirror| mirror.download_url.as_deref()) {
+        Some(download_url) => download_url.trim_end_matches('/'),
+        None => return link
+    };
+
+    match link.split_once("://").and_then(|(_, rest)| rest.find('/').map(|index| &rest[index..])) {
+        Some(path) => format!("{}{}", download_url, path),
+        None => link
+    }
+}
+
+///
+/// Queries Adoptium for the latest JDK release of the provided major version and architecture, returning the
+/// archive that would be downloaded for the current platform.
+///
+pub fn resolve_release(major_version: u32, arch: &str, mirror: Option<&ProviderMirror>) -> Result<Option<AdoptiumRelease>> {
+    let url = format!("{}/assets/latest/{}/hotspot", get_api_url(mirror), major_version);
+    let assets: Vec<AdoptiumAsset> =
+        download::get(&url)?
+            .query("image_type", "jdk")
+            .query("os", ADOPTIUM_OS)
+            .query("architecture", arch)
+            .call()
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Failed to query {}: {}", url, error))
+            })?
+            .into_json()
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Failed to parse response from {}: {}", url, error))
+            })?;
+
+    Ok(assets
+        .into_iter()
+        .next()
+        .map(|asset| AdoptiumRelease {
+            release_name: asset.release_name,
+            version: strip_build(&asset.version.semver).to_owned(),
+            file_name: asset.binary.package.name,
+            url: get_download_url(asset.binary.package.link, mirror),
+            size: asset.binary.package.size,
+            checksum: asset.binary.package.checksum,
+            signature_url: asset.binary.package.signature_link.map(|link| get_download_url(link, mirror))
+        }))
+}
+
+///
+/// Queries Adoptium for the latest JDK release of the provided major version, returning its version without the
+/// build number (e.g. "17.0.9").
+///
+pub fn get_latest_version(major_version: u32, mirror: Option<&ProviderMirror>) -> Result<Option<String>> {
+    let url = format!("{}/assets/latest/{}/hotspot", get_api_url(mirror), major_version);
+    let assets: Vec<AdoptiumAsset> =
+        download::get(&url)?
+            .query("image_type", "jdk")
+            .query("os", ADOPTIUM_OS)
+            .call()
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Failed to query {}: {}", url, error))
+            })?
+            .into_json()
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Failed to parse response from {}: {}", url, error))
+            })?;
+
+    Ok(assets
+        .into_iter()
+        .next()
+        .map(|asset| strip_build(&asset.version.semver).to_owned()))
+}
+
+///
+/// Queries Adoptium for the major java versions it has releases of, newest first, each along with whether it is a
+/// long-term support release.
+///
+pub fn list_major_versions(mirror: Option<&ProviderMirror>) -> Result<Vec<(u32, bool)>> {
+    let url = format!("{}/info/available_releases", get_api_url(mirror));
+    let releases: AdoptiumAvailableReleases =
+        download::get(&url)?
+            .call()
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Failed to query {}: {}", url, error))
+            })?
+            .into_json()
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Failed to parse response from {}: {}", url, error))
+            })?;
+
+    let lts_releases = releases.available_lts_releases;
+    let mut major_versions: Vec<_> = releases.available_releases
+        .into_iter()
+        .map(|major_version| (major_version, lts_releases.contains(&major_version)))
+        .collect();
+    major_versions.sort_by_key(|(major_version, _)| Reverse(*major_version));
+    Ok(major_versions)
+}
+
+///
+/// Queries Adoptium for the general availability releases of a major version for the current platform and the
+/// provided architecture, returning their versions without build numbers, newest first. Only the most recent
+/// releases are returned, as many as fit in a single page of the API.
+///
+pub fn list_versions(major_version: u32, arch: &str, mirror: Option<&ProviderMirror>) -> Result<Vec<String>> {
+    let url = format!("{}/info/release_versions", get_api_url(mirror));
+    let response =
+        download::get(&url)?
+            .query("version", &format!("[{},{})", major_version, major_version + 1))
+            .query("release_type", "ga")
+            .query("vendor", "eclipse")
+            .query("image_type", "jdk")
+            .query("os", ADOPTIUM_OS)
+            .query("architecture", arch)
+            .query("page_size", RELEASE_VERSIONS_PAGE_SIZE)
+            .query("sort_order", "DESC")
+            .call();
+
+    // The API responds with 404 rather than an empty list when nothing matches.
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(Vec::new()),
+        Err(error) => return Err(JvmsError::NetworkError(format!("Failed to query {}: {}", url, error)))
+    };
+
+    let release_versions: AdoptiumReleaseVersions =
+        response
+            .into_json()
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Failed to parse response from {}: {}", url, error))
+            })?;
+
+    let mut versions: Vec<String> = release_versions.versions
+        .iter()
+        .map(|version| strip_build(&version.semver).to_owned())
+        .collect();
+
+    // Rebuilds of a release share its version.
+    versions.dedup();
+    Ok(versions)
+}
+
+///
+/// Strips the build number from a version, e.g. "17.0.9+9" becomes "17.0.9".
+///
+fn strip_build(semver: &str) -> &str {
+    semver.split('+').next().unwrap_or_default()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    pub(crate) const RELEASE_21: &str = r#"[{
+        "binary": {
+            "package": {
+                "name": "OpenJDK21U-jdk_aarch64_linux_hotspot_21.0.4_7.tar.gz",
+                "link": "https://github.com/adoptium/temurin21-binaries/releases/download/jdk-21.0.4%2B7/OpenJDK21U-jdk_aarch64_linux_hotspot_21.0.4_7.tar.gz",
+                "size": 205000000,
+                "checksum": "0e9e6b5c4f6ebd7f1a3dbbd2d1ad4e2b6df77cd5d8d9f8e51ac4c3c8a1f1fc3a",
+                "signature_link": "https://github.com/adoptium/temurin21-binaries/releases/download/jdk-21.0.4%2B7/OpenJDK21U-jdk_aarch64_linux_hotspot_21.0.4_7.tar.gz.sig"
+            }
+        },
+        "release_name": "jdk-21.0.4+7",
+        "version": { "semver": "21.0.4+7.0.LTS" }
+    }]"#;
+
+    ///
+    /// Serves the responses, one per connection, from a local mock of the Adoptium API. Returns the API url to use as
+    /// a mirror and a handle that yields the request line of each request once all responses were served.
+    ///
+    pub(crate) fn serve_api(responses: Vec<(u16, &'static str)>) -> (ProviderMirror, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mirror = ProviderMirror {
+            api_url: Some(format!("http://{}/v3/", listener.local_addr().unwrap())),
+            download_url: None
+        };
+
+        let handle = thread::spawn(move || {
+            responses.into_iter()
+                .map(|(status, body)| {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut request_line = String::new();
+                    reader.read_line(&mut request_line).unwrap();
+
+                    let mut header = String::new();
+                    while reader.read_line(&mut header).unwrap() > 2 {
+                        header.clear();
+                    }
+
+                    write!(stream, "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body).unwrap();
+                    request_line.trim_end().to_owned()
+                })
+                .collect()
+        });
+
+        (mirror, handle)
+    }
+
+    #[test]
+    fn resolves_the_latest_release_from_the_api() {
+        let (mirror, handle) = serve_api(vec![(200, RELEASE_21)]);
+        let release = resolve_release(21, "aarch64", Some(&mirror)).unwrap().unwrap();
+        let requests = handle.join().unwrap();
+
+        assert!(requests[0].starts_with("GET /v3/assets/latest/21/hotspot?"), "unexpected request {}", requests[0]);
+        for query in &["image_type=jdk", &format!("os={}", ADOPTIUM_OS), "architecture=aarch64"] {
+            assert!(requests[0].contains(query), "{} is missing from {}", query, requests[0]);
+        }
+
+        assert_eq!(release.release_name, "jdk-21.0.4+7");
+        assert_eq!(release.version, "21.0.4");
+        assert_eq!(release.file_name, "OpenJDK21U-jdk_aarch64_linux_hotspot_21.0.4_7.tar.gz");
+        assert_eq!(release.size, 205000000);
+        assert!(release.url.starts_with("https://github.com/adoptium/"));
+        assert!(release.checksum.is_some());
+    }
+
+    #[test]
+    fn resolves_nothing_when_there_is_no_release() {
+        let (mirror, handle) = serve_api(vec![(200, "[]")]);
+        assert!(resolve_release(99, "x64", Some(&mirror)).unwrap().is_none());
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn reports_failed_requests_as_network_errors() {
+        let (mirror, handle) = serve_api(vec![(500, "{}"), (200, "not json")]);
+        assert!(matches!(resolve_release(21, "x64", Some(&mirror)), Err(JvmsError::NetworkError(_))));
+        assert!(matches!(resolve_release(21, "x64", Some(&mirror)), Err(JvmsError::NetworkError(_))));
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn points_download_links_at_the_mirror() {
+        let mirror = ProviderMirror {
+            api_url: None,
+            download_url: Some("https://nexus.example.com/repository/github/".to_owned())
+        };
+
+        assert_eq!(
+            get_download_url("https://github.com/adoptium/temurin21-binaries/jdk.tar.gz".to_owned(), Some(&mirror)),
+            "https://nexus.example.com/repository/github/adoptium/temurin21-binaries/jdk.tar.gz"
+        );
+        assert_eq!(get_download_url("https://github.com/jdk.tar.gz".to_owned(), None), "https://github.com/jdk.tar.gz");
+        assert_eq!(get_download_url("not a url".to_owned(), Some(&mirror)), "not a url");
+    }
+
+}
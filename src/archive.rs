@@ -0,0 +1,40 @@
+
+use crate::error::{JvmsError, Result};
+use flate2::read::GzDecoder;
+use std::fs;
+use std::path::Path;
+
+///
+/// Extracts a .tar.gz or .zip archive, chosen by the extension of its file name, into the destination directory.
+/// Entries that would be written outside of the destination are rejected.
+///
+pub fn extract(archive_path: &Path, destination: &Path) -> Result<()> {
+    let file_name = archive_path.file_name().map(|name| name.to_string_lossy().to_lowercase()).unwrap_or_default();
+    fs::create_dir_all(destination)?;
+
+    if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+        let mut archive = tar::Archive::new(GzDecoder::new(fs::File::open(archive_path)?));
+        archive.set_preserve_permissions(true);
+
+        // Archive::unpack skips entries that would escape the destination.
+        archive.unpack(destination)
+            .map_err(|error| {
+                JvmsError::InvalidToolchain(format!("Failed to extract {:?}: {}", archive_path, error))
+            })
+
+    } else if file_name.ends_with(".zip") {
+        let mut archive = zip::ZipArchive::new(fs::File::open(archive_path)?)
+            .map_err(|error| {
+                JvmsError::InvalidToolchain(format!("Failed to read {:?}: {}", archive_path, error))
+            })?;
+
+        // ZipArchive::extract rejects entries that would escape the destination.
+        archive.extract(destination)
+            .map_err(|error| {
+                JvmsError::InvalidToolchain(format!("Failed to extract {:?}: {}", archive_path, error))
+            })
+
+    } else {
+        Err(JvmsError::InvalidToolchain(format!("{:?} is not a .tar.gz or .zip archive.", archive_path)))
+    }
+}
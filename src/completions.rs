@@ -0,0 +1,88 @@
+
+use clap::App;
+use clap_generate::generate;
+use clap_generate::generators::{Bash, Fish, PowerShell, Zsh};
+use std::io::{self, Write};
+use std::str::FromStr;
+
+///
+/// Completes toolchain names for the argument following a toolchain-taking subcommand or option in bash. The pair of
+/// words before the cursor is matched so that e.g. `override set` and `toolchain remove` are told apart from
+/// subcommands of the same name that take no toolchain. Everything else is left to the generated completion.
+///
+const BASH_DYNAMIC_COMPLETION: &str = r#"
+_jvms_with_toolchains() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    if [[ ${COMP_CWORD} -ge 2 && ${cur} != -* ]]; then
+        case "${COMP_WORDS[COMP_CWORD-2]} ${COMP_WORDS[COMP_CWORD-1]}" in
+            *" default"|*" run"|*" shell"|*" use"|*" -t"|*" --toolchain"|"override set"|"toolchain export-path"|"toolchain remove"|"toolchain rename"|"toolchain tag"|"toolchain untag")
+                COMPREPLY=( $(compgen -W "$(jvms toolchain list --names 2>/dev/null)" -- "${cur}") )
+                return 0
+                ;;
+        esac
+    fi
+
+    _jvms "$@"
+}
+
+complete -F _jvms_with_toolchains -o bashdefault -o default jvms
+"#;
+
+///
+/// The fish equivalent of BASH_DYNAMIC_COMPLETION.
+///
+const FISH_DYNAMIC_COMPLETION: &str = r#"
+function __fish_jvms_needs_toolchain
+    set -l tokens (commandline -opc)
+    test (count $tokens) -ge 2; or return 1
+    switch "$tokens[-2] $tokens[-1]"
+        case '* default' '* run' '* shell' '* use' '* -t' '* --toolchain' 'override set' 'toolchain export-path' 'toolchain remove' 'toolchain rename' 'toolchain tag' 'toolchain untag'
+            return 0
+    end
+    return 1
+end
+
+complete -c jvms -n __fish_jvms_needs_toolchain -f -a '(jvms toolchain list --names 2>/dev/null)'
+"#;
+
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+    PowerShell
+}
+
+impl FromStr for CompletionShell {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(CompletionShell::Bash),
+            "zsh" => Ok(CompletionShell::Zsh),
+            "fish" => Ok(CompletionShell::Fish),
+            "powershell" => Ok(CompletionShell::PowerShell),
+            _ => Err(format!("Unknown shell: {}", s))
+        }
+    }
+}
+
+///
+/// Writes the completion script of the app for the shell. For bash and fish, toolchain names are completed from the
+/// configuration by calling `jvms toolchain list --names` each time they are needed.
+///
+pub fn write_completions(shell: CompletionShell, app: &mut App, out: &mut dyn Write) -> io::Result<()> {
+    match shell {
+        CompletionShell::Bash => {
+            generate::<Bash, _>(app, "jvms", out);
+            out.write_all(BASH_DYNAMIC_COMPLETION.as_bytes())?;
+        },
+        CompletionShell::Zsh => generate::<Zsh, _>(app, "jvms", out),
+        CompletionShell::Fish => {
+            generate::<Fish, _>(app, "jvms", out);
+            out.write_all(FISH_DYNAMIC_COMPLETION.as_bytes())?;
+        },
+        CompletionShell::PowerShell => generate::<PowerShell, _>(app, "jvms", out)
+    }
+
+    out.flush()
+}
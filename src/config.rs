@@ -1,31 +1,387 @@
 
+use crate::{adoptium, archive};
 use crate::error::{JvmsError, Result};
+use crate::hooks::{self, HookContext, HookEvent};
+use crate::jdk::{self, JavaVersion, JdkInfo};
+use crate::selector::ToolchainSelector;
+use log::{debug, trace};
 use serde::{Deserialize, Serialize};
-use std::{env, fs};
+use std::{env, fs, io};
+use std::io::Write;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::cmp::{Ordering, Reverse};
 use std::collections::HashMap;
+use std::hash::Hash;
 use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
 use crate::shim::Shim;
 
+///
+/// Name of the TOML project configuration file.
+///
+const PROJECT_CONFIG_FILE_NAME: &str = ".jvms.toml";
+
+///
+/// Name of the JSON project configuration file, read for projects that predate .jvms.toml.
+///
+const LEGACY_PROJECT_CONFIG_FILE_NAME: &str = ".jvms.conf";
+
+///
+/// Name of the project file containing only the name of a toolchain, like .java-version.
+///
+pub const PROJECT_RC_FILE_NAME: &str = ".jvmsrc";
+
+///
+/// Project files searched for in the current directory and its ancestors, in order of precedence within a directory.
+///
+const PROJECT_CONFIG_FILE_NAMES: [&str; 3] = [PROJECT_CONFIG_FILE_NAME, PROJECT_RC_FILE_NAME, LEGACY_PROJECT_CONFIG_FILE_NAME];
+
+///
+/// Environment variable that overrides the project configuration path, relative to the project root.
+///
+const PROJECT_CONFIG_ENV: &str = "JVMS_PROJECT_CONFIG";
+
+///
+/// Name of the jenv compatible file containing the name of a toolchain.
+///
+pub const JAVA_VERSION_FILE_NAME: &str = ".java-version";
+
+///
+/// Name of the project file containing the path of a java home to use directly, without a registered toolchain.
+///
+pub const PROJECT_HOME_FILE_NAME: &str = ".jvms-home";
+
+///
+/// Name of the asdf file listing the versions of several tools, of which only the java entry is used.
+///
+pub const TOOL_VERSIONS_FILE_NAME: &str = ".tool-versions";
+
+///
+/// Environment variable that enables strict resolution when set to a value other than "0" or "false".
+///
+const STRICT_ENV: &str = "JVMS_STRICT";
+
+///
+/// Environment variable naming the directory at which upward searches for project files stop.
+///
+const SEARCH_ROOT_ENV: &str = "JVMS_ROOT";
+
+///
+/// Environment variable naming the installation directory, used when it cannot be found from the running binary.
+///
+const INSTALL_DIR_ENV: &str = "JVMS_INSTALL_DIR";
+
+///
+/// Environment variable naming a configuration file to use instead of the one in the installation directory. It is
+/// also set by `--config`, so that shims started by the command use the same file.
+///
+pub const CONFIG_ENV: &str = "JVMS_CONFIG";
+
+///
+/// Environment variable selecting how shims are linked when no link mode is given on the command line.
+///
+const LINK_MODE_ENV: &str = "JVMS_LINK_MODE";
+
+///
+/// Environment variable naming the toolchain for the current shell session, set by `jvms use` and `jvms shell`. It
+/// takes precedence over every other way of selecting a toolchain.
+///
+pub const TOOLCHAIN_ENV: &str = "JVMS_TOOLCHAIN";
+
+///
+/// Name of the file in the installation directory listing the shims generated by `jvms rehash`, one per line, so
+/// that only those are removed when their tools go away.
+///
+const GENERATED_SHIMS_FILE_NAME: &str = "jvms.shims";
+
+///
+/// Name of the configuration file in the installation directory. New configurations are written in TOML.
+///
+const CONFIG_FILE_NAME: &str = "jvms.toml";
+
+///
+/// Name of the JSON configuration file used before TOML was supported. It is still read and written as long as
+/// there is no TOML configuration file, until `jvms config migrate` converts it.
+///
+const LEGACY_CONFIG_FILE_NAME: &str = "jvms.conf";
+
+///
+/// Name of the file the configuration is written to before it is renamed over the configuration file.
+///
+const CONFIG_TEMP_FILE_NAME: &str = "jvms.conf.tmp";
+
+///
+/// Name of the file locked while the configuration file is written, so that concurrent writers take turns.
+///
+const CONFIG_LOCK_FILE_NAME: &str = "jvms.conf.lock";
+
 pub struct JvmsInstallation {
-    installation_path: PathBuf
+    installation_path: PathBuf,
+    ///
+    /// The configuration file chosen with --config or JVMS_CONFIG, used instead of the one in the installation
+    /// directory.
+    ///
+    config_file_path: Option<PathBuf>,
+    ///
+    /// The lock file of the configuration while this process holds the lock.
+    ///
+    config_lock: RefCell<Option<fs::File>>
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct JvmsConfiguration {
     toolchains: Option<HashMap<String, JavaToolchain>>,
     default: Option<String>,
-    overrides: Option<Vec<JvmsOverride>>
+    #[serde(serialize_with = "serialize_overrides_sorted")]
+    overrides: Option<Vec<JvmsOverride>>,
+    strict: Option<bool>,
+    ///
+    /// Environment variables removed from the environment of tools launched through a shim.
+    ///
+    clear_env: Option<Vec<String>>,
+    ///
+    /// Whether duplicate entries are collapsed when a toolchain's bin directory is prepended to PATH. Defaults to on.
+    ///
+    #[serde(default)]
+    dedupe_path: Option<bool>,
+    ///
+    /// Whether symlinks are resolved in override paths and the current directory before they are compared. Defaults
+    /// to off.
+    ///
+    #[serde(default)]
+    resolve_symlinks: Option<bool>,
+    ///
+    /// The preferred toolchain for each major java version, used when a toolchain is selected by major version.
+    ///
+    #[serde(default, serialize_with = "serialize_version_defaults", deserialize_with = "deserialize_version_defaults")]
+    version_defaults: Option<HashMap<u32, String>>,
+    ///
+    /// Alternative names for toolchains, mapping each alias to the name of the toolchain it refers to.
+    ///
+    #[serde(default)]
+    aliases: Option<HashMap<String, String>>,
+    ///
+    /// Alternative locations of download providers, keyed by provider name, such as internal mirrors.
+    ///
+    #[serde(default)]
+    mirrors: Option<HashMap<String, ProviderMirror>>,
+    ///
+    /// Shell commands run when the default changes, when an override is set, and before a shim launches a tool.
+    ///
+    #[serde(default)]
+    hooks: Option<JvmsHooks>,
+    ///
+    /// Modification time of the configuration file when it was loaded or last saved, used to detect changes to the
+    /// file.
+    ///
+    #[serde(skip)]
+    loaded_mtime: Cell<Option<SystemTime>>,
+    ///
+    /// The system configuration this configuration was layered over, along with its path. Only the entries that
+    /// differ from it are saved.
+    ///
+    #[serde(skip)]
+    system_layer: Option<(PathBuf, Box<JvmsConfiguration>)>
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
 pub struct JvmsOverride {
     pub path: PathBuf,
-    pub toolchain: String
+    pub toolchain: String,
+    ///
+    /// Arguments passed to java ahead of the forwarded arguments, after those of the toolchain.
+    ///
+    #[serde(default)]
+    pub java_opts: Option<Vec<String>>,
+    ///
+    /// Environment variables set for tools launched in the directory, taking precedence over those of the toolchain.
+    ///
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>,
+    ///
+    /// Whether the override only applies to the directory itself rather than to everything below it as well.
+    ///
+    #[serde(default)]
+    pub exact: Option<bool>
+}
+
+///
+/// Where a download provider is reached instead of its public location.
+///
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
+pub struct ProviderMirror {
+    ///
+    /// Base URL of the provider's API, replacing e.g. https://api.adoptium.net/v3.
+    ///
+    pub api_url: Option<String>,
+    ///
+    /// Base URL replacing the scheme and host of the archive links returned by the API.
+    ///
+    pub download_url: Option<String>
+}
+
+///
+/// Shell commands run around changes to the selected toolchain and before tools are launched, keyed by event.
+///
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
+pub struct JvmsHooks {
+    pub pre_default: Option<String>,
+    pub post_default: Option<String>,
+    pub pre_override: Option<String>,
+    pub post_override: Option<String>,
+    pub pre_launch: Option<String>
+}
+
+///
+/// Selects which overrides are removed when cleaning the override list.
+///
+#[derive(Clone, Copy)]
+pub enum OverrideCleanPolicy {
+    /// Remove overrides for directories that no longer exist.
+    MissingDirectory,
+    /// Remove overrides referencing toolchains that are no longer registered.
+    MissingToolchain,
+    /// Remove overrides matching either of the other policies.
+    All
+}
+
+///
+/// A setting that selects a toolchain.
+///
+pub enum ToolchainReference {
+    /// The default toolchain.
+    Default,
+    /// The version default for a major java version.
+    VersionDefault(u32),
+    /// The override for a directory.
+    Override(PathBuf)
+}
+
+///
+/// Where the toolchain selected for a directory came from.
+///
+pub enum ResolutionSource {
+    /// A .jvms-home file naming a java home that is not a registered toolchain.
+    ProjectHome(PathBuf),
+    /// A .jvms.toml, .jvmsrc, or .jvms.conf project file, or the file named by JVMS_PROJECT_CONFIG.
+    ProjectConfig(PathBuf),
+    /// The java entry of an asdf .tool-versions file.
+    ToolVersions(PathBuf),
+    /// A jenv .java-version file in the directory or one of its parents.
+    JavaVersion(PathBuf),
+    /// An override registered for the directory or one of its parents.
+    Override(PathBuf),
+    /// The default toolchain of the configuration.
+    Default,
+    /// The global .java-version file in the user's home directory.
+    GlobalJavaVersion(PathBuf),
+    /// The JVMS_TOOLCHAIN environment variable of the current shell session.
+    Session
+}
+
+///
+/// The toolchain selected for a directory. A java home named by a .jvms-home file has no name, and its toolchain is
+/// not registered in the configuration.
+///
+pub struct ResolvedToolchain<'a> {
+    pub name: Option<&'a str>,
+    pub toolchain: Cow<'a, JavaToolchain>,
+    pub source: ResolutionSource
+}
+
+///
+/// The java home selected for a directory, along with the toolchain it belongs to. A java home named by a .jvms-home
+/// file has no toolchain.
+///
+pub struct ResolvedHome<'a> {
+    pub name: Option<&'a str>,
+    pub toolchain: Option<&'a JavaToolchain>,
+    pub java_home: PathBuf
+}
+
+///
+/// How shims are linked to the jvms binary of an installation.
+///
+#[derive(Clone, Copy)]
+pub enum LinkMode {
+    /// Hard link the shim, which requires the shim and binary to be on the same filesystem.
+    Hard,
+    /// Symlink the shim to the binary.
+    Symbolic,
+    /// Copy the binary to the shim.
+    Copy,
+    /// Try a hard link, then a symlink, then a copy, e.g. when the installation is on a different filesystem than
+    /// the binary or on one without hard links.
+    Auto
+}
+
+///
+/// Decides which side wins when a merged configuration has an entry that conflicts with an existing one.
+///
+#[derive(Clone, Copy)]
+pub enum MergePolicy {
+    PreferExisting,
+    PreferImported
+}
+
+///
+/// Describes the entries added, updated, and skipped because of a conflict when merging configurations.
+///
+#[derive(Default)]
+pub struct MergeReport {
+    pub added: Vec<String>,
+    pub updated: Vec<String>,
+    pub skipped: Vec<String>
+}
+
+///
+/// Builds a JvmsConfiguration in one expression, for embedders and for generating configurations programmatically.
+///
+pub struct JvmsConfigurationBuilder {
+    config: JvmsConfiguration
 }
 
 #[derive(Deserialize, Serialize)]
+pub struct JvmsProjectConfiguration {
+    pub toolchain: String
+}
+
+#[derive(Clone, Deserialize, PartialEq, Serialize)]
 pub struct JavaToolchain {
-    pub java_home: PathBuf
+    pub java_home: PathBuf,
+    pub arch: Option<String>,
+    pub managed: Option<bool>,
+    ///
+    /// Set to false when the java home was registered while unavailable, e.g. a lazily mounted network path.
+    ///
+    pub verified: Option<bool>,
+    ///
+    /// Free-form labels used to group toolchains for listing and bulk removal.
+    ///
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    ///
+    /// Arguments passed to java ahead of the forwarded arguments.
+    ///
+    #[serde(default)]
+    pub java_opts: Option<Vec<String>>,
+    ///
+    /// The version detected when the toolchain was added, for java homes whose release file does not provide one.
+    ///
+    #[serde(default)]
+    pub version: Option<String>,
+    ///
+    /// The vendor recorded when the toolchain was added, shown when the release file is unavailable.
+    ///
+    #[serde(default)]
+    pub vendor: Option<String>,
+    ///
+    /// Environment variables set for tools launched with this toolchain.
+    ///
+    #[serde(default)]
+    pub env: Option<HashMap<String, String>>
 }
 
 fn normalize_path(path: impl AsRef<Path>) -> PathBuf {
@@ -88,7 +444,47 @@ fn normalize_path(path: impl AsRef<Path>) -> PathBuf {
     norm_path
 }
 
-fn make_absolute(path: impl AsRef<Path>) -> PathBuf {
+///
+/// Converts an absolute path to the form override paths are compared in. Symlinks are resolved when requested and
+/// the path exists, and on Windows, whose file systems ignore case, the path is lowercased.
+///
+pub(crate) fn comparable_path(path: &Path, resolve_symlinks: bool) -> PathBuf {
+    let path = if resolve_symlinks {
+        fs::canonicalize(path).map(strip_verbatim_prefix).unwrap_or_else(|_| normalize_path(path))
+
+    } else {
+        normalize_path(path)
+    };
+
+    if cfg!(target_os="windows") {
+        PathBuf::from(path.to_string_lossy().to_lowercase())
+
+    } else {
+        path
+    }
+}
+
+///
+/// Removes the \\?\ prefix that canonicalize adds on Windows, so that canonical paths compare equal to the paths
+/// they were resolved from.
+///
+fn strip_verbatim_prefix(path: PathBuf) -> PathBuf {
+    #[cfg(target_os="windows")]
+    {
+        let path_str = path.to_string_lossy();
+        if let Some(unc_path) = path_str.strip_prefix(r"\\?\UNC\") {
+            return PathBuf::from(format!(r"\\{}", unc_path));
+        }
+
+        if let Some(disk_path) = path_str.strip_prefix(r"\\?\") {
+            return PathBuf::from(disk_path);
+        }
+    }
+
+    path
+}
+
+pub fn make_absolute(path: impl AsRef<Path>) -> PathBuf {
     let path = path.as_ref();
     let absolute_path = if path.is_absolute() {
         path.to_path_buf()
@@ -102,266 +498,3222 @@ fn make_absolute(path: impl AsRef<Path>) -> PathBuf {
     normalize_path(absolute_path)
 }
 
-impl JvmsInstallation {
+///
+/// Reads the whole file through a single handle, returning its contents along with the modification time of that
+/// same file. Should the path be replaced while reading, both still describe the file that was opened.
+///
+fn read_snapshot(path: &Path) -> io::Result<(Vec<u8>, SystemTime)> {
+    let mut file = fs::File::open(path)?;
+    let modified = file.metadata()?.modified()?;
+
+    let mut contents = Vec::new();
+    io::Read::read_to_end(&mut file, &mut contents)?;
+    Ok((contents, modified))
+}
 
-    pub fn new(installation_path: PathBuf) -> JvmsInstallation {
-        JvmsInstallation {
-            installation_path
-        }
+///
+/// Checks whether a configuration file is in TOML rather than JSON, by its extension.
+///
+fn is_toml_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "toml")
+}
+
+///
+/// Loads the configuration file, or an empty configuration if there is none, remembering its modification time.
+///
+fn read_configuration_file(config_file_path: &Path) -> Result<JvmsConfiguration> {
+    if !config_file_path.is_file() {
+        debug!("Configuration file {:?} does not exist, using an empty configuration", config_file_path);
+        return Ok(JvmsConfiguration::new());
     }
 
-    pub fn get_current_installation() -> JvmsInstallation {
-        let current_exe_dir =
-            env::current_exe().expect("Could not locate the currently executing binary.");
+    let (contents, loaded_mtime) = match read_snapshot(config_file_path) {
+        Ok(snapshot) => snapshot,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            debug!("Configuration file {:?} does not exist, using an empty configuration", config_file_path);
+            return Ok(JvmsConfiguration::new());
+        },
+        Err(error) => {
+            return Err(JvmsError::InvalidConfiguration(format!("Failed to open jvms configuration file: {:?}", error)));
+        }
+    };
 
-        let installation_dir =
-            current_exe_dir.parent().expect("Could not locate the currently executing installation directory.");
+    let config = JvmsConfiguration::parse(config_file_path, &contents)?;
+    config.loaded_mtime.set(Some(loaded_mtime));
+    debug!("Loaded configuration file {:?}", config_file_path);
+    Ok(config)
+}
 
-        JvmsInstallation::new(installation_dir.to_path_buf())
+///
+/// Adds the entries of the layer to the map, replacing those with the same key.
+///
+fn overlay_map<K: Eq + Hash, V>(map: &mut Option<HashMap<K, V>>, layer: Option<HashMap<K, V>>) {
+    if let Some(layer) = layer {
+        map.get_or_insert_with(HashMap::new).extend(layer);
     }
+}
 
-    pub fn get_installation_path(&self) -> &Path {
-        self.installation_path.as_path()
+///
+/// Returns the entries of the map that are missing from or differ in the system map, or the key of an entry of the
+/// system map that the map no longer has.
+///
+fn diff_map<K: Clone + Eq + Hash, V: Clone + PartialEq>(map: &Option<HashMap<K, V>>, system_map: &Option<HashMap<K, V>>) -> std::result::Result<Option<HashMap<K, V>>, K> {
+    let empty = HashMap::new();
+    let map = map.as_ref().unwrap_or(&empty);
+    let system_map = system_map.as_ref().unwrap_or(&empty);
+    if let Some(removed_key) = system_map.keys().find(|key| !map.contains_key(*key)) {
+        return Err(removed_key.clone());
     }
 
-    pub fn install_binaries(&self) -> Result<()> {
-        let jvms_source_binary = env::current_exe().expect("Failed to get current executing binary.");
+    let diff: HashMap<K, V> = map.iter()
+        .filter(|(key, value)| system_map.get(*key) != Some(*value))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
 
-        fs::create_dir_all(&self.installation_path)?;
+    Ok(if diff.is_empty() { None } else { Some(diff) })
+}
 
-        //
-        // Copy the jvms binary into the installation path.
-        //
+///
+/// Returns the setting if it differs from the system setting, or Err if the system setting was cleared.
+///
+fn diff_setting<T: Clone + PartialEq>(setting: &Option<T>, system_setting: &Option<T>) -> std::result::Result<Option<T>, ()> {
+    match (setting, system_setting) {
+        (None, Some(_)) => Err(()),
+        (setting, system_setting) if setting == system_setting => Ok(None),
+        (setting, _) => Ok(setting.clone())
+    }
+}
 
-        let jvms_dest_binary = {
-            let mut path = self.installation_path.clone();
-            path.push("jvms");
+///
+/// Writes the configuration to a new file at the path, in TOML or JSON, and flushes it to disk.
+///
+fn write_configuration_file(path: &Path, config: &JvmsConfiguration, toml: bool) -> Result<()> {
+    let contents = if toml {
+        toml::to_string_pretty(config)
+            .map_err(|serde_error| {
+                JvmsError::InvalidConfiguration(format!("Failed to write jvms configuration file: {:?}", serde_error))
+            })?
 
-            #[cfg(target_os="windows")]
-            {
-                assert!(path.set_extension("exe"));
-            }
+    } else {
+        serde_json::to_string_pretty(config)
+            .map_err(|serde_error| {
+                JvmsError::InvalidConfiguration(format!("Failed to write jvms configuration file: {:?}", serde_error))
+            })?
+    };
 
-            path
-        };
+    let mut writer =
+        fs::File::create(path)
+            .map_err(|io_error| {
+                JvmsError::InvalidConfiguration(format!("Failed to open jvms configuration file: {:?}", io_error))
+            })?;
+
+    writer.write_all(contents.as_bytes())
+        .and_then(|_| writer.sync_all())
+        .map_err(|io_error| {
+            JvmsError::InvalidConfiguration(format!("Failed to write jvms configuration file: {:?}", io_error))
+        })
+}
 
-        println!("Copying {:?} to {:?}", jvms_source_binary, jvms_dest_binary);
-        fs::copy(&jvms_source_binary, &jvms_dest_binary)?;
+///
+/// Writes the overrides sorted by path so that the configuration file does not change with the order in which
+/// overrides were added. Resolution picks the most specific override regardless of order.
+///
+fn serialize_overrides_sorted<S: serde::Serializer>(overrides: &Option<Vec<JvmsOverride>>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let sorted = overrides.as_ref().map(|overrides| {
+        let mut sorted: Vec<_> = overrides.iter().collect();
+        sorted.sort_by(|a, b| a.path.cmp(&b.path));
+        sorted
+    });
+
+    serde::Serialize::serialize(&sorted, serializer)
+}
 
-        //
-        // Create a hard link from all shims to the destination jvms binary.
-        //
+///
+/// Writes the version defaults with string keys sorted by major version, as TOML only allows string keys.
+///
+fn serialize_version_defaults<S: serde::Serializer>(version_defaults: &Option<HashMap<u32, String>>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    let sorted = version_defaults.as_ref().map(|version_defaults| {
+        let mut sorted: Vec<_> = version_defaults.iter().collect();
+        sorted.sort();
+        sorted.into_iter().map(|(major, name)| (major.to_string(), name)).collect::<Vec<_>>()
+    });
+
+    match sorted {
+        Some(sorted) => serializer.collect_map(sorted),
+        None => serializer.serialize_none()
+    }
+}
 
-        let mut source_path = self.installation_path.clone();
-        for shim in Shim::get_shims() {
-            source_path.push(shim.name);
+///
+/// Reads the version defaults written by serialize_version_defaults, parsing the string keys back into major
+/// versions.
+///
+fn deserialize_version_defaults<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Option<HashMap<u32, String>>, D::Error> {
+    let version_defaults: Option<HashMap<String, String>> = Deserialize::deserialize(deserializer)?;
+    version_defaults
+        .map(|version_defaults| {
+            version_defaults
+                .into_iter()
+                .map(|(major, name)| {
+                    major.parse()
+                        .map(|major| (major, name))
+                        .map_err(|_| serde::de::Error::custom(format!("invalid major version: {}", major)))
+                })
+                .collect()
+        })
+        .transpose()
+}
 
-            #[cfg(target_os="windows")]
-            {
-                assert!(source_path.set_extension("exe"));
-            }
+fn read_java_version_file(path: &Path) -> Result<Option<String>> {
+    let contents = fs::read_to_string(path)?;
+    Ok(contents
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .map(|line| line.to_owned()))
+}
 
-            println!("Linking {:?} to {:?}", source_path, jvms_dest_binary);
-            fs::hard_link(&jvms_dest_binary, &source_path)?;
-            assert!(source_path.pop());
-        }
+///
+/// Parses the java entry of an asdf .tool-versions file, returning its versions in order of preference. Returns
+/// None if the file has no java entry, and a java entry without versions counts as none.
+///
+fn parse_tool_versions(contents: &str) -> Option<Vec<String>> {
+    contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.first() == Some(&"java") && fields.len() > 1)
+        .map(|fields| fields[1..].iter().map(|version| (*version).to_owned()).collect())
+}
 
-        Ok(())
+///
+/// Strips the distribution prefix and build suffix from an asdf java version, e.g. "temurin-21.0.1+12" becomes
+/// "21.0.1".
+///
+fn strip_asdf_version(version: &str) -> &str {
+    let version = version.split('+').next().unwrap_or_default();
+    match version.rfind('-') {
+        Some(index) if version[index + 1..].starts_with(|c: char| c.is_ascii_digit()) => &version[index + 1..],
+        _ => version
     }
+}
 
-    pub fn load_configuration(&self) -> Result<JvmsConfiguration> {
-        let config_file_path = self.get_config_file_path();
-        if !config_file_path.is_file() {
-            return Ok(JvmsConfiguration::new());
-        }
+///
+/// Searches the start path and its ancestors for the relative path, stopping after the search root if it is one of
+/// the ancestors.
+///
+fn find_upwards(start_path: &Path, relative_path: &Path, search_root: Option<&Path>) -> Option<PathBuf> {
+    find_any_upwards(start_path, &[relative_path], search_root)
+}
 
-        let reader =
-            fs::File::open(config_file_path)
-                .map_err(|io_error| {
-                    JvmsError::InvalidConfiguration(format!("Failed to open jvms configuration file: {:?}", io_error))
-                })?;
+///
+/// Searches like find_upwards for the first of several relative paths, trying all of them in a directory before
+/// moving on to its parent.
+///
+fn find_any_upwards<P: AsRef<Path>>(start_path: &Path, relative_paths: &[P], search_root: Option<&Path>) -> Option<PathBuf> {
+    let found = find_map_upwards(start_path, search_root, |dir| {
+        Ok(relative_paths.iter().map(|relative_path| dir.join(relative_path)).find(|path| path.is_file()))
+    });
 
-        let config =
-            serde_json::from_reader(reader)
-                .map_err(|serde_error| {
-                    JvmsError::InvalidConfiguration(format!("Failed to parse jvms configuration file: {:?}", serde_error))
-                })?;
+    found.unwrap_or_default()
+}
+
+///
+/// Walks up from the start path like find_upwards, returning the first value the function finds in a directory.
+///
+fn find_map_upwards<T, F>(start_path: &Path, search_root: Option<&Path>, mut find: F) -> Result<Option<T>>
+    where F: FnMut(&Path) -> Result<Option<T>> {
+
+    let search_root = search_root.map(make_absolute);
+    for dir in make_absolute(start_path).ancestors() {
+        if let Some(value) = find(dir)? {
+            return Ok(Some(value));
+        }
 
-        Ok(config)
+        if search_root.as_deref() == Some(dir) {
+            break;
+        }
     }
 
-    pub fn save_configuration(&self, config: &JvmsConfiguration, force: bool) -> Result<()> {
-        if !force {
-            config.validate_configuration()?;
+    Ok(None)
+}
+
+///
+/// Recursively copies a directory, printing progress as files are copied. Symlinks are recreated on Unix and
+/// replaced by copies of their targets elsewhere, since creating them on Windows requires extra privileges.
+///
+fn copy_dir_recursive(source: &Path, destination: &Path, copied_files: &mut usize) -> Result<()> {
+    fs::create_dir_all(destination)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let source_path = entry.path();
+        let destination_path = destination.join(entry.file_name());
+
+        #[cfg(unix)]
+        {
+            if entry.file_type()?.is_symlink() {
+                std::os::unix::fs::symlink(fs::read_link(&source_path)?, &destination_path)?;
+                continue;
+            }
         }
 
-        let config_file_path = self.get_config_file_path();
-        let writer =
-            fs::File::create(config_file_path)
-                .map_err(|io_error| {
-                    JvmsError::InvalidConfiguration(format!("Failed to open jvms configuration file: {:?}", io_error))
-                })?;
+        if source_path.is_dir() {
+            copy_dir_recursive(&source_path, &destination_path, copied_files)?;
 
-        serde_json::to_writer_pretty(writer, config)
-            .map_err(|serde_error| {
-                JvmsError::InvalidConfiguration(format!("Failed to write jvms configuration file: {:?}", serde_error))
-            })
-    }
+        } else {
+            // fs::copy also copies permission bits, which keeps the JDK binaries executable on Unix.
+            fs::copy(&source_path, &destination_path)?;
 
-    fn get_config_file_path(&self) -> PathBuf {
-        let mut installation_path = self.installation_path.clone();
-        installation_path.push("jvms.conf");
-        installation_path
+            *copied_files += 1;
+            if copied_files.is_multiple_of(100) {
+                print!("\rCopied {} files...", copied_files);
+                let _ = io::stdout().flush();
+            }
+        }
     }
 
+    Ok(())
 }
 
-impl JvmsConfiguration {
-
-    pub fn new() -> JvmsConfiguration {
-        JvmsConfiguration {
-            toolchains: None,
-            default: None,
-            overrides: None
+///
+/// Computes the number of single character insertions, deletions, and substitutions needed to turn one string into
+/// the other.
+///
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current_row = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution_cost = if a_char == *b_char { 0 } else { 1 };
+            let distance = (previous_row[j] + substitution_cost)
+                .min(previous_row[j + 1] + 1)
+                .min(current_row[j] + 1);
+            current_row.push(distance);
         }
-    }
 
-    pub fn get_toolchain(&self, toolchain_name: &str) -> Option<&JavaToolchain> {
-        self.toolchains
-            .as_ref()
-            .map(|i| i.get(toolchain_name))
-            .flatten()
+        previous_row = current_row;
     }
 
-    pub fn get_default_toolchain(&self) -> Option<&JavaToolchain> {
-        self.get_default_toolchain_name()
-            .map(|name| self.get_toolchain(name))
-            .flatten()
+    previous_row[b.len()]
+}
+
+///
+/// Expands a leading '~' to the home directory and any $VAR or ${VAR} references to the values of the environment
+/// variables, failing if a referenced variable is not set.
+///
+pub fn expand_path(path: &str) -> Result<PathBuf> {
+    let mut expanded = String::new();
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") || rest.starts_with("~\\") {
+        let home_dir = dirs::home_dir()
+            .ok_or_else(|| JvmsError::InvalidConfiguration(format!("Cannot expand {:?}, the home directory is unknown.", path)))?;
+        expanded.push_str(&home_dir.to_string_lossy());
+        rest = &rest[1..];
     }
 
-    pub fn get_environment_toolchain(&self, environment_path: &Path) -> Option<&JavaToolchain> {
-        let environment_path = make_absolute(environment_path);
-        let mut best_override: Option<&JvmsOverride> = None;
-        if let Some(overrides) = &self.overrides {
-            for ovrride in overrides {
-                if !environment_path.starts_with(&ovrride.path) {
-                    continue;
-                }
+    while let Some(index) = rest.find('$') {
+        expanded.push_str(&rest[..index]);
+        rest = &rest[index + 1..];
 
-                if best_override.map(|o| ovrride.path.starts_with(&o.path)).unwrap_or(true) {
-                    best_override = Some(ovrride);
-                }
+        let (name, remainder) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => return Err(JvmsError::InvalidConfiguration(format!("Unterminated variable reference in {:?}", path)))
             }
+
+        } else {
+            let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+
+        if name.is_empty() {
+            expanded.push('$');
+
+        } else {
+            let value = env::var(name)
+                .map_err(|_| JvmsError::InvalidConfiguration(format!("Environment variable {} referenced by {:?} is not set.", name, path)))?;
+            expanded.push_str(&value);
         }
 
-        best_override
-            .map(|o| self.get_toolchain(&o.toolchain))
-            .flatten()
+        rest = remainder;
     }
 
-    pub fn has_toolchain(&self, toolchain_name: &str) -> bool {
-        self.get_toolchain(toolchain_name).is_some()
+    expanded.push_str(rest);
+    Ok(PathBuf::from(expanded))
+}
+
+///
+/// Finds the closest .jvms-home file and the java home it names, walking up from the provided directory. Relative
+/// paths are resolved against the directory containing the file, and the java home must be a valid JDK.
+///
+fn find_project_java_home(environment_path: &Path, search_root: Option<&Path>) -> Result<Option<(PathBuf, PathBuf)>> {
+    let path = match find_upwards(environment_path, Path::new(PROJECT_HOME_FILE_NAME), search_root) {
+        Some(path) => path,
+        None => return Ok(None)
+    };
+
+    let contents = match read_java_version_file(&path)? {
+        Some(contents) => contents,
+        None => return Err(JvmsError::InvalidToolchain(format!("{:?} does not contain a java home.", path)))
+    };
+
+    let java_home = path.parent()
+        .unwrap_or_else(|| Path::new(""))
+        .join(expand_path(&contents)?);
+
+    jdk::JdkInfo::inspect(&java_home)
+        .map_err(|error| {
+            let reason = match error {
+                JvmsError::InvalidToolchain(reason) => reason,
+                error => error.to_string()
+            };
+
+            JvmsError::InvalidToolchain(format!("{:?} does not name a valid java home: {}", path, reason))
+        })?;
+
+    Ok(Some((path, normalize_path(java_home))))
+}
+
+///
+/// Rewrites a .java-version, .jvmsrc, or .tool-versions file so that it references the new toolchain name instead of the old
+/// one. Returns the new contents, or None if the file does not exist or does not reference the old name.
+///
+pub fn rename_in_project_file(path: &Path, old_name: &str, new_name: &str) -> Result<Option<String>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error.into())
+    };
+
+    let file_name = path.file_name().and_then(|file_name| file_name.to_str());
+    let mut changed = false;
+    let mut lines: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let rewritten = match file_name {
+            //
+            // Only the first non-empty line of a .java-version or .jvmsrc file names a toolchain.
+            //
+
+            Some(JAVA_VERSION_FILE_NAME) | Some(PROJECT_RC_FILE_NAME) => {
+                let is_entry = !changed && !line.trim().is_empty() && lines.iter().all(|line| line.trim().is_empty());
+                if is_entry && line.trim() == old_name {
+                    Some(new_name.to_owned())
+
+                } else {
+                    None
+                }
+            },
+            Some(TOOL_VERSIONS_FILE_NAME) => {
+                let (entry, comment) = match line.find('#') {
+                    Some(index) => line.split_at(index),
+                    None => (line, "")
+                };
+
+                let fields: Vec<_> = entry.split_whitespace().collect();
+                if fields.first() == Some(&"java") && fields.contains(&old_name) {
+                    let fields: Vec<_> = fields.into_iter()
+                        .map(|field| if field == old_name { new_name } else { field })
+                        .collect();
+                    let separator = if comment.is_empty() { "" } else { " " };
+                    Some(format!("{}{}{}", fields.join(" "), separator, comment))
+
+                } else {
+                    None
+                }
+            },
+            _ => None
+        };
+
+        changed |= rewritten.is_some();
+        lines.push(rewritten.unwrap_or_else(|| line.to_owned()));
     }
 
-    pub fn get_default_toolchain_name(&self) -> Option<&str> {
-        self.default.as_ref().map(|v| v.as_str())
+    if !changed {
+        return Ok(None);
     }
 
-    pub fn set_default_toolchain_name(&mut self, toolchain_name: String) {
-        self.default = Some(toolchain_name);
+    let mut new_contents = lines.join("\n");
+    if contents.ends_with('\n') {
+        new_contents.push('\n');
     }
 
-    pub fn add_toolchain(&mut self, toolchain_name: String, java_home: PathBuf) {
-        let java_home = make_absolute(java_home);
-        if self.toolchains.is_none() {
-            self.toolchains = Some(HashMap::new());
-        }
+    Ok(Some(new_contents))
+}
+
+///
+/// Symlinks the link path to the binary. The target is relative when both are in the same directory, so that the
+/// installation can be moved.
+///
+fn symlink_binary(binary_path: &Path, link_path: &Path) -> io::Result<()> {
+    let target = match (binary_path.parent(), link_path.parent(), binary_path.file_name()) {
+        (Some(binary_dir), Some(link_dir), Some(file_name)) if binary_dir == link_dir => Path::new(file_name),
+        _ => binary_path
+    };
 
-        self.toolchains.as_mut().unwrap().insert(toolchain_name, JavaToolchain::new(java_home));
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link_path)
     }
 
-    pub fn get_toolchains(&self) -> impl Iterator<Item = (&String, &JavaToolchain)> {
-        self.toolchains
-            .iter()
-            .map(|i| i.iter())
-            .flatten()
+    #[cfg(windows)]
+    {
+        std::os::windows::fs::symlink_file(target, link_path)
     }
+}
 
-    pub fn remove_toolchain(&mut self, toolchain_name: &str) {
-        if let Some(toolchains) = self.toolchains.as_mut() {
-            toolchains.remove(toolchain_name);
-        }
+///
+/// Returns the path the running binary was invoked through, from argv[0], searching PATH if it is a bare name.
+///
+fn get_invoked_path() -> Option<PathBuf> {
+    let arg0 = PathBuf::from(env::args_os().next()?);
+    if arg0.components().count() > 1 {
+        return Some(make_absolute(arg0));
     }
 
-    pub fn add_override(&mut self, path: &Path, toolchain_name: String) {
-        if self.overrides.is_none() {
-            self.overrides = Some(Vec::new());
+    #[cfg(target_os="windows")]
+    let arg0 = arg0.with_extension("exe");
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&arg0))
+        .find(|path| path.is_file())
+        .map(make_absolute)
+}
+
+///
+/// Returns the toolchain selected for the current shell session through JVMS_TOOLCHAIN, if any.
+///
+pub fn get_session_toolchain() -> Option<String> {
+    env::var(TOOLCHAIN_ENV).ok().filter(|toolchain_name| !toolchain_name.is_empty())
+}
+
+///
+/// Gets the directory that bounds upward searches for project files, preferring the provided --root argument over
+/// the JVMS_ROOT environment variable. Without either, searches stop at the filesystem root.
+///
+pub fn get_search_root(root_arg: Option<PathBuf>) -> Option<PathBuf> {
+    root_arg.or_else(|| {
+        env::var_os(SEARCH_ROOT_ENV)
+            .filter(|root| !root.is_empty())
+            .map(PathBuf::from)
+    })
+}
+
+impl JvmsInstallation {
+
+    pub fn new(installation_path: PathBuf) -> JvmsInstallation {
+        JvmsInstallation {
+            installation_path,
+            config_file_path: None,
+            config_lock: RefCell::new(None)
         }
+    }
 
-        self.overrides
-            .as_mut()
-            .unwrap()
-            .push(JvmsOverride {
-                path: make_absolute(path),
-                toolchain: toolchain_name
-            });
+    ///
+    /// Uses the configuration file instead of the one in the installation directory. Its temporary and lock files
+    /// are kept next to it, and it gets its own cache directory.
+    ///
+    pub fn with_config_file(mut self, config_file_path: impl AsRef<Path>) -> JvmsInstallation {
+        self.config_file_path = Some(make_absolute(config_file_path));
+        self
     }
 
-    pub fn clean_overrides(&mut self) {
-        if let Some(overrides) = self.overrides.as_mut() {
-            overrides.retain(|o| o.path.exists());
+    ///
+    /// Finds the installation of the running binary.
+    ///
+    /// Depending on the platform, current_exe may report the path of a symlink the binary was invoked through rather
+    /// than the binary itself. The resolved location of the binary is tried first, then the directory of the path
+    /// it was invoked through, then JVMS_INSTALL_DIR. The first that contains a configuration file or shims is used,
+    /// falling back to the resolved location if none do.
+    ///
+    /// The configuration file named by JVMS_CONFIG, if set, is used instead of the one of the installation.
+    ///
+    pub fn get_current_installation() -> JvmsInstallation {
+        let installation = JvmsInstallation::find_current_installation();
+        match env::var_os(CONFIG_ENV).filter(|path| !path.is_empty()) {
+            Some(config_file_path) => {
+                debug!("Using configuration file {:?} from {}", config_file_path, CONFIG_ENV);
+                installation.with_config_file(PathBuf::from(config_file_path))
+            },
+            None => installation
         }
     }
 
-    pub fn get_overrides(&self) -> Option<&[JvmsOverride]> {
-        self.overrides.as_ref().map(|o| &**o)
+    fn find_current_installation() -> JvmsInstallation {
+        let current_exe =
+            env::current_exe().expect("Could not locate the currently executing binary.");
+        let install_dir_env = env::var_os(INSTALL_DIR_ENV).filter(|dir| !dir.is_empty()).map(PathBuf::from);
+        JvmsInstallation::find_installation(current_exe, get_invoked_path(), install_dir_env)
     }
 
-    pub fn remove_override(&mut self, override_path: &Path) {
-        let override_path = make_absolute(override_path);
-        if let Some(overrides) = self.overrides.as_mut() {
-            overrides.retain(|o| o.path != override_path)
+    ///
+    /// Finds the installation of the binary at current_exe that was invoked through invoked_path, falling back to
+    /// install_dir. See get_current_installation.
+    ///
+    fn find_installation(current_exe: PathBuf, invoked_path: Option<PathBuf>, install_dir: Option<PathBuf>) -> JvmsInstallation {
+        let current_exe = fs::canonicalize(&current_exe).unwrap_or(current_exe);
+
+        let installation_dir =
+            current_exe.parent().expect("Could not locate the currently executing installation directory.");
+        let installation = JvmsInstallation::new(installation_dir.to_path_buf());
+        if installation.is_installed() {
+            debug!("Using installation {:?} of the current binary", installation.installation_path);
+            return installation;
         }
+
+        let invoked_dir = invoked_path.and_then(|path| path.parent().map(Path::to_path_buf));
+        let installation = invoked_dir.into_iter()
+            .chain(install_dir)
+            .map(JvmsInstallation::new)
+            .find(JvmsInstallation::is_installed)
+            .unwrap_or(installation);
+
+        debug!("Using installation {:?}", installation.installation_path);
+        installation
     }
 
-    pub fn validate_configuration(&self) -> Result<()> {
-        if self.toolchains.is_none() || self.toolchains.as_ref().unwrap().is_empty() {
-            return Err(JvmsError::InvalidConfiguration("Configuration has no installations.".to_owned()));
-        }
+    ///
+    /// Checks whether the installation directory has a configuration file or any shims.
+    ///
+    fn is_installed(&self) -> bool {
+        self.get_installation_config_file_path().is_file() || self.get_missing_shims().len() < Shim::get_shims().len()
+    }
 
-        for toolchain in self.toolchains.as_ref().unwrap() {
-            if !toolchain.1.java_home.exists() {
-                return Err(JvmsError::InvalidConfiguration(format!("Installation {} does not point to a valid java home.", toolchain.0)));
-            }
-        }
+    pub fn get_installation_path(&self) -> &Path {
+        self.installation_path.as_path()
+    }
 
-        if let Some(default) = self.get_default_toolchain_name() {
-            if !self.has_toolchain(default) {
-                return Err(JvmsError::InvalidConfiguration(format!("Default installation references an unknown installation: {}", default)))
-            }
+    pub fn install_binaries(&self, link_mode: LinkMode) -> Result<()> {
+        let jvms_source_binary = env::current_exe().expect("Failed to get current executing binary.");
 
-        } else {
-            return Err(JvmsError::InvalidConfiguration("Configuration does not have a default installation.".to_owned()));
-        }
+        fs::create_dir_all(&self.installation_path)?;
 
-        if let Some(overrides) = self.overrides.as_ref() {
-            for o in overrides {
-                if !self.has_toolchain(&o.toolchain) {
-                    return Err(JvmsError::InvalidConfiguration(format!("Override at {:?} references an unknown installation: {}", o.path, o.toolchain)));
-                }
-            }
+        //
+        // Copy the jvms binary into the installation path.
+        //
+
+        let jvms_dest_binary = self.get_binary_path("jvms");
+
+        println!("Copying {:?} to {:?}", jvms_source_binary, jvms_dest_binary);
+        fs::copy(&jvms_source_binary, &jvms_dest_binary)?;
+
+        //
+        // Link all shims to the destination jvms binary.
+        //
+
+        for shim in Shim::get_shims() {
+            self.link_shim(shim, link_mode)?;
         }
 
         Ok(())
     }
 
-}
+    ///
+    /// Lists the shims that are missing from the installation directory.
+    ///
+    pub fn get_missing_shims(&self) -> Vec<&'static Shim> {
+        Shim::get_shims()
+            .iter()
+            .filter(|shim| !self.has_binary(shim.name()))
+            .collect()
+    }
+
+    ///
+    /// Checks whether the installation directory has a binary with the provided name.
+    ///
+    pub fn has_binary(&self, binary_name: &str) -> bool {
+        self.get_binary_path(binary_name).exists()
+    }
+
+    ///
+    /// Checks whether `jvms rehash` generated a shim with the provided name. Other binaries in the installation
+    /// directory, like backups or unrelated tools, are not shims.
+    ///
+    pub fn is_generated_shim(&self, name: &str) -> Result<bool> {
+        Ok(self.read_generated_shims()?.iter().any(|shim_name| shim_name == name))
+    }
+
+    ///
+    /// Reads the names of the shims generated by `jvms rehash`.
+    ///
+    fn read_generated_shims(&self) -> Result<Vec<String>> {
+        match fs::read_to_string(self.installation_path.join(GENERATED_SHIMS_FILE_NAME)) {
+            Ok(contents) => Ok(contents.lines().map(str::trim).filter(|line| !line.is_empty()).map(str::to_owned).collect()),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(error) => Err(error.into())
+        }
+    }
+
+    ///
+    /// Brings the shims of the installation in line with the tools of the registered toolchains: a shim is created
+    /// for every tool that does not have one, and generated shims whose tool no longer exists in any toolchain are
+    /// removed. Files in the installation directory that jvms did not create are left alone. Returns the names of
+    /// the created and removed shims.
+    ///
+    pub fn rehash(&self, config: &JvmsConfiguration, link_mode: LinkMode) -> Result<(Vec<String>, Vec<String>)> {
+        let shims = Shim::get_toolchain_shims(config);
+        let is_wanted = |name: &str| shims.iter().any(|shim| shim.name() == name);
+        let is_core = |name: &str| Shim::get_shims().iter().any(|shim| shim.name() == name);
+
+        let mut generated_shims = Vec::new();
+        let mut removed_shims = Vec::new();
+        for name in self.read_generated_shims()? {
+            if is_wanted(&name) {
+                generated_shims.push(name);
+
+            } else if !is_core(&name) {
+                let shim_path = self.get_binary_path(&name);
+                if fs::symlink_metadata(&shim_path).is_ok() {
+                    println!("Removing {:?}", shim_path);
+                    fs::remove_file(&shim_path)?;
+                }
+
+                removed_shims.push(name);
+            }
+        }
+
+        let mut created_shims = Vec::new();
+        for shim in &shims {
+            if fs::symlink_metadata(self.get_binary_path(shim.name())).is_ok() {
+                continue;
+            }
+
+            self.link_shim(shim, link_mode)?;
+            created_shims.push(shim.name().to_owned());
+            if !is_core(shim.name()) && !generated_shims.iter().any(|name| name == shim.name()) {
+                generated_shims.push(shim.name().to_owned());
+            }
+        }
+
+        generated_shims.sort();
+        let contents: String = generated_shims.iter().map(|name| format!("{}\n", name)).collect();
+        fs::write(self.installation_path.join(GENERATED_SHIMS_FILE_NAME), contents)?;
+
+        Ok((created_shims, removed_shims))
+    }
+
+    ///
+    /// Links the shim to the jvms binary of the installation.
+    ///
+    pub fn link_shim(&self, shim: &Shim, link_mode: LinkMode) -> Result<()> {
+        let jvms_binary = self.get_binary_path("jvms");
+        let shim_path = self.get_binary_path(shim.name());
+
+        match link_mode {
+            LinkMode::Hard => {
+                println!("Linking {:?} to {:?}", shim_path, jvms_binary);
+                fs::hard_link(&jvms_binary, &shim_path)?;
+            },
+            LinkMode::Symbolic => {
+                println!("Symlinking {:?} to {:?}", shim_path, jvms_binary);
+                symlink_binary(&jvms_binary, &shim_path)?;
+            },
+            LinkMode::Copy => {
+                println!("Copying {:?} to {:?}", jvms_binary, shim_path);
+                fs::copy(&jvms_binary, &shim_path)?;
+            },
+            LinkMode::Auto => {
+                //
+                // An existing shim fails every strategy the same way, and falling through to a copy would overwrite
+                // it, so only other errors move on to the next strategy.
+                //
+
+                println!("Linking {:?} to {:?}", shim_path, jvms_binary);
+                let error = match fs::hard_link(&jvms_binary, &shim_path) {
+                    Err(error) if error.kind() != io::ErrorKind::AlreadyExists => error,
+                    result => return Ok(result?)
+                };
+
+                eprintln!("Failed to hard link {:?} ({}), symlinking it instead", shim_path, error);
+                let error = match symlink_binary(&jvms_binary, &shim_path) {
+                    Err(error) if error.kind() != io::ErrorKind::AlreadyExists => error,
+                    result => return Ok(result?)
+                };
+
+                eprintln!("Failed to symlink {:?} ({}), copying the binary instead", shim_path, error);
+                fs::copy(&jvms_binary, &shim_path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Replaces the shims of the installation, including those generated by `jvms rehash`, with fresh links to its
+    /// existing jvms binary, leaving the binary and configuration untouched. Returns the number of shims replaced.
+    ///
+    pub fn reinstall_shims(&self, link_mode: LinkMode) -> Result<usize> {
+        let jvms_binary = self.get_binary_path("jvms");
+        if !jvms_binary.is_file() {
+            return Err(JvmsError::InvalidConfiguration(format!("{:?} does not contain a jvms binary.", self.installation_path)));
+        }
+
+        let mut shims = Shim::get_shims().to_vec();
+        shims.extend(self.read_generated_shims()?.into_iter().map(Shim::new));
+        for shim in &shims {
+            //
+            // symlink_metadata also finds dangling symlinks, which exists() reports as missing.
+            //
+
+            let shim_path = self.get_binary_path(shim.name());
+            if fs::symlink_metadata(&shim_path).is_ok() {
+                fs::remove_file(&shim_path)?;
+            }
+
+            self.link_shim(shim, link_mode)?;
+        }
+
+        Ok(shims.len())
+    }
+
+    ///
+    /// Removes the shims and the jvms binary of the installation, and with remove_config also its configuration,
+    /// the toolchains installed into it, and its cache. The installation directory itself is removed if nothing
+    /// else is left in it.
+    ///
+    pub fn uninstall(&self, remove_config: bool) -> Result<()> {
+        let jvms_binary = self.get_binary_path("jvms");
+        if !jvms_binary.is_file() {
+            return Err(JvmsError::InvalidConfiguration(format!("{:?} does not contain a jvms binary.", self.installation_path)));
+        }
+
+        let mut shims = Shim::get_shims().to_vec();
+        shims.extend(self.read_generated_shims()?.into_iter().map(Shim::new));
+        for shim in &shims {
+            let shim_path = self.get_binary_path(shim.name());
+            if fs::symlink_metadata(&shim_path).is_ok() {
+                println!("Removing {:?}", shim_path);
+                fs::remove_file(&shim_path)?;
+            }
+        }
+
+        let mut files = vec![self.installation_path.join(GENERATED_SHIMS_FILE_NAME)];
+        if remove_config {
+            files.push(self.installation_path.join(CONFIG_FILE_NAME));
+            files.push(self.installation_path.join(LEGACY_CONFIG_FILE_NAME));
+            files.push(self.installation_path.join(CONFIG_TEMP_FILE_NAME));
+            files.push(self.installation_path.join(CONFIG_LOCK_FILE_NAME));
+
+            for directory in Some(self.get_toolchains_path()).into_iter().chain(self.get_cache_path()) {
+                if directory.is_dir() {
+                    println!("Removing {:?}", directory);
+                    fs::remove_dir_all(&directory)?;
+                }
+            }
+        }
+
+        for file in files {
+            if file.is_file() {
+                println!("Removing {:?}", file);
+                fs::remove_file(&file)?;
+            }
+        }
+
+        //
+        // Windows does not allow deleting the binary of a running process, so uninstalling the installation that is
+        // running leaves the binary behind there.
+        //
+
+        println!("Removing {:?}", jvms_binary);
+        if let Err(error) = fs::remove_file(&jvms_binary) {
+            if cfg!(windows) && error.kind() == io::ErrorKind::PermissionDenied {
+                eprintln!("Could not remove the running jvms binary, delete {:?} once jvms has exited", jvms_binary);
+
+            } else {
+                return Err(error.into());
+            }
+        }
+
+        let _ = fs::remove_dir(&self.installation_path);
+        Ok(())
+    }
+
+    ///
+    /// Returns the link mode to use when none is given on the command line, from JVMS_LINK_MODE, defaulting to
+    /// auto.
+    ///
+    pub fn get_default_link_mode() -> Result<LinkMode> {
+        match env::var(LINK_MODE_ENV) {
+            Ok(link_mode) if !link_mode.is_empty() => {
+                link_mode.parse()
+                    .map_err(|error| JvmsError::InvalidConfiguration(format!("{} is invalid: {}", LINK_MODE_ENV, error)))
+            },
+            _ => Ok(LinkMode::Auto)
+        }
+    }
+
+    fn get_binary_path(&self, binary_name: &str) -> PathBuf {
+        let path = self.installation_path.join(binary_name);
+
+        #[cfg(target_os="windows")]
+        let path = path.with_extension("exe");
+
+        path
+    }
+
+    ///
+    /// Loads the configuration file, or an empty configuration if there is none.
+    ///
+    /// Loading takes no lock so that shims never wait on a writer. Instead the file is read in one shot through a
+    /// single handle, along with its modification time, and parsed from memory. A writer that replaces the file by
+    /// renaming a complete new file over it is therefore observed either entirely before or entirely after the
+    /// rename, never as a mix of the two.
+    ///
+    /// When the user configuration exists, it is layered over the configuration of the installation, see
+    /// get_system_config_file_path.
+    ///
+    pub fn load_configuration(&self) -> Result<JvmsConfiguration> {
+        let config = read_configuration_file(&self.get_config_file_path())?;
+        let system_config_file_path = match self.get_system_config_file_path() {
+            Some(system_config_file_path) => system_config_file_path,
+            None => return Ok(config)
+        };
+
+        let system_config = read_configuration_file(&system_config_file_path)?;
+        debug!("Layering the user configuration over the system configuration {:?}", system_config_file_path);
+        let mut layered_config = system_config.clone();
+        layered_config.overlay(config);
+        layered_config.system_layer = Some((system_config_file_path, Box::new(system_config)));
+        Ok(layered_config)
+    }
+
+    ///
+    /// Returns the modification time of the configuration file, or None if it does not exist.
+    ///
+    pub fn config_mtime(&self) -> Result<Option<SystemTime>> {
+        match fs::metadata(self.get_config_file_path()) {
+            Ok(metadata) => Ok(Some(metadata.modified()?)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into())
+        }
+    }
+
+    ///
+    /// Reloads the configuration if the configuration file changed since it was loaded, returning whether a reload
+    /// happened. Only the file's modification time is checked, so calling this repeatedly is cheap.
+    ///
+    pub fn reload_if_changed(&self, config: &mut JvmsConfiguration) -> Result<bool> {
+        let current_mtime = self.config_mtime()?;
+        if current_mtime == config.loaded_mtime.get() {
+            return Ok(false);
+        }
+
+        *config = self.load_configuration()?;
+        Ok(true)
+    }
+
+    ///
+    /// Saves the configuration by writing it to a temporary file and renaming that over the configuration file, so
+    /// that readers and a crash midway only ever see a complete file.
+    ///
+    /// Writers hold an advisory lock on a separate lock file while saving, see lock_configuration. The save fails if
+    /// another process saved the configuration since it was loaded, rather than silently dropping that process's
+    /// changes, which can only happen if the lock was not taken before loading.
+    ///
+    pub fn save_configuration(&self, config: &JvmsConfiguration, force: bool) -> Result<()> {
+        if !force {
+            config.validate_configuration()?;
+        }
+
+        self.lock_configuration()?;
+        if self.config_mtime()? != config.loaded_mtime.get() {
+            return Err(JvmsError::InvalidConfiguration(
+                "The configuration file was changed by another jvms process since it was loaded, run the command again.".to_owned()
+            ));
+        }
+
+        let config_file_path = self.get_config_file_path();
+        let user_layer = config.get_user_layer()?;
+        let temp_file_path = self.get_config_sibling_path(CONFIG_TEMP_FILE_NAME, "tmp");
+        let result = write_configuration_file(&temp_file_path, user_layer.as_ref().unwrap_or(config), is_toml_file(&config_file_path))
+            .and_then(|_| {
+                fs::rename(&temp_file_path, &config_file_path)
+                    .map_err(|io_error| {
+                        JvmsError::InvalidConfiguration(format!("Failed to replace jvms configuration file: {:?}", io_error))
+                    })
+            });
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_file_path);
+        }
+
+        result?;
+        config.loaded_mtime.set(self.config_mtime()?);
+        Ok(())
+    }
+
+    ///
+    /// Takes the advisory lock guarding writes to the configuration file, waiting for other writers to finish, unless
+    /// this process already holds it. The lock is held until the process exits or unlock_configuration is called.
+    /// Taking it before loading the configuration makes the whole load, modify, and save cycle exclusive.
+    ///
+    /// Readers never take the lock, so shims are not held up by writers.
+    ///
+    pub fn lock_configuration(&self) -> Result<()> {
+        if self.config_lock.borrow().is_some() {
+            return Ok(());
+        }
+
+        let lock_file =
+            fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(self.get_config_sibling_path(CONFIG_LOCK_FILE_NAME, "lock"))
+                .map_err(|io_error| {
+                    JvmsError::InvalidConfiguration(format!("Failed to open jvms configuration lock file: {:?}", io_error))
+                })?;
+
+        lock_file.lock()
+            .map_err(|io_error| {
+                JvmsError::InvalidConfiguration(format!("Failed to lock jvms configuration file: {:?}", io_error))
+            })?;
+
+        *self.config_lock.borrow_mut() = Some(lock_file);
+        Ok(())
+    }
+
+    ///
+    /// Releases the lock taken by lock_configuration, if this process holds it, so that other writers can proceed.
+    /// Processes that keep running after changing the configuration, like `jvms ui`, release it once a change is
+    /// done.
+    ///
+    pub fn unlock_configuration(&self) {
+        self.config_lock.borrow_mut().take();
+    }
+
+    pub fn get_toolchains_path(&self) -> PathBuf {
+        self.installation_path.join("toolchains")
+    }
+
+    ///
+    /// Returns the directory of the managed toolchain storage that holds the java home, if it is stored there.
+    ///
+    pub fn get_managed_toolchain_dir(&self, java_home: &Path) -> Option<PathBuf> {
+        let toolchains_path = make_absolute(self.get_toolchains_path());
+        let java_home = make_absolute(java_home);
+        let dir_name = java_home.strip_prefix(&toolchains_path).ok()?.components().next()?;
+        Some(toolchains_path.join(dir_name))
+    }
+
+    ///
+    /// Lists the directories of the managed toolchain storage that hold the java home of no registered toolchain.
+    /// Hidden entries, such as the staging directories of installs in progress, are left out.
+    ///
+    pub fn find_unreferenced_toolchain_dirs(&self, config: &JvmsConfiguration) -> Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(self.get_toolchains_path()) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(error) => return Err(error.into())
+        };
+
+        let referenced_dirs: Vec<PathBuf> = config.get_toolchains()
+            .filter_map(|(_, toolchain)| self.get_managed_toolchain_dir(&toolchain.java_home))
+            .collect();
+
+        let mut unreferenced_dirs = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let path = make_absolute(entry.path());
+            if entry.file_name().to_string_lossy().starts_with('.') || !path.is_dir() {
+                continue;
+            }
+
+            if !referenced_dirs.contains(&path) {
+                unreferenced_dirs.push(path);
+            }
+        }
+
+        unreferenced_dirs.sort();
+        Ok(unreferenced_dirs)
+    }
+
+    ///
+    /// Copies a java home into the managed toolchain storage of the installation, returning the path of the copy.
+    /// A partial copy is removed if copying fails.
+    ///
+    pub fn copy_toolchain(&self, toolchain_name: &str, java_home: &Path) -> Result<PathBuf> {
+        if !java_home.is_dir() {
+            return Err(JvmsError::InvalidToolchain(format!("{:?} is not a directory.", java_home)));
+        }
+
+        let destination = self.get_new_toolchain_path(toolchain_name)?;
+
+        println!("Copying {:?} to {:?}", java_home, destination);
+        let mut copied_files = 0;
+        if let Err(error) = copy_dir_recursive(java_home, &destination, &mut copied_files) {
+            println!();
+            let _ = fs::remove_dir_all(&destination);
+            return Err(error);
+        }
+
+        println!("\rCopied {} files.", copied_files);
+        Ok(destination)
+    }
+
+    ///
+    /// Extracts a JDK archive into the managed toolchain storage of the installation, returning the path of the
+    /// extracted directory. Archives that wrap the JDK in a single top-level directory, as most vendors do, have it
+    /// unwrapped. A partial extraction is removed if extracting fails.
+    ///
+    pub fn extract_toolchain(&self, toolchain_name: &str, archive_path: &Path) -> Result<PathBuf> {
+        let destination = self.get_new_toolchain_path(toolchain_name)?;
+        let staging_path = self.get_toolchains_path().join(format!(".{}.partial", toolchain_name));
+        if staging_path.exists() {
+            fs::remove_dir_all(&staging_path)?;
+        }
+
+        println!("Extracting {:?} to {:?}", archive_path, destination);
+        let result = archive::extract(archive_path, &staging_path)
+            .and_then(|_| {
+                let entries = fs::read_dir(&staging_path)?.collect::<io::Result<Vec<_>>>()?;
+                let extracted_root = match entries.as_slice() {
+                    [entry] if entry.path().is_dir() => entry.path(),
+                    _ => staging_path.clone()
+                };
+
+                fs::rename(&extracted_root, &destination)?;
+                Ok(())
+            });
+
+        if staging_path.exists() {
+            let _ = fs::remove_dir_all(&staging_path);
+        }
+
+        result.map(|_| destination)
+    }
+
+    ///
+    /// Returns the path a new managed toolchain with the provided name is stored at, checking that the name is a
+    /// single path component and that nothing is stored there yet.
+    ///
+    fn get_new_toolchain_path(&self, toolchain_name: &str) -> Result<PathBuf> {
+        let mut name_components = Path::new(toolchain_name).components();
+        if !matches!((name_components.next(), name_components.next()), (Some(Component::Normal(_)), None)) {
+            return Err(JvmsError::InvalidToolchain(format!("{} cannot be used as a directory name.", toolchain_name)));
+        }
+
+        let destination = self.get_toolchains_path().join(toolchain_name);
+        if destination.exists() {
+            return Err(JvmsError::InvalidToolchain(format!("{:?} already exists.", destination)));
+        }
+
+        Ok(destination)
+    }
+
+    ///
+    /// Returns the path of the configuration file jvms reads and writes: the one chosen with --config or JVMS_CONFIG
+    /// if any, then the user configuration if it exists, and otherwise the configuration of the installation.
+    ///
+    pub fn get_config_file_path(&self) -> PathBuf {
+        if let Some(config_file_path) = self.config_file_path.as_ref() {
+            return config_file_path.clone();
+        }
+
+        self.get_user_layer_path().unwrap_or_else(|| self.get_installation_config_file_path())
+    }
+
+    ///
+    /// Returns the path of the configuration of the installation when the user configuration is layered over it,
+    /// which is whenever the user configuration exists and no configuration file was chosen with --config or
+    /// JVMS_CONFIG. The system configuration is then only read, changes are saved to the user configuration.
+    ///
+    pub fn get_system_config_file_path(&self) -> Option<PathBuf> {
+        self.get_user_layer_path().map(|_| self.get_installation_config_file_path())
+    }
+
+    ///
+    /// Returns the per-user configuration file, jvms/jvms.toml in the user's configuration directory: $XDG_CONFIG_HOME
+    /// or ~/.config on Linux, ~/Library/Application Support on macOS, and %APPDATA% on Windows.
+    ///
+    pub fn get_user_config_file_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("jvms").join(CONFIG_FILE_NAME))
+    }
+
+    ///
+    /// Returns the user configuration if it is layered over the configuration of the installation.
+    ///
+    fn get_user_layer_path(&self) -> Option<PathBuf> {
+        if self.config_file_path.is_some() {
+            return None;
+        }
+
+        JvmsInstallation::get_user_config_file_path()
+            .filter(|user_config_file_path| {
+                user_config_file_path.is_file() && *user_config_file_path != self.get_installation_config_file_path()
+            })
+    }
+
+    ///
+    /// Returns the configuration file of the installation: jvms.toml, unless only a legacy jvms.conf exists.
+    ///
+    fn get_installation_config_file_path(&self) -> PathBuf {
+        let config_file_path = self.installation_path.join(CONFIG_FILE_NAME);
+        let legacy_config_file_path = self.installation_path.join(LEGACY_CONFIG_FILE_NAME);
+        if !config_file_path.exists() && legacy_config_file_path.exists() {
+            legacy_config_file_path
+
+        } else {
+            config_file_path
+        }
+    }
+
+    ///
+    /// Returns the path of a file kept next to the configuration file: the file name in the installation directory,
+    /// or the name of a configuration file elsewhere followed by the extension.
+    ///
+    fn get_config_sibling_path(&self, file_name: &str, extension: &str) -> PathBuf {
+        if self.config_file_path.is_none() && self.get_user_layer_path().is_none() {
+            return self.installation_path.join(file_name);
+        }
+
+        let mut path = self.get_config_file_path().into_os_string();
+        path.push(".");
+        path.push(extension);
+        PathBuf::from(path)
+    }
+
+    ///
+    /// Converts a legacy jvms.conf into jvms.toml, keeping the JSON file as jvms.conf.bak. Returns the path of the
+    /// backup.
+    ///
+    pub fn migrate_configuration(&self) -> Result<PathBuf> {
+        if let Some(config_file_path) = self.config_file_path.as_ref() {
+            return Err(JvmsError::InvalidConfiguration(format!("Only the configuration of the installation can be migrated, not {:?}.", config_file_path)));
+        }
+
+        let config_file_path = self.installation_path.join(CONFIG_FILE_NAME);
+        let legacy_config_file_path = self.installation_path.join(LEGACY_CONFIG_FILE_NAME);
+        if config_file_path.exists() {
+            return Err(JvmsError::InvalidConfiguration(format!("{:?} already exists.", config_file_path)));
+        }
+
+        if !legacy_config_file_path.is_file() {
+            return Err(JvmsError::InvalidConfiguration(format!("There is no {:?} to migrate.", legacy_config_file_path)));
+        }
+
+        self.lock_configuration()?;
+        let config = JvmsConfiguration::load(&legacy_config_file_path)?;
+        let temp_file_path = self.installation_path.join(CONFIG_TEMP_FILE_NAME);
+        let result = write_configuration_file(&temp_file_path, &config, true)
+            .and_then(|_| {
+                fs::rename(&temp_file_path, &config_file_path)
+                    .map_err(|io_error| {
+                        JvmsError::InvalidConfiguration(format!("Failed to create jvms configuration file: {:?}", io_error))
+                    })
+            });
+
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_file_path);
+        }
+
+        result?;
+
+        //
+        // jvms.toml takes precedence from here on, so a failure to move the old file aside is harmless.
+        //
+
+        let backup_path = legacy_config_file_path.with_extension("conf.bak");
+        fs::rename(&legacy_config_file_path, &backup_path)?;
+        Ok(backup_path)
+    }
+
+    ///
+    /// Returns the cache directory of this installation, in the platform's cache location: `$XDG_CACHE_HOME/jvms`
+    /// on Linux, `~/Library/Caches/jvms` on macOS, and `%LOCALAPPDATA%\jvms\cache` on Windows. Each configuration
+    /// file gets its own subdirectory so that installations and configurations chosen with --config don't share
+    /// cached data. Returns None if the platform has no cache location.
+    ///
+    pub fn get_cache_path(&self) -> Option<PathBuf> {
+        #[cfg(target_os="windows")]
+        let cache_root = dirs::cache_dir()?.join("jvms").join("cache");
+
+        #[cfg(not(target_os="windows"))]
+        let cache_root = dirs::cache_dir()?.join("jvms");
+
+        let config_file_path = match self.config_file_path.as_ref() {
+            Some(config_file_path) => config_file_path.clone(),
+            None => make_absolute(self.installation_path.join(LEGACY_CONFIG_FILE_NAME))
+        };
+
+        //
+        // FNV-1a of the configuration path, which unlike the std hashers is stable across builds. The default
+        // configuration is keyed by the legacy file name regardless of format, so that migrating keeps the cache.
+        //
+
+        let key = config_file_path.to_string_lossy()
+            .bytes()
+            .fold(0xcbf29ce484222325u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+
+        Some(cache_root.join(format!("{:016x}", key)))
+    }
+
+}
+
+impl FromStr for LinkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "hard" => Ok(LinkMode::Hard),
+            "sym" => Ok(LinkMode::Symbolic),
+            "copy" => Ok(LinkMode::Copy),
+            "auto" => Ok(LinkMode::Auto),
+            _ => Err(format!("Unknown link mode: {}", s))
+        }
+    }
+}
+
+impl ResolutionSource {
+
+    ///
+    /// A one word description of the source, for compact output such as shell prompts.
+    ///
+    pub fn get_label(&self) -> &'static str {
+        match self {
+            ResolutionSource::ProjectHome(_) => "home",
+            ResolutionSource::ProjectConfig(_) => "project",
+            ResolutionSource::ToolVersions(_) => "tool-versions",
+            ResolutionSource::JavaVersion(_) => "java-version",
+            ResolutionSource::Override(_) => "override",
+            ResolutionSource::Default => "default",
+            ResolutionSource::GlobalJavaVersion(_) => "global",
+            ResolutionSource::Session => "session"
+        }
+    }
+
+    ///
+    /// A description of the source including the file or override that made the selection.
+    ///
+    pub fn get_description(&self) -> String {
+        match self {
+            ResolutionSource::ProjectHome(path) => format!("{} file {:?}", PROJECT_HOME_FILE_NAME, path),
+            ResolutionSource::ProjectConfig(path) => format!("project configuration {:?}", path),
+            ResolutionSource::ToolVersions(path) => format!("{} file {:?}", TOOL_VERSIONS_FILE_NAME, path),
+            ResolutionSource::JavaVersion(path) => format!("{} file {:?}", JAVA_VERSION_FILE_NAME, path),
+            ResolutionSource::Override(path) => format!("override for {:?}", path),
+            ResolutionSource::Default => "default toolchain".to_owned(),
+            ResolutionSource::GlobalJavaVersion(path) => format!("global {} file {:?}", JAVA_VERSION_FILE_NAME, path),
+            ResolutionSource::Session => format!("{} environment variable", TOOLCHAIN_ENV)
+        }
+    }
+
+    ///
+    /// The file or overridden directory that made the selection, if any.
+    ///
+    pub fn get_path(&self) -> Option<&Path> {
+        match self {
+            ResolutionSource::ProjectHome(path)
+                | ResolutionSource::ProjectConfig(path)
+                | ResolutionSource::ToolVersions(path)
+                | ResolutionSource::JavaVersion(path)
+                | ResolutionSource::Override(path)
+                | ResolutionSource::GlobalJavaVersion(path) => Some(path),
+            ResolutionSource::Default | ResolutionSource::Session => None
+        }
+    }
+
+}
+
+impl<'a> ResolvedToolchain<'a> {
+
+    fn new((name, toolchain): (&'a str, &'a JavaToolchain), source: ResolutionSource) -> ResolvedToolchain<'a> {
+        debug!("Selected toolchain {} from the {}", name, source.get_description());
+        ResolvedToolchain { name: Some(name), toolchain: Cow::Borrowed(toolchain), source }
+    }
+
+    fn from_project_home(path: PathBuf, java_home: PathBuf) -> ResolvedToolchain<'a> {
+        debug!("Selected java home {:?} from the {} file {:?}", java_home, PROJECT_HOME_FILE_NAME, path);
+        let mut toolchain = JavaToolchain::new(java_home);
+        toolchain.record_metadata();
+        ResolvedToolchain { name: None, toolchain: Cow::Owned(toolchain), source: ResolutionSource::ProjectHome(path) }
+    }
+
+    ///
+    /// Describes the selection for messages, by the name of the toolchain or the java home of a .jvms-home file.
+    ///
+    pub fn get_display_name(&self) -> String {
+        match self.name {
+            Some(name) => name.to_owned(),
+            None => self.toolchain.java_home.display().to_string()
+        }
+    }
+
+}
+
+impl ResolvedHome<'_> {
+
+    ///
+    /// Describes the selection for messages, by the name of the toolchain or the java home of a .jvms-home file.
+    ///
+    pub fn get_display_name(&self) -> String {
+        match self.name {
+            Some(name) => name.to_owned(),
+            None => self.java_home.display().to_string()
+        }
+    }
+
+}
+
+impl OverrideCleanPolicy {
+
+    fn cleans_missing_directories(self) -> bool {
+        matches!(self, OverrideCleanPolicy::MissingDirectory | OverrideCleanPolicy::All)
+    }
+
+    fn cleans_missing_toolchains(self) -> bool {
+        matches!(self, OverrideCleanPolicy::MissingToolchain | OverrideCleanPolicy::All)
+    }
+
+}
+
+impl Default for JvmsConfiguration {
+
+    fn default() -> Self {
+        JvmsConfiguration::new()
+    }
+
+}
+
+impl JvmsConfiguration {
+
+    pub fn new() -> JvmsConfiguration {
+        JvmsConfiguration {
+            toolchains: None,
+            default: None,
+            overrides: None,
+            strict: None,
+            clear_env: None,
+            dedupe_path: None,
+            resolve_symlinks: None,
+            aliases: None,
+            version_defaults: None,
+            mirrors: None,
+            hooks: None,
+            loaded_mtime: Cell::new(None),
+            system_layer: None
+        }
+    }
+
+    pub fn load(config_file_path: &Path) -> Result<JvmsConfiguration> {
+        let (contents, _) =
+            read_snapshot(config_file_path)
+                .map_err(|io_error| {
+                    JvmsError::InvalidConfiguration(format!("Failed to open jvms configuration file: {:?}", io_error))
+                })?;
+
+        JvmsConfiguration::parse(config_file_path, &contents)
+    }
+
+    fn parse(config_file_path: &Path, contents: &[u8]) -> Result<JvmsConfiguration> {
+        if is_toml_file(config_file_path) {
+            let contents = String::from_utf8_lossy(contents);
+            return toml::from_str(&contents)
+                .map_err(|error| {
+                    JvmsError::TomlConfigParse { path: config_file_path.to_path_buf(), error }
+                });
+        }
+
+        serde_json::from_slice(contents)
+            .map_err(|error| {
+                JvmsError::ConfigParse { path: config_file_path.to_path_buf(), error }
+            })
+    }
+
+    ///
+    /// Checks whether strict resolution is enabled by the configuration or the JVMS_STRICT environment variable.
+    ///
+    /// Strict resolution disables every fallback: the configuration must validate, overrides and the default must
+    /// reference known toolchains, JVMS_PROJECT_CONFIG must point at an existing file, and the global
+    /// .java-version file is ignored.
+    ///
+    pub fn is_strict(&self) -> bool {
+        let strict_env = env::var(STRICT_ENV)
+            .map(|value| value != "0" && !value.eq_ignore_ascii_case("false"))
+            .unwrap_or(false);
+
+        strict_env || self.strict.unwrap_or(false)
+    }
+
+    pub fn get_cleared_env_vars(&self) -> &[String] {
+        self.clear_env.as_deref().unwrap_or(&[])
+    }
+
+    pub fn is_dedupe_path(&self) -> bool {
+        self.dedupe_path.unwrap_or(true)
+    }
+
+    pub fn get_mirror(&self, provider_name: &str) -> Option<&ProviderMirror> {
+        self.mirrors.as_ref().and_then(|mirrors| mirrors.get(provider_name))
+    }
+
+    pub fn is_resolve_symlinks(&self) -> bool {
+        self.resolve_symlinks.unwrap_or(false)
+    }
+
+    pub fn get_hook(&self, event: HookEvent) -> Option<&str> {
+        let hooks = self.hooks.as_ref()?;
+        let hook = match event {
+            HookEvent::PreDefault => hooks.pre_default.as_ref(),
+            HookEvent::PostDefault => hooks.post_default.as_ref(),
+            HookEvent::PreOverride => hooks.pre_override.as_ref(),
+            HookEvent::PostOverride => hooks.post_override.as_ref(),
+            HookEvent::PreLaunch => hooks.pre_launch.as_ref()
+        };
+
+        hook.map(|hook| hook.as_str()).filter(|hook| !hook.trim().is_empty())
+    }
+
+    ///
+    /// Runs the hook configured for the event, if any. A failing hook that runs before its event fails, so that the
+    /// caller can abort, while one that runs after it only prints a warning.
+    ///
+    pub fn run_hook(&self, event: HookEvent, context: &HookContext) -> Result<()> {
+        let hook = match self.get_hook(event) {
+            Some(hook) => hook,
+            None => return Ok(())
+        };
+
+        match hooks::run_hook(hook, event, context) {
+            Err(error) if !event.is_pre() => {
+                eprintln!("Warning: {}", error);
+                Ok(())
+            },
+            result => result
+        }
+    }
+
+    ///
+    /// Checks whether two paths name the same override directory, ignoring case on Windows and resolving symlinks
+    /// when resolve_symlinks is set.
+    ///
+    pub fn is_same_override_path(&self, a: &Path, b: &Path) -> bool {
+        let resolve_symlinks = self.is_resolve_symlinks();
+        comparable_path(&make_absolute(a), resolve_symlinks) == comparable_path(&make_absolute(b), resolve_symlinks)
+    }
+
+    pub fn get_toolchain(&self, toolchain_name: &str) -> Option<&JavaToolchain> {
+        self.get_toolchain_entry(toolchain_name).map(|(_, toolchain)| toolchain)
+    }
+
+    ///
+    /// Finds the name of the toolchain registered for the java home, if any. Homes that exist are compared with
+    /// symlinks resolved, so the same JDK reached through different paths is still found.
+    ///
+    pub fn find_toolchain_by_home(&self, java_home: &Path) -> Option<&str> {
+        let canonical_home = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| make_absolute(path));
+
+        let java_home = canonical_home(java_home);
+        let mut toolchains: Vec<_> = self.get_toolchains()
+            .filter(|(_, toolchain)| canonical_home(&toolchain.java_home) == java_home)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        toolchains.sort_unstable();
+        toolchains.into_iter().next()
+    }
+
+    pub fn get_toolchain_mut(&mut self, toolchain_name: &str) -> Option<&mut JavaToolchain> {
+        self.toolchains
+            .as_mut()
+            .and_then(|toolchains| toolchains.get_mut(toolchain_name))
+    }
+
+    ///
+    /// Finds the most specific override containing the provided path. Exact overrides only match their own
+    /// directory.
+    ///
+    /// Override paths are normalized before matching, ignoring case on Windows and resolving symlinks when
+    /// resolve_symlinks is set. When several overrides are equally specific, the one whose path orders first wins,
+    /// followed by the one listed first in the configuration, so that the selection does not depend on the order
+    /// the overrides were added in.
+    ///
+    pub fn get_environment_override(&self, environment_path: &Path) -> Option<&JvmsOverride> {
+        let resolve_symlinks = self.is_resolve_symlinks();
+        let environment_path = comparable_path(&make_absolute(environment_path), resolve_symlinks);
+        let mut best_override: Option<(&JvmsOverride, PathBuf)> = None;
+        if let Some(overrides) = &self.overrides {
+            for ovrride in overrides {
+                let override_path = comparable_path(&ovrride.path, resolve_symlinks);
+                if !environment_path.starts_with(&override_path) || (ovrride.is_exact() && environment_path != override_path) {
+                    continue;
+                }
+
+                let is_better = match &best_override {
+                    Some((_, best_path)) => {
+                        let ordering =
+                            override_path.components().count().cmp(&best_path.components().count())
+                                .then_with(|| best_path.cmp(&override_path));
+
+                        ordering == Ordering::Greater
+                    },
+                    None => true
+                };
+
+                if is_better {
+                    best_override = Some((ovrride, override_path));
+                }
+            }
+        }
+
+        best_override.map(|(o, _)| o)
+    }
+
+    ///
+    /// Resolves the java home to use in the provided directory as an absolute path with environment variables
+    /// expanded. See resolve_home_with_toolchain.
+    ///
+    pub fn resolve_home(&self, environment_path: &Path, search_root: Option<&Path>, pinned_toolchain: Option<&str>) -> Result<PathBuf> {
+        Ok(self.resolve_home_with_toolchain(environment_path, search_root, pinned_toolchain)?.java_home)
+    }
+
+    ///
+    /// Resolves the java home to use in the provided directory. A pinned toolchain name wins over the usual toolchain
+    /// resolution. The java home is expanded, made absolute relative to the directory, and must exist.
+    ///
+    pub fn resolve_home_with_toolchain(&self, environment_path: &Path, search_root: Option<&Path>, pinned_toolchain: Option<&str>) -> Result<ResolvedHome<'_>> {
+        let (name, toolchain) = match pinned_toolchain {
+            Some(pinned_toolchain) => {
+                debug!("Using the pinned toolchain {}", pinned_toolchain);
+                self.select_toolchain(pinned_toolchain)
+                    .ok_or_else(|| self.unknown_toolchain_error(pinned_toolchain.to_owned()))?
+            },
+            None => {
+                let resolved = self.resolve_toolchain_with_source(environment_path, search_root)?
+                    .ok_or_else(|| JvmsError::NoToolchainResolved { path: environment_path.to_path_buf() })?;
+
+                match (resolved.name, resolved.toolchain) {
+                    (Some(name), Cow::Borrowed(toolchain)) => (name, toolchain),
+                    (_, toolchain) => return Ok(ResolvedHome { name: None, toolchain: None, java_home: toolchain.into_owned().java_home })
+                }
+            }
+        };
+
+        let java_home = match toolchain.java_home.to_str() {
+            Some(java_home) => expand_path(java_home)?,
+            None => toolchain.java_home.clone()
+        };
+
+        let java_home = normalize_path(environment_path.join(java_home));
+        if !java_home.is_dir() {
+            return Err(JvmsError::ToolchainHomeMissing { name: name.to_owned(), path: java_home });
+        }
+
+        debug!("Resolved java home {:?} of toolchain {}", java_home, name);
+
+        Ok(ResolvedHome { name: Some(name), toolchain: Some(toolchain), java_home })
+    }
+
+    ///
+    /// Resolves the toolchain for the provided directory, also reporting where the selection came from. The toolchain
+    /// of the shell session wins, followed by a .jvms-home file, project files, overrides, the default toolchain, and
+    /// the global .java-version file.
+    ///
+    pub fn resolve_toolchain_with_source(&self, environment_path: &Path, search_root: Option<&Path>) -> Result<Option<ResolvedToolchain<'_>>> {
+        let strict = self.is_strict();
+        if strict {
+            self.validate_configuration()?;
+        }
+
+        if let Some(session_toolchain) = get_session_toolchain() {
+            return match self.select_toolchain(&session_toolchain) {
+                Some(entry) => Ok(Some(ResolvedToolchain::new(entry, ResolutionSource::Session))),
+                None => Err(self.unknown_toolchain_error(session_toolchain))
+            };
+        }
+
+        if let Some((path, java_home)) = find_project_java_home(environment_path, search_root)? {
+            return Ok(Some(ResolvedToolchain::from_project_home(path, java_home)));
+        }
+
+        if let Some((project_config_path, project_config)) = JvmsProjectConfiguration::find(environment_path, search_root, strict)? {
+            return match self.select_toolchain(&project_config.toolchain) {
+                Some(entry) => Ok(Some(ResolvedToolchain::new(entry, ResolutionSource::ProjectConfig(project_config_path)))),
+                None => Err(JvmsError::UnknownProjectToolchain { path: project_config_path, toolchain: project_config.toolchain.clone() })
+            };
+        }
+
+        if let Some((path, entry)) = self.resolve_tool_versions_toolchain(environment_path, search_root)? {
+            return Ok(Some(ResolvedToolchain::new(entry, ResolutionSource::ToolVersions(path))));
+        }
+
+        if let Some((path, entry)) = self.resolve_java_version_toolchain(environment_path, search_root)? {
+            return Ok(Some(ResolvedToolchain::new(entry, ResolutionSource::JavaVersion(path))));
+        }
+
+        if let Some(o) = self.get_environment_override(environment_path) {
+            if let Some(entry) = self.select_toolchain(&o.toolchain) {
+                return Ok(Some(ResolvedToolchain::new(entry, ResolutionSource::Override(o.path.clone()))));
+
+            } else if strict {
+                return Err(JvmsError::DanglingOverride { path: o.path.clone(), toolchain: o.toolchain.clone() });
+            }
+
+            debug!("Skipping the override for {:?}, which references an unknown toolchain: {}", o.path, o.toolchain);
+        }
+
+        if let Some(default_name) = self.get_default_toolchain_name() {
+            if let Some(entry) = self.select_toolchain(default_name) {
+                return Ok(Some(ResolvedToolchain::new(entry, ResolutionSource::Default)));
+
+            } else if strict {
+                return Err(JvmsError::UnknownToolchain(default_name.to_owned()));
+            }
+
+            debug!("Skipping the default toolchain {}, which is not registered", default_name);
+        }
+
+        if strict {
+            return Ok(None);
+        }
+
+        Ok(self.resolve_global_toolchain()?
+            .map(|(path, entry)| ResolvedToolchain::new(entry, ResolutionSource::GlobalJavaVersion(path))))
+    }
+
+    ///
+    /// Resolves the toolchain pinned by the java entry of the closest asdf .tool-versions file. Files without a
+    /// java entry are skipped, as asdf does.
+    ///
+    /// Each listed version is tried in order, first as a toolchain name and then with its distribution prefix and
+    /// build number removed as a version requirement. The asdf "system" version defers to the rest of the
+    /// resolution order.
+    ///
+    fn resolve_tool_versions_toolchain(&self, environment_path: &Path, search_root: Option<&Path>) -> Result<Option<(PathBuf, (&str, &JavaToolchain))>> {
+        let found = find_map_upwards(environment_path, search_root, |dir| {
+            let path = dir.join(TOOL_VERSIONS_FILE_NAME);
+            if !path.is_file() {
+                return Ok(None);
+            }
+
+            Ok(parse_tool_versions(&fs::read_to_string(&path)?).map(|versions| (path, versions)))
+        })?;
+
+        let (path, versions) = match found {
+            Some(found) => found,
+            None => return Ok(None)
+        };
+
+        for version in &versions {
+            if version == "system" {
+                return Ok(None);
+            }
+
+            if let Some(entry) = self.get_toolchain_entry(version) {
+                return Ok(Some((path, entry)));
+            }
+
+            if let Some(entry) = self.select_toolchain(strip_asdf_version(version)) {
+                return Ok(Some((path, entry)));
+            }
+        }
+
+        Err(JvmsError::UnknownProjectToolchain { path, toolchain: versions.join(" ") })
+    }
+
+    ///
+    /// Resolves the toolchain named by the closest jenv .java-version file. Its contents are tried as a toolchain
+    /// name and then as a version requirement, with a jenv style prefix such as "temurin64-" removed if needed.
+    ///
+    /// The .java-version in the user's home directory is left to resolve_global_toolchain, so that it keeps acting
+    /// as a fallback rather than taking precedence over overrides and the default.
+    ///
+    fn resolve_java_version_toolchain(&self, environment_path: &Path, search_root: Option<&Path>) -> Result<Option<(PathBuf, (&str, &JavaToolchain))>> {
+        let path = match find_upwards(environment_path, Path::new(JAVA_VERSION_FILE_NAME), search_root) {
+            Some(path) => path,
+            None => return Ok(None)
+        };
+
+        if dirs::home_dir().is_some_and(|home_dir| path == make_absolute(home_dir).join(JAVA_VERSION_FILE_NAME)) {
+            return Ok(None);
+        }
+
+        let version = match read_java_version_file(&path)? {
+            Some(version) => version,
+            None => return Ok(None)
+        };
+
+        match self.select_toolchain(&version).or_else(|| self.select_toolchain(strip_asdf_version(&version))) {
+            Some(entry) => Ok(Some((path, entry))),
+            None => Err(JvmsError::UnknownProjectToolchain { path, toolchain: version })
+        }
+    }
+
+    ///
+    /// Resolves the toolchain named by the global .java-version in the user's home directory. This is only used
+    /// when the configuration has no usable default, so that it can provide a default before one has been
+    /// configured.
+    ///
+    fn resolve_global_toolchain(&self) -> Result<Option<(PathBuf, (&str, &JavaToolchain))>> {
+        match dirs::home_dir() {
+            Some(home_dir) => self.resolve_global_java_version(home_dir.join(JAVA_VERSION_FILE_NAME)),
+            None => Ok(None)
+        }
+    }
+
+    ///
+    /// Resolves the toolchain named by the global .java-version file at the provided path. Unlike project files, it
+    /// is only matched against toolchain names and aliases.
+    ///
+    fn resolve_global_java_version(&self, global_version_path: PathBuf) -> Result<Option<(PathBuf, (&str, &JavaToolchain))>> {
+        if !global_version_path.is_file() {
+            return Ok(None);
+        }
+
+        match read_java_version_file(&global_version_path)? {
+            Some(toolchain_name) => {
+                match self.get_toolchain_entry(&toolchain_name) {
+                    Some(entry) => Ok(Some((global_version_path, entry))),
+                    None => Err(JvmsError::UnknownProjectToolchain { path: global_version_path, toolchain: toolchain_name })
+                }
+            },
+            None => Ok(None)
+        }
+    }
+
+    pub fn has_toolchain(&self, toolchain_name: &str) -> bool {
+        self.get_toolchain(toolchain_name).is_some()
+    }
+
+    ///
+    /// Finds a toolchain by name or alias, returning the name it is registered under.
+    ///
+    pub fn get_toolchain_entry(&self, toolchain_name: &str) -> Option<(&str, &JavaToolchain)> {
+        let toolchain_name = self.get_alias_target(toolchain_name).unwrap_or(toolchain_name);
+        self.toolchains
+            .as_ref()
+            .and_then(|i| i.get_key_value(toolchain_name))
+            .map(|(name, toolchain)| (name.as_str(), toolchain))
+    }
+
+    pub fn get_alias_target(&self, alias: &str) -> Option<&str> {
+        self.aliases
+            .as_ref()
+            .and_then(|aliases| aliases.get(alias))
+            .map(|v| v.as_str())
+    }
+
+    ///
+    /// Returns the aliases referring to the toolchain, sorted by name.
+    ///
+    pub fn get_aliases_for(&self, toolchain_name: &str) -> Vec<&str> {
+        let mut aliases: Vec<_> = self.aliases
+            .iter()
+            .flatten()
+            .filter(|(_, target)| *target == toolchain_name)
+            .map(|(alias, _)| alias.as_str())
+            .collect();
+        aliases.sort_unstable();
+        aliases
+    }
+
+    pub fn add_alias(&mut self, alias: String, toolchain_name: String) {
+        self.aliases
+            .get_or_insert_with(HashMap::new)
+            .insert(alias, toolchain_name);
+    }
+
+    ///
+    /// Removes every alias referring to the toolchain, returning the removed aliases sorted by name.
+    ///
+    pub fn remove_aliases_for(&mut self, toolchain_name: &str) -> Vec<String> {
+        let mut removed = Vec::new();
+        if let Some(aliases) = self.aliases.as_mut() {
+            aliases.retain(|alias, target| {
+                if target == toolchain_name {
+                    removed.push(alias.clone());
+                    false
+
+                } else {
+                    true
+                }
+            });
+        }
+
+        removed.sort();
+        removed
+    }
+
+    ///
+    /// Selects a toolchain by exact name or, failing that, by treating the spec as a glob or version requirement.
+    ///
+    /// When several toolchains match, the one with the newest detected version is selected, with ties broken by
+    /// name. A spec that is just a major version selects the default toolchain for that version if one is set.
+    ///
+    pub fn select_toolchain(&self, spec: &str) -> Option<(&str, &JavaToolchain)> {
+        if let Some(entry) = self.get_toolchain_entry(spec) {
+            return Some(entry);
+        }
+
+        if let Some(entry) = spec.parse().ok().and_then(|major| self.get_version_default(major)) {
+            trace!("Selected toolchain {} as the default for java {}", entry.0, spec);
+            return Some(entry);
+        }
+
+        let selector = ToolchainSelector::parse(spec);
+        let mut candidates: Vec<_> = self.get_toolchains()
+            .map(|(name, toolchain)| (name.as_str(), toolchain, toolchain.get_java_version()))
+            .filter(|(name, _, version)| selector.matches(name, version.as_ref()))
+            .collect();
+
+        candidates.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(b.0)));
+        trace!("Toolchains matching {}: {:?}", spec, candidates.iter().map(|c| c.0).collect::<Vec<_>>());
+        candidates
+            .into_iter()
+            .next()
+            .map(|(name, toolchain, _)| (name, toolchain))
+    }
+
+    ///
+    /// Finds the registered toolchain name closest to the provided name, if any is close enough to be a likely typo.
+    ///
+    pub fn suggest_toolchain_name(&self, toolchain_name: &str) -> Option<&str> {
+        let max_distance = (toolchain_name.chars().count() / 3).max(2);
+
+        //
+        // Between equally distant names prefer the one sharing the longest prefix, as typos tend to be near the end.
+        //
+
+        self.get_toolchains()
+            .map(|(name, _)| {
+                let common_prefix = toolchain_name.chars().zip(name.chars()).take_while(|(a, b)| a == b).count();
+                (edit_distance(toolchain_name, name), Reverse(common_prefix), name)
+            })
+            .filter(|(distance, _, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, _, name)| name.as_str())
+    }
+
+    ///
+    /// Creates the error for an unknown toolchain name, suggesting the closest registered name if there is one.
+    ///
+    pub fn unknown_toolchain_error(&self, toolchain_name: String) -> JvmsError {
+        match self.suggest_toolchain_name(&toolchain_name) {
+            Some(suggestion) => JvmsError::UnknownToolchainSuggestion { suggestion: suggestion.to_owned(), name: toolchain_name },
+            None => JvmsError::UnknownToolchain(toolchain_name)
+        }
+    }
+
+    pub fn get_default_toolchain_name(&self) -> Option<&str> {
+        self.default.as_ref().map(|v| v.as_str())
+    }
+
+    pub fn set_default_toolchain_name(&mut self, toolchain_name: String) {
+        self.default = Some(toolchain_name);
+    }
+
+    ///
+    /// Gets the default toolchain for the major java version, if one is set and registered.
+    ///
+    pub fn get_version_default(&self, major_version: u32) -> Option<(&str, &JavaToolchain)> {
+        self.version_defaults
+            .as_ref()
+            .and_then(|version_defaults| version_defaults.get(&major_version))
+            .and_then(|toolchain_name| self.get_toolchain_entry(toolchain_name))
+    }
+
+    pub fn set_version_default(&mut self, major_version: u32, toolchain_name: String) {
+        self.version_defaults
+            .get_or_insert_with(HashMap::new)
+            .insert(major_version, toolchain_name);
+    }
+
+    pub fn clear_default_toolchain_name(&mut self) {
+        self.default = None;
+    }
+
+    pub fn add_toolchain(&mut self, toolchain_name: String, mut toolchain: JavaToolchain) {
+        toolchain.java_home = make_absolute(&toolchain.java_home);
+        if self.toolchains.is_none() {
+            self.toolchains = Some(HashMap::new());
+        }
+
+        self.toolchains.as_mut().unwrap().insert(toolchain_name, toolchain);
+    }
+
+    pub fn get_toolchains(&self) -> impl Iterator<Item = (&String, &JavaToolchain)> {
+        self.toolchains
+            .iter()
+            .map(|i| i.iter())
+            .flatten()
+    }
+
+    pub fn remove_toolchain(&mut self, toolchain_name: &str) -> Option<JavaToolchain> {
+        self.toolchains
+            .as_mut()
+            .and_then(|toolchains| toolchains.remove(toolchain_name))
+    }
+
+    ///
+    /// Lists the default, version defaults, and overrides along with the toolchain spec each of them holds.
+    ///
+    fn get_toolchain_references(&self) -> Vec<(ToolchainReference, String)> {
+        let mut references: Vec<_> = self.default.iter()
+            .map(|default| (ToolchainReference::Default, default.clone()))
+            .collect();
+
+        references.extend(self.version_defaults.iter()
+            .flatten()
+            .map(|(major_version, name)| (ToolchainReference::VersionDefault(*major_version), name.clone())));
+        references.extend(self.overrides.iter()
+            .flatten()
+            .map(|o| (ToolchainReference::Override(o.path.clone()), o.toolchain.clone())));
+        references
+    }
+
+    ///
+    /// Removes a toolchain unless the default, a version default, or an override would select nothing without it,
+    /// because it names the toolchain or one of its aliases, or it is the only toolchain matching its version
+    /// requirement. With cascade those settings are removed along with the toolchain and returned.
+    ///
+    pub fn remove_toolchain_cascading(&mut self, toolchain_name: &str, cascade: bool) -> Result<(JavaToolchain, Vec<ToolchainReference>)> {
+        let selecting: Vec<_> = self.get_toolchain_references()
+            .into_iter()
+            .filter(|(_, spec)| self.select_toolchain(spec).is_some_and(|(name, _)| name == toolchain_name))
+            .collect();
+
+        let toolchain = self.remove_toolchain(toolchain_name)
+            .ok_or_else(|| self.unknown_toolchain_error(toolchain_name.to_owned()))?;
+
+        let dangling: Vec<_> = selecting
+            .into_iter()
+            .filter(|(_, spec)| self.select_toolchain(spec).is_none())
+            .map(|(reference, _)| reference)
+            .collect();
+
+        if !dangling.is_empty() && !cascade {
+            self.toolchains.get_or_insert_with(HashMap::new).insert(toolchain_name.to_owned(), toolchain);
+            return Err(JvmsError::ToolchainInUse {
+                name: toolchain_name.to_owned(),
+                references: dangling.iter().map(|reference| reference.get_description()).collect()
+            });
+        }
+
+        for reference in &dangling {
+            match reference {
+                ToolchainReference::Default => self.clear_default_toolchain_name(),
+                ToolchainReference::VersionDefault(major_version) => {
+                    if let Some(version_defaults) = self.version_defaults.as_mut() {
+                        version_defaults.remove(major_version);
+                    }
+                },
+                ToolchainReference::Override(path) => {
+                    if let Some(overrides) = self.overrides.as_mut() {
+                        overrides.retain(|o| o.path != *path);
+                    }
+                }
+            }
+        }
+
+        Ok((toolchain, dangling))
+    }
+
+    ///
+    /// Renames a toolchain, updating the default, version defaults, aliases, and overrides that reference it.
+    ///
+    pub fn rename_toolchain(&mut self, old_name: &str, new_name: &str) -> Result<()> {
+        if self.has_toolchain(new_name) || self.get_alias_target(new_name).is_some() {
+            return Err(JvmsError::InvalidConfiguration(format!("A toolchain or alias named {} already exists.", new_name)));
+        }
+
+        let toolchain = self.toolchains
+            .as_mut()
+            .and_then(|toolchains| toolchains.remove(old_name))
+            .ok_or_else(|| self.unknown_toolchain_error(old_name.to_owned()))?;
+        self.toolchains.get_or_insert_with(HashMap::new).insert(new_name.to_owned(), toolchain);
+
+        let references = self.default.iter_mut()
+            .chain(self.version_defaults.iter_mut().flat_map(|defaults| defaults.values_mut()))
+            .chain(self.aliases.iter_mut().flat_map(|aliases| aliases.values_mut()))
+            .chain(self.overrides.iter_mut().flat_map(|overrides| overrides.iter_mut().map(|o| &mut o.toolchain)));
+
+        for reference in references {
+            if reference == old_name {
+                *reference = new_name.to_owned();
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add_override(&mut self, path: &Path, toolchain_name: String) {
+        self.add_override_entry(JvmsOverride::new(path.to_path_buf(), toolchain_name));
+    }
+
+    ///
+    /// Adds the override along with its settings, making its path absolute.
+    ///
+    pub fn add_override_entry(&mut self, mut jvms_override: JvmsOverride) {
+        jvms_override.path = make_absolute(&jvms_override.path);
+        self.overrides
+            .get_or_insert_with(Vec::new)
+            .push(jvms_override);
+    }
+
+    ///
+    /// Removes the overrides selected by the policy, returning each removed override along with why it was removed.
+    ///
+    pub fn clean_overrides(&mut self, policy: OverrideCleanPolicy) -> Vec<(JvmsOverride, &'static str)> {
+        let overrides = match self.overrides.take() {
+            Some(overrides) => overrides,
+            None => return Vec::new()
+        };
+
+        let mut kept = Vec::new();
+        let mut removed = Vec::new();
+        for jvms_override in overrides {
+            let missing_directory = !jvms_override.path.exists();
+            let missing_toolchain = self.select_toolchain(&jvms_override.toolchain).is_none();
+
+            if missing_directory && policy.cleans_missing_directories() {
+                removed.push((jvms_override, "directory no longer exists"));
+
+            } else if missing_toolchain && policy.cleans_missing_toolchains() {
+                removed.push((jvms_override, "toolchain no longer exists"));
+
+            } else {
+                kept.push(jvms_override);
+            }
+        }
+
+        self.overrides = Some(kept);
+        removed
+    }
+
+    ///
+    /// Lays the configuration over this one. Its toolchains, overrides, aliases, version defaults, and mirrors
+    /// replace those with the same name or directory, and the settings it has replace those of this configuration.
+    ///
+    fn overlay(&mut self, layer: JvmsConfiguration) {
+        overlay_map(&mut self.toolchains, layer.toolchains);
+        for layer_override in layer.overrides.unwrap_or_default() {
+            self.remove_override(&layer_override.path);
+            self.add_override_entry(layer_override);
+        }
+
+        self.default = layer.default.or(self.default.take());
+        self.strict = layer.strict.or(self.strict);
+        self.clear_env = layer.clear_env.or(self.clear_env.take());
+        self.dedupe_path = layer.dedupe_path.or(self.dedupe_path);
+        self.resolve_symlinks = layer.resolve_symlinks.or(self.resolve_symlinks);
+        self.hooks = layer.hooks.or(self.hooks.take());
+        overlay_map(&mut self.version_defaults, layer.version_defaults);
+        overlay_map(&mut self.aliases, layer.aliases);
+        overlay_map(&mut self.mirrors, layer.mirrors);
+        self.loaded_mtime.set(layer.loaded_mtime.get());
+    }
+
+    ///
+    /// Returns what is saved to the user configuration when this configuration is layered over a system
+    /// configuration: the entries that differ from it. Entries of the system configuration can only be replaced in
+    /// the user configuration, removing one fails. Returns None if there is no system configuration.
+    ///
+    fn get_user_layer(&self) -> Result<Option<JvmsConfiguration>> {
+        let (system_config_file_path, system_config) = match self.system_layer.as_ref() {
+            Some(system_layer) => system_layer,
+            None => return Ok(None)
+        };
+
+        let removed = |description: String| {
+            JvmsError::InvalidConfiguration(format!("{} comes from the system configuration {:?} and can only be removed there.", description, system_config_file_path))
+        };
+
+        let overrides = self.overrides.as_deref().unwrap_or_default();
+        let system_overrides = system_config.overrides.as_deref().unwrap_or_default();
+        if let Some(removed_override) = system_overrides.iter().find(|s| !overrides.iter().any(|o| self.is_same_override_path(&o.path, &s.path))) {
+            return Err(removed(format!("The override for {:?}", removed_override.path)));
+        }
+
+        let layer_overrides: Vec<JvmsOverride> = overrides.iter()
+            .filter(|o| !system_overrides.contains(o))
+            .cloned()
+            .collect();
+
+        let mut layer = JvmsConfiguration::new();
+        layer.toolchains = diff_map(&self.toolchains, &system_config.toolchains).map_err(|name| removed(format!("Toolchain {}", name)))?;
+        layer.overrides = if layer_overrides.is_empty() { None } else { Some(layer_overrides) };
+        layer.default = diff_setting(&self.default, &system_config.default).map_err(|_| removed("The default toolchain".to_owned()))?;
+        layer.strict = diff_setting(&self.strict, &system_config.strict).map_err(|_| removed("strict".to_owned()))?;
+        layer.clear_env = diff_setting(&self.clear_env, &system_config.clear_env).map_err(|_| removed("clear_env".to_owned()))?;
+        layer.dedupe_path = diff_setting(&self.dedupe_path, &system_config.dedupe_path).map_err(|_| removed("dedupe_path".to_owned()))?;
+        layer.resolve_symlinks = diff_setting(&self.resolve_symlinks, &system_config.resolve_symlinks).map_err(|_| removed("resolve_symlinks".to_owned()))?;
+        layer.hooks = diff_setting(&self.hooks, &system_config.hooks).map_err(|_| removed("hooks".to_owned()))?;
+        layer.version_defaults = diff_map(&self.version_defaults, &system_config.version_defaults).map_err(|major_version| removed(format!("The default for java {}", major_version)))?;
+        layer.aliases = diff_map(&self.aliases, &system_config.aliases).map_err(|alias| removed(format!("Alias {}", alias)))?;
+        layer.mirrors = diff_map(&self.mirrors, &system_config.mirrors).map_err(|provider_name| removed(format!("The mirror of {}", provider_name)))?;
+        Ok(Some(layer))
+    }
+
+    ///
+    /// Merges the toolchains, overrides, and default of another configuration into this one. Entries are only ever
+    /// added or updated, never removed, and the policy decides conflicts between differing entries.
+    ///
+    pub fn merge(&mut self, other: JvmsConfiguration, policy: MergePolicy) -> MergeReport {
+        let mut report = MergeReport::default();
+        let prefer_imported = matches!(policy, MergePolicy::PreferImported);
+
+        let mut other_toolchains: Vec<_> = other.toolchains.unwrap_or_default().into_iter().collect();
+        other_toolchains.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, mut toolchain) in other_toolchains {
+            toolchain.java_home = make_absolute(&toolchain.java_home);
+            let description = format!("toolchain {}", name);
+            match self.get_toolchain(&name) {
+                None => {
+                    self.add_toolchain(name, toolchain);
+                    report.added.push(description);
+                },
+                Some(existing) if *existing == toolchain => {},
+                Some(_) if prefer_imported => {
+                    self.add_toolchain(name, toolchain);
+                    report.updated.push(description);
+                },
+                Some(_) => report.skipped.push(description)
+            }
+        }
+
+        for mut other_override in other.overrides.unwrap_or_default() {
+            other_override.path = make_absolute(&other_override.path);
+            let description = format!("override {:?}", other_override.path);
+            let existing = self.overrides
+                .as_ref()
+                .and_then(|overrides| overrides.iter().find(|o| self.is_same_override_path(&o.path, &other_override.path)));
+
+            match existing {
+                None => {
+                    self.add_override_entry(other_override);
+                    report.added.push(description);
+                },
+                Some(existing) if *existing == other_override => {},
+                Some(_) if prefer_imported => {
+                    self.remove_override(&other_override.path);
+                    self.add_override_entry(other_override);
+                    report.updated.push(description);
+                },
+                Some(_) => report.skipped.push(description)
+            }
+        }
+
+        if let Some(other_default) = other.default {
+            match self.default.as_ref() {
+                None => {
+                    self.default = Some(other_default);
+                    report.added.push("default".to_owned());
+                },
+                Some(existing) if *existing == other_default => {},
+                Some(_) if prefer_imported => {
+                    self.default = Some(other_default);
+                    report.updated.push("default".to_owned());
+                },
+                Some(_) => report.skipped.push("default".to_owned())
+            }
+        }
+
+        report
+    }
+
+    pub fn get_overrides(&self) -> Option<&[JvmsOverride]> {
+        self.overrides.as_ref().map(|o| &**o)
+    }
+
+    ///
+    /// Returns the overrides sorted by path, independent of the order in which they were added.
+    ///
+    pub fn iter_overrides_sorted(&self) -> impl Iterator<Item = &JvmsOverride> {
+        let mut overrides: Vec<_> = self.overrides.iter().flatten().collect();
+        overrides.sort_by(|a, b| a.path.cmp(&b.path));
+        overrides.into_iter()
+    }
+
+    pub fn remove_override(&mut self, override_path: &Path) {
+        let resolve_symlinks = self.is_resolve_symlinks();
+        let override_path = comparable_path(&make_absolute(override_path), resolve_symlinks);
+        if let Some(overrides) = self.overrides.as_mut() {
+            overrides.retain(|o| comparable_path(&o.path, resolve_symlinks) != override_path)
+        }
+    }
+
+    pub fn validate_configuration(&self) -> Result<()> {
+        match self.collect_validation_errors().into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(())
+        }
+    }
+
+    ///
+    /// Validates the configuration, collecting every problem found rather than stopping at the first one.
+    ///
+    pub fn collect_validation_errors(&self) -> Vec<JvmsError> {
+        let mut errors = Vec::new();
+        if self.toolchains.is_none() || self.toolchains.as_ref().unwrap().is_empty() {
+            errors.push(JvmsError::InvalidConfiguration("Configuration has no installations.".to_owned()));
+        }
+
+        let mut toolchains: Vec<_> = self.get_toolchains().collect();
+        toolchains.sort_by(|a, b| a.0.cmp(b.0));
+        for toolchain in toolchains {
+            if !toolchain.1.java_home.exists() {
+                //
+                // Unverified homes are expected to be unavailable at times, so their absence is not an error.
+                //
+
+                if !toolchain.1.is_verified() {
+                    continue;
+                }
+
+                errors.push(JvmsError::ToolchainHomeMissing { name: toolchain.0.clone(), path: toolchain.1.java_home.clone() });
+                continue;
+            }
+
+            let java_path = jdk::get_tool_path(&toolchain.1.java_home, "java");
+            if java_path.exists() && !jdk::is_executable(&java_path) {
+                errors.push(JvmsError::ToolchainNotExecutable { name: toolchain.0.clone(), path: java_path });
+            }
+        }
+
+        if let Some(default) = self.get_default_toolchain_name() {
+            if self.select_toolchain(default).is_none() {
+                errors.push(JvmsError::UnknownToolchain(default.to_owned()));
+            }
+
+        } else {
+            errors.push(JvmsError::NoDefaultConfigured);
+        }
+
+        if let Some(version_defaults) = self.version_defaults.as_ref() {
+            let mut version_defaults: Vec<_> = version_defaults.iter().collect();
+            version_defaults.sort();
+            for (major_version, toolchain_name) in version_defaults {
+                if !self.has_toolchain(toolchain_name) {
+                    errors.push(JvmsError::InvalidConfiguration(format!("Default for java {} references an unknown toolchain: {}", major_version, toolchain_name)));
+                }
+            }
+        }
+
+        if let Some(aliases) = self.aliases.as_ref() {
+            let mut aliases: Vec<_> = aliases.iter().collect();
+            aliases.sort();
+            for (alias, toolchain_name) in aliases {
+                let toolchains = self.toolchains.as_ref();
+                if toolchains.is_some_and(|toolchains| toolchains.contains_key(alias)) {
+                    errors.push(JvmsError::InvalidConfiguration(format!("Alias {} has the same name as a toolchain.", alias)));
+
+                } else if !toolchains.is_some_and(|toolchains| toolchains.contains_key(toolchain_name)) {
+                    errors.push(JvmsError::InvalidConfiguration(format!("Alias {} references an unknown toolchain: {}", alias, toolchain_name)));
+                }
+            }
+        }
+
+        if let Some(overrides) = self.overrides.as_ref() {
+            for o in overrides {
+                if self.select_toolchain(&o.toolchain).is_none() {
+                    errors.push(JvmsError::DanglingOverride { path: o.path.clone(), toolchain: o.toolchain.clone() });
+                }
+            }
+        }
+
+        if let Some(mirrors) = self.mirrors.as_ref() {
+            let mut provider_names: Vec<_> = mirrors.keys().collect();
+            provider_names.sort();
+            for provider_name in provider_names {
+                if provider_name != adoptium::PROVIDER_NAME {
+                    errors.push(JvmsError::InvalidConfiguration(format!("Mirror configured for unknown download provider: {}", provider_name)));
+                }
+            }
+        }
+
+        errors
+    }
+
+}
+
+impl Default for JvmsConfigurationBuilder {
+
+    fn default() -> Self {
+        JvmsConfigurationBuilder::new()
+    }
+
+}
+
+impl JvmsConfigurationBuilder {
+
+    pub fn new() -> JvmsConfigurationBuilder {
+        JvmsConfigurationBuilder {
+            config: JvmsConfiguration::new()
+        }
+    }
+
+    pub fn toolchain(mut self, toolchain_name: impl Into<String>, java_home: impl Into<PathBuf>) -> JvmsConfigurationBuilder {
+        self.config.add_toolchain(toolchain_name.into(), JavaToolchain::new(java_home.into()));
+        self
+    }
+
+    pub fn default_toolchain(mut self, toolchain_name: impl Into<String>) -> JvmsConfigurationBuilder {
+        self.config.set_default_toolchain_name(toolchain_name.into());
+        self
+    }
+
+    pub fn override_toolchain(mut self, path: impl AsRef<Path>, toolchain_name: impl Into<String>) -> JvmsConfigurationBuilder {
+        let path = path.as_ref();
+        self.config.remove_override(path);
+        self.config.add_override(path, toolchain_name.into());
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> JvmsConfigurationBuilder {
+        self.config.strict = Some(strict);
+        self
+    }
+
+    ///
+    /// Builds the configuration, failing if it does not pass validation.
+    ///
+    pub fn build(self) -> Result<JvmsConfiguration> {
+        self.config.validate_configuration()?;
+        Ok(self.config)
+    }
+
+    ///
+    /// Builds the configuration without validating it, allowing invalid states to be constructed for tests.
+    ///
+    pub fn build_unchecked(self) -> JvmsConfiguration {
+        self.config
+    }
+
+}
+
+impl JvmsOverride {
+
+    pub fn new(path: PathBuf, toolchain_name: String) -> JvmsOverride {
+        JvmsOverride {
+            path,
+            toolchain: toolchain_name,
+            java_opts: None,
+            env: None,
+            exact: None
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.exact.unwrap_or(false)
+    }
+
+}
+
+impl ToolchainReference {
+
+    pub fn get_description(&self) -> String {
+        match self {
+            ToolchainReference::Default => "the default toolchain".to_owned(),
+            ToolchainReference::VersionDefault(major_version) => format!("the version default for java {}", major_version),
+            ToolchainReference::Override(path) => format!("override {:?}", path)
+        }
+    }
+
+}
+
+impl JvmsProjectConfiguration {
+
+    ///
+    /// Finds the project configuration for the provided directory by walking up its ancestors, stopping at the
+    /// search root if one is provided. The closest directory with a .jvms.toml, .jvmsrc, or .jvms.conf file wins,
+    /// and within a directory they are tried in that order.
+    ///
+    /// If JVMS_PROJECT_CONFIG is set, the walk first looks for that path relative to each ancestor. When no
+    /// ancestor contains it a warning is printed and the walk falls back to looking for the project files, unless
+    /// strict is set in which case an error is returned.
+    ///
+    pub fn find(environment_path: &Path, search_root: Option<&Path>, strict: bool) -> Result<Option<(PathBuf, JvmsProjectConfiguration)>> {
+        if let Some(relative_path) = env::var_os(PROJECT_CONFIG_ENV) {
+            if let Some(path) = find_upwards(environment_path, Path::new(&relative_path), search_root) {
+                let project_config = JvmsProjectConfiguration::load(&path)?;
+                return Ok(Some((path, project_config)));
+            }
+
+            if strict {
+                return Err(JvmsError::InvalidConfiguration(format!("{} is set to {:?} but no such project configuration was found.", PROJECT_CONFIG_ENV, relative_path)));
+            }
+
+            eprintln!("Warning: {} is set to {:?} but no such project configuration was found, falling back to {}", PROJECT_CONFIG_ENV, relative_path, PROJECT_CONFIG_FILE_NAMES.join(", "));
+        }
+
+        if let Some(path) = find_any_upwards(environment_path, &PROJECT_CONFIG_FILE_NAMES, search_root) {
+            let project_config = JvmsProjectConfiguration::load(&path)?;
+            return Ok(Some((path, project_config)));
+        }
+
+        Ok(None)
+    }
+
+    ///
+    /// Loads a project file, as a bare toolchain name for .jvmsrc, as TOML for .toml files, and as JSON otherwise.
+    ///
+    fn load(path: &Path) -> Result<JvmsProjectConfiguration> {
+        let contents =
+            fs::read_to_string(path)
+                .map_err(|io_error| {
+                    JvmsError::InvalidConfiguration(format!("Failed to open project configuration file {:?}: {:?}", path, io_error))
+                })?;
+
+        if path.file_name().is_some_and(|file_name| file_name == PROJECT_RC_FILE_NAME) {
+            return contents.lines()
+                .map(|line| line.trim())
+                .find(|line| !line.is_empty())
+                .map(|toolchain| JvmsProjectConfiguration { toolchain: toolchain.to_owned() })
+                .ok_or_else(|| JvmsError::InvalidConfiguration(format!("Project file {:?} does not name a toolchain.", path)));
+        }
+
+        if is_toml_file(path) {
+            return toml::from_str(&contents)
+                .map_err(|error| {
+                    JvmsError::TomlConfigParse { path: path.to_path_buf(), error }
+                });
+        }
+
+        serde_json::from_str(&contents)
+            .map_err(|error| {
+                JvmsError::ConfigParse { path: path.to_path_buf(), error }
+            })
+    }
+
+}
 
 impl JavaToolchain {
 
     pub fn new(java_home: PathBuf) -> JavaToolchain {
         JavaToolchain {
-            java_home
+            java_home,
+            arch: None,
+            managed: None,
+            verified: None,
+            tags: None,
+            java_opts: None,
+            version: None,
+            vendor: None,
+            env: None
+        }
+    }
+
+    ///
+    /// Records the version, vendor, and architecture from the release file of the java home, keeping any values that
+    /// are already set. This lets toolchains be told apart even while their java home is unavailable.
+    ///
+    pub fn record_metadata(&mut self) {
+        if let Ok(jdk_info) = JdkInfo::read(&self.java_home) {
+            if self.version.is_none() {
+                self.version = jdk_info.version;
+            }
+
+            if self.vendor.is_none() {
+                self.vendor = jdk_info.vendor;
+            }
+
+            if self.arch.is_none() {
+                self.arch = jdk_info.arch;
+            }
+        }
+    }
+
+    ///
+    /// Checks whether the java home is owned by jvms and should be deleted along with the toolchain.
+    ///
+    pub fn is_managed(&self) -> bool {
+        self.managed.unwrap_or(false)
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.as_ref().map(|tags| tags.iter().any(|t| t == tag)).unwrap_or(false)
+    }
+
+    ///
+    /// Adds the tag if not already present, returning whether it was added.
+    ///
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        if self.has_tag(tag) {
+            return false;
+        }
+
+        self.tags.get_or_insert_with(Vec::new).push(tag.to_owned());
+        true
+    }
+
+    ///
+    /// Removes the tag, returning whether it was present.
+    ///
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        if !self.has_tag(tag) {
+            return false;
+        }
+
+        let tags = self.tags.as_mut().unwrap();
+        tags.retain(|t| t != tag);
+        if tags.is_empty() {
+            self.tags = None;
+        }
+
+        true
+    }
+
+    ///
+    /// Checks whether the java home was known to exist when the toolchain was registered.
+    ///
+    pub fn is_verified(&self) -> bool {
+        self.verified.unwrap_or(true)
+    }
+
+    ///
+    /// Clears the unverified mark once the java home exists, recording the metadata that could not be read when the
+    /// toolchain was registered. Returns whether the mark was cleared.
+    ///
+    pub fn verify(&mut self) -> bool {
+        if self.is_verified() || !self.java_home.is_dir() {
+            return false;
+        }
+
+        self.verified = None;
+        self.record_metadata();
+        true
+    }
+
+    ///
+    /// Reads the metadata of the java home, falling back to the values recorded when the toolchain was added for any
+    /// that the release file does not provide.
+    ///
+    pub fn read_jdk_info(&self) -> Option<JdkInfo> {
+        let mut jdk_info = JdkInfo::read(&self.java_home).ok();
+        if self.version.is_some() || self.vendor.is_some() || self.arch.is_some() {
+            let jdk_info = jdk_info.get_or_insert_with(JdkInfo::default);
+            if jdk_info.version.is_none() {
+                jdk_info.version = self.version.clone();
+            }
+
+            if jdk_info.vendor.is_none() {
+                jdk_info.vendor = self.vendor.clone();
+            }
+
+            if jdk_info.arch.is_none() {
+                jdk_info.arch = self.arch.clone();
+            }
+        }
+
+        jdk_info
+    }
+
+    pub fn get_java_version(&self) -> Option<JavaVersion> {
+        self.read_jdk_info().and_then(|info| info.get_java_version())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jdk::tests::create_fake_jdk;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+    use std::thread;
+    use std::time::Duration;
+
+    fn config_with_toolchains(count: usize) -> JvmsConfiguration {
+        let mut config = JvmsConfiguration::new();
+        for index in 0..count {
+            config.add_toolchain(format!("toolchain-{}", index), JavaToolchain::new(PathBuf::from(format!("/opt/jdk-{}", index))));
+        }
+
+        config
+    }
+
+    #[test]
+    fn jvms_home_file_is_a_resolution_source() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project_dir = temp_dir.path().join("project");
+        let java_home = project_dir.join("vendor").join("jdk");
+        create_fake_jdk(&java_home, "21.0.1");
+        fs::write(project_dir.join(PROJECT_HOME_FILE_NAME), "vendor/jdk\n").unwrap();
+
+        let nested_dir = project_dir.join("src");
+        fs::create_dir_all(&nested_dir).unwrap();
+
+        let mut config = config_with_toolchains(1);
+        config.set_default_toolchain_name("toolchain-0".to_owned());
+
+        let resolved = config.resolve_toolchain_with_source(&nested_dir, Some(temp_dir.path())).unwrap().unwrap();
+        assert_eq!(resolved.name, None);
+        assert_eq!(resolved.toolchain.java_home, normalize_path(java_home.clone()));
+        assert_eq!(resolved.toolchain.version.as_deref(), Some("21.0.1"));
+        assert_eq!(resolved.source.get_path(), Some(&*project_dir.join(PROJECT_HOME_FILE_NAME)));
+
+        let resolved_home = config.resolve_home_with_toolchain(&nested_dir, Some(temp_dir.path()), None).unwrap();
+        assert_eq!(resolved_home.name, None);
+        assert_eq!(resolved_home.java_home, normalize_path(java_home));
+
+        let outside = config.resolve_toolchain_with_source(temp_dir.path(), Some(temp_dir.path())).unwrap().unwrap();
+        assert_eq!(outside.name, Some("toolchain-0"));
+    }
+
+    #[test]
+    fn pinned_toolchain_wins_over_jvms_home_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let java_home = temp_dir.path().join("jdk");
+        create_fake_jdk(&java_home, "17.0.2");
+        fs::write(temp_dir.path().join(PROJECT_HOME_FILE_NAME), "jdk").unwrap();
+
+        let mut config = JvmsConfiguration::new();
+        config.add_toolchain("pinned".to_owned(), JavaToolchain::new(java_home.clone()));
+
+        let resolved_home = config.resolve_home_with_toolchain(temp_dir.path(), Some(temp_dir.path()), Some("pinned")).unwrap();
+        assert_eq!(resolved_home.name, Some("pinned"));
+        assert!(resolved_home.toolchain.is_some());
+    }
+
+    #[test]
+    fn unknown_toolchain_in_project_file_names_the_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = config_with_toolchains(1);
+        let java_version_path = temp_dir.path().join(JAVA_VERSION_FILE_NAME);
+        fs::write(&java_version_path, "missing\n").unwrap();
+
+        match config.resolve_toolchain_with_source(temp_dir.path(), Some(temp_dir.path())) {
+            Err(JvmsError::UnknownProjectToolchain { path, toolchain }) => {
+                assert_eq!(path, java_version_path);
+                assert_eq!(toolchain, "missing");
+            },
+            _ => panic!("expected an unknown project toolchain error")
+        }
+
+        let tool_versions_path = temp_dir.path().join(TOOL_VERSIONS_FILE_NAME);
+        fs::write(&tool_versions_path, "java missing-1 missing-2\n").unwrap();
+
+        match config.resolve_toolchain_with_source(temp_dir.path(), Some(temp_dir.path())) {
+            Err(error @ JvmsError::UnknownProjectToolchain { .. }) => {
+                assert!(error.to_string().contains(TOOL_VERSIONS_FILE_NAME));
+                assert_eq!(error.get_exit_code(), crate::error::EXIT_NO_TOOLCHAIN);
+            },
+            _ => panic!("expected an unknown project toolchain error")
+        }
+    }
+
+    #[test]
+    fn only_listed_binaries_are_generated_shims() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let installation = JvmsInstallation::new(temp_dir.path().to_path_buf());
+        assert!(!installation.is_generated_shim("jshell").unwrap());
+
+        fs::write(temp_dir.path().join(GENERATED_SHIMS_FILE_NAME), "jfr\njshell\n").unwrap();
+        fs::write(installation.get_binary_path("backup-tool"), "").unwrap();
+
+        assert!(installation.is_generated_shim("jshell").unwrap());
+        assert!(installation.has_binary("backup-tool"));
+        assert!(!installation.is_generated_shim("backup-tool").unwrap());
+    }
+
+    #[test]
+    fn verify_clears_the_unverified_mark_once_the_java_home_exists() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let java_home = temp_dir.path().join("jdk");
+        let mut toolchain = JavaToolchain::new(java_home.clone());
+        toolchain.verified = Some(false);
+
+        assert!(!toolchain.verify());
+        assert!(!toolchain.is_verified());
+
+        create_fake_jdk(&java_home, "17.0.9");
+        assert!(toolchain.verify());
+        assert!(toolchain.is_verified());
+        assert_eq!(toolchain.verified, None);
+        assert_eq!(toolchain.version.as_deref(), Some("17.0.9"));
+        assert!(!toolchain.verify());
+    }
+
+    #[test]
+    fn parses_the_java_entry_of_tool_versions() {
+        assert_eq!(parse_tool_versions("nodejs 20.1.0\njava temurin-21.0.1+12 17 # pinned\n"), Some(vec!["temurin-21.0.1+12".to_owned(), "17".to_owned()]));
+        assert_eq!(parse_tool_versions("nodejs 20.1.0\n"), None);
+        assert_eq!(parse_tool_versions("java\njava 17\n"), Some(vec!["17".to_owned()]));
+        assert_eq!(parse_tool_versions("java # no version yet\n"), None);
+        assert_eq!(strip_asdf_version("temurin-21.0.1+12"), "21.0.1");
+        assert_eq!(strip_asdf_version("zulu17"), "zulu17");
+    }
+
+    #[test]
+    fn tool_versions_without_a_java_version_are_skipped() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project_dir = temp_dir.path().join("project");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::write(temp_dir.path().join(TOOL_VERSIONS_FILE_NAME), "java toolchain-1\n").unwrap();
+        fs::write(project_dir.join(TOOL_VERSIONS_FILE_NAME), "nodejs 20.1.0\njava\n").unwrap();
+
+        let config = config_with_toolchains(2);
+        let resolved = config.resolve_toolchain_with_source(&project_dir, Some(temp_dir.path())).unwrap().unwrap();
+        assert_eq!(resolved.name, Some("toolchain-1"));
+        assert_eq!(resolved.source.get_path(), Some(&*temp_dir.path().join(TOOL_VERSIONS_FILE_NAME)));
+
+        fs::write(project_dir.join(TOOL_VERSIONS_FILE_NAME), "java system\n").unwrap();
+        assert!(config.resolve_tool_versions_toolchain(&project_dir, Some(temp_dir.path())).unwrap().is_none());
+    }
+
+    #[test]
+    fn global_java_version_names_a_toolchain() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let global_version_path = temp_dir.path().join(JAVA_VERSION_FILE_NAME);
+        let config = config_with_toolchains(2);
+
+        assert!(config.resolve_global_java_version(global_version_path.clone()).unwrap().is_none());
+
+        fs::write(&global_version_path, "\n  toolchain-1\n").unwrap();
+        let (path, (name, _)) = config.resolve_global_java_version(global_version_path.clone()).unwrap().unwrap();
+        assert_eq!(path, global_version_path);
+        assert_eq!(name, "toolchain-1");
+
+        fs::write(&global_version_path, "17\n").unwrap();
+        assert!(matches!(
+            config.resolve_global_java_version(global_version_path.clone()),
+            Err(JvmsError::UnknownProjectToolchain { .. })
+        ));
+
+        fs::write(&global_version_path, "\n").unwrap();
+        assert!(config.resolve_global_java_version(global_version_path).unwrap().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn validation_reports_a_java_binary_that_is_not_executable() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let java_home = temp_dir.path().join("jdk");
+        create_fake_jdk(&java_home, "21.0.1");
+        fs::set_permissions(jdk::get_tool_path(&java_home, "java"), fs::Permissions::from_mode(0o644)).unwrap();
+
+        let mut config = JvmsConfiguration::new();
+        config.add_toolchain("21".to_owned(), JavaToolchain::new(java_home.clone()));
+        config.set_default_toolchain_name("21".to_owned());
+        match config.validate_configuration() {
+            Err(JvmsError::ToolchainNotExecutable { name, path }) => {
+                assert_eq!(name, "21");
+                assert_eq!(path, jdk::get_tool_path(&java_home, "java"));
+            },
+            _ => panic!("expected the java binary to be reported as not executable")
+        }
+
+        jdk::set_executable(&jdk::get_tool_path(&java_home, "java")).unwrap();
+        assert!(config.validate_configuration().is_ok());
+    }
+
+    #[test]
+    fn the_most_specific_override_wins_regardless_of_order() {
+        for reverse in &[false, true] {
+            let mut overrides = vec![
+                (PathBuf::from("/work"), "toolchain-0"),
+                (PathBuf::from("/work/project"), "toolchain-1"),
+                (PathBuf::from("/work/project/module"), "toolchain-2")
+            ];
+
+            if *reverse {
+                overrides.reverse();
+            }
+
+            let mut config = config_with_toolchains(3);
+            for (path, toolchain_name) in overrides {
+                config.add_override(&path, toolchain_name.to_owned());
+            }
+
+            let select = |path: &str| config.get_environment_override(Path::new(path)).map(|o| o.toolchain.as_str());
+            assert_eq!(select("/work/project/module/src"), Some("toolchain-2"));
+            assert_eq!(select("/work/project/other"), Some("toolchain-1"));
+            assert_eq!(select("/work/projects"), Some("toolchain-0"));
+            assert_eq!(select("/home"), None);
+        }
+    }
+
+    #[test]
+    fn equivalent_overrides_resolve_to_the_first_listed() {
+        let mut config = config_with_toolchains(2);
+        config.add_override(Path::new("/work/project/"), "toolchain-0".to_owned());
+        config.add_override(Path::new("/work/project"), "toolchain-1".to_owned());
+        assert_eq!(config.get_environment_override(Path::new("/work/project/src")).unwrap().toolchain, "toolchain-0");
+
+        let mut config = config_with_toolchains(2);
+        config.add_override(Path::new("/work/project"), "toolchain-1".to_owned());
+        config.add_override(Path::new("/work/project/"), "toolchain-0".to_owned());
+        assert_eq!(config.get_environment_override(Path::new("/work/project/src")).unwrap().toolchain, "toolchain-1");
+    }
+
+    #[test]
+    fn exact_overrides_only_match_their_own_directory() {
+        let mut config = config_with_toolchains(2);
+        config.add_override(Path::new("/work"), "toolchain-0".to_owned());
+        let mut exact_override = JvmsOverride::new(PathBuf::from("/work/project"), "toolchain-1".to_owned());
+        exact_override.exact = Some(true);
+        config.add_override_entry(exact_override);
+
+        assert_eq!(config.get_environment_override(Path::new("/work/project")).unwrap().toolchain, "toolchain-1");
+        assert_eq!(config.get_environment_override(Path::new("/work/project/src")).unwrap().toolchain, "toolchain-0");
+    }
+
+    #[test]
+    fn builder_builds_valid_configurations() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let java_home = temp_dir.path().join("jdk");
+        create_fake_jdk(&java_home, "21.0.1");
+        jdk::set_executable(&jdk::get_tool_path(&java_home, "java")).unwrap();
+
+        let config = JvmsConfigurationBuilder::new()
+            .toolchain("21", &java_home)
+            .default_toolchain("21")
+            .override_toolchain("/work/project", "21")
+            .strict(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.get_default_toolchain_name(), Some("21"));
+        assert_eq!(config.select_toolchain("21").unwrap().1.java_home, java_home);
+        assert_eq!(config.get_environment_override(Path::new("/work/project")).unwrap().toolchain, "21");
+        assert!(config.is_strict());
+    }
+
+    #[test]
+    fn builder_rejects_invalid_configurations_unless_unchecked() {
+        let builder = || JvmsConfigurationBuilder::new().toolchain("toolchain-0", "/opt/jdk-0").default_toolchain("missing");
+        assert!(builder().build().is_err());
+
+        let config = builder().build_unchecked();
+        assert_eq!(config.get_default_toolchain_name(), Some("missing"));
+        assert!(matches!(JvmsConfigurationBuilder::new().build(), Err(JvmsError::InvalidConfiguration(_))));
+    }
+
+    #[test]
+    fn builder_replaces_overrides_of_the_same_path() {
+        let config = JvmsConfigurationBuilder::new()
+            .toolchain("toolchain-0", "/opt/jdk-0")
+            .toolchain("toolchain-1", "/opt/jdk-1")
+            .override_toolchain("/work/project", "toolchain-0")
+            .override_toolchain("/work/project", "toolchain-1")
+            .build_unchecked();
+
+        let overrides = config.get_overrides().unwrap();
+        assert_eq!(overrides.len(), 1);
+        assert_eq!(overrides[0].toolchain, "toolchain-1");
+    }
+
+    #[test]
+    fn reloads_the_configuration_only_when_the_file_changed() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_file_path = temp_dir.path().join("jvms.toml");
+        let installation = JvmsInstallation::new(temp_dir.path().to_path_buf()).with_config_file(&config_file_path);
+        installation.save_configuration(&config_with_toolchains(1), true).unwrap();
+        installation.unlock_configuration();
+
+        let mut config = installation.load_configuration().unwrap();
+        assert!(!installation.reload_if_changed(&mut config).unwrap());
+
+        //
+        // Another process saves the configuration, pinning the modification time so that it differs even on file
+        // systems with a coarse timestamp resolution.
+        //
+
+        let other_installation = JvmsInstallation::new(temp_dir.path().to_path_buf()).with_config_file(&config_file_path);
+        let mut other_config = other_installation.load_configuration().unwrap();
+        other_config.add_toolchain("toolchain-1".to_owned(), JavaToolchain::new(PathBuf::from("/opt/jdk-1")));
+        other_installation.save_configuration(&other_config, true).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&config_file_path)
+            .unwrap()
+            .set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(86400))
+            .unwrap();
+
+        assert!(installation.reload_if_changed(&mut config).unwrap());
+        assert_eq!(config.get_toolchains().count(), 2);
+        assert!(!installation.reload_if_changed(&mut config).unwrap());
+    }
+
+    #[test]
+    fn cleans_overrides_selected_by_the_policy() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let existing_dir = temp_dir.path().join("existing");
+        let missing_dir = temp_dir.path().join("missing");
+        fs::create_dir(&existing_dir).unwrap();
+
+        let config_with_overrides = || {
+            let mut config = config_with_toolchains(1);
+            config.add_override(&existing_dir, "toolchain-0".to_owned());
+            config.add_override(&existing_dir.join("removed"), "removed-toolchain".to_owned());
+            config.add_override(&missing_dir, "toolchain-0".to_owned());
+            config
+        };
+
+        let removed_paths = |policy: OverrideCleanPolicy| {
+            let mut config = config_with_overrides();
+            let removed: Vec<_> = config.clean_overrides(policy).into_iter().map(|(o, reason)| (o.path, reason)).collect();
+            (removed, config.get_overrides().unwrap().len())
+        };
+
+        fs::create_dir(existing_dir.join("removed")).unwrap();
+        assert_eq!(removed_paths(OverrideCleanPolicy::MissingToolchain), (vec![(existing_dir.join("removed"), "toolchain no longer exists")], 2));
+        assert_eq!(removed_paths(OverrideCleanPolicy::MissingDirectory), (vec![(missing_dir.clone(), "directory no longer exists")], 2));
+
+        let (removed, kept_count) = removed_paths(OverrideCleanPolicy::All);
+        assert_eq!(removed.len(), 2);
+        assert_eq!(kept_count, 1);
+
+        let mut config = config_with_toolchains(1);
+        assert!(config.clean_overrides(OverrideCleanPolicy::All).is_empty());
+    }
+
+    fn merge_configurations(policy: MergePolicy) -> (JvmsConfiguration, MergeReport) {
+        let mut config = config_with_toolchains(2);
+        config.set_default_toolchain_name("toolchain-0".to_owned());
+        config.add_override(Path::new("/work/a"), "toolchain-0".to_owned());
+
+        let mut other = config_with_toolchains(1);
+        other.add_toolchain("toolchain-1".to_owned(), JavaToolchain::new(PathBuf::from("/opt/other-1")));
+        other.add_toolchain("toolchain-2".to_owned(), JavaToolchain::new(PathBuf::from("/opt/jdk-2")));
+        other.set_default_toolchain_name("toolchain-1".to_owned());
+        other.add_override(Path::new("/work/a"), "toolchain-1".to_owned());
+        other.add_override(Path::new("/work/b"), "toolchain-2".to_owned());
+
+        let report = config.merge(other, policy);
+        (config, report)
+    }
+
+    #[test]
+    fn merging_keeps_existing_entries_on_conflict() {
+        let (config, report) = merge_configurations(MergePolicy::PreferExisting);
+        assert_eq!(report.added, vec!["toolchain toolchain-2".to_owned(), format!("override {:?}", Path::new("/work/b"))]);
+        assert_eq!(report.skipped, vec!["toolchain toolchain-1".to_owned(), format!("override {:?}", Path::new("/work/a")), "default".to_owned()]);
+        assert!(report.updated.is_empty());
+
+        assert_eq!(config.get_toolchain("toolchain-1").unwrap().java_home, PathBuf::from("/opt/jdk-1"));
+        assert_eq!(config.get_environment_override(Path::new("/work/a")).unwrap().toolchain, "toolchain-0");
+        assert_eq!(config.get_environment_override(Path::new("/work/b")).unwrap().toolchain, "toolchain-2");
+        assert_eq!(config.get_default_toolchain_name(), Some("toolchain-0"));
+    }
+
+    #[test]
+    fn merging_can_prefer_imported_entries() {
+        let (config, report) = merge_configurations(MergePolicy::PreferImported);
+        assert_eq!(report.added.len(), 2);
+        assert_eq!(report.updated, vec!["toolchain toolchain-1".to_owned(), format!("override {:?}", Path::new("/work/a")), "default".to_owned()]);
+        assert!(report.skipped.is_empty());
+
+        assert_eq!(config.get_toolchain("toolchain-1").unwrap().java_home, PathBuf::from("/opt/other-1"));
+        assert_eq!(config.get_overrides().unwrap().len(), 2);
+        assert_eq!(config.get_environment_override(Path::new("/work/a")).unwrap().toolchain, "toolchain-1");
+        assert_eq!(config.get_default_toolchain_name(), Some("toolchain-1"));
+    }
+
+    #[test]
+    fn resolves_the_java_home_of_a_pinned_toolchain() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let project_dir = temp_dir.path().join("project");
+        let shared_home = temp_dir.path().join("shared").join("jdk-17");
+        create_fake_jdk(&project_dir.join("jdk"), "21.0.1");
+        create_fake_jdk(&shared_home, "17.0.9");
+
+        //
+        // Commands make java homes absolute when adding them, so a relative one can only come from an edited file.
+        //
+
+        let config = JvmsConfiguration::parse(Path::new("jvms.toml"), format!(r#"
+            [toolchains.relative]
+            java_home = "./jdk"
+
+            [toolchains.absolute]
+            java_home = '{}'
+        "#, shared_home.display()).as_bytes()).unwrap();
+
+        assert_eq!(config.resolve_home(&project_dir, Some(temp_dir.path()), Some("relative")).unwrap(), project_dir.join("jdk"));
+        assert_eq!(config.resolve_home(&project_dir, Some(temp_dir.path()), Some("absolute")).unwrap(), shared_home);
+
+        let resolved = config.resolve_home_with_toolchain(&project_dir, Some(temp_dir.path()), Some("relative")).unwrap();
+        assert_eq!(resolved.name, Some("relative"));
+        assert_eq!(resolved.toolchain.unwrap().java_home, PathBuf::from("./jdk"));
+    }
+
+    #[test]
+    fn expands_variables_in_paths() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(expand_path("${CARGO_MANIFEST_DIR}/jdk").unwrap(), manifest_dir.join("jdk"));
+        assert_eq!(expand_path("$CARGO_MANIFEST_DIR/jdk").unwrap(), manifest_dir.join("jdk"));
+        assert_eq!(expand_path("/opt/jdk").unwrap(), PathBuf::from("/opt/jdk"));
+        assert!(matches!(expand_path("${CARGO_MANIFEST_DIR/jdk"), Err(JvmsError::InvalidConfiguration(_))));
+    }
+
+    #[test]
+    fn resolving_a_missing_java_home_fails() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config = config_with_toolchains(1);
+
+        match config.resolve_home(temp_dir.path(), Some(temp_dir.path()), Some("toolchain-0")) {
+            Err(JvmsError::ToolchainHomeMissing { name, path }) => {
+                assert_eq!(name, "toolchain-0");
+                assert_eq!(path, PathBuf::from("/opt/jdk-0"));
+            },
+            _ => panic!("expected the java home to be missing")
+        }
+
+        assert!(matches!(config.resolve_home(temp_dir.path(), Some(temp_dir.path()), Some("missing")), Err(JvmsError::UnknownToolchain(_))));
+    }
+
+    #[test]
+    fn finds_toolchains_by_their_java_home() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let java_home = temp_dir.path().join("jdk");
+        create_fake_jdk(&java_home, "21.0.1");
+
+        let mut config = config_with_toolchains(1);
+        config.add_toolchain("zulu-21".to_owned(), JavaToolchain::new(java_home.clone()));
+        config.add_toolchain("temurin-21".to_owned(), JavaToolchain::new(java_home.clone()));
+
+        assert_eq!(config.find_toolchain_by_home(&java_home), Some("temurin-21"));
+        assert_eq!(config.find_toolchain_by_home(&java_home.join("bin").join("..")), Some("temurin-21"));
+        assert_eq!(config.find_toolchain_by_home(Path::new("/opt/jdk-0")), Some("toolchain-0"));
+        assert_eq!(config.find_toolchain_by_home(&temp_dir.path().join("other")), None);
+
+        #[cfg(unix)]
+        {
+            let link_path = temp_dir.path().join("current");
+            std::os::unix::fs::symlink(&java_home, &link_path).unwrap();
+            assert_eq!(config.find_toolchain_by_home(&link_path), Some("temurin-21"));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn finds_the_installation_through_symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let installation_dir = temp_dir.path().join("installation");
+        let store_dir = temp_dir.path().join("store");
+        let bin_dir = temp_dir.path().join("bin");
+        fs::create_dir(&installation_dir).unwrap();
+        fs::create_dir(&store_dir).unwrap();
+        fs::write(installation_dir.join(CONFIG_FILE_NAME), "").unwrap();
+        fs::write(store_dir.join("jvms"), "").unwrap();
+        symlink(&installation_dir, &bin_dir).unwrap();
+
+        let find = |current_exe: &Path, invoked_path: Option<&Path>, install_dir: Option<&Path>| {
+            let installation = JvmsInstallation::find_installation(current_exe.to_path_buf(), invoked_path.map(Path::to_path_buf), install_dir.map(Path::to_path_buf));
+            fs::canonicalize(installation.get_installation_path()).unwrap()
+        };
+
+        let installation_dir = fs::canonicalize(&installation_dir).unwrap();
+        let store_dir = fs::canonicalize(&store_dir).unwrap();
+
+        //
+        // A binary in a symlinked bin directory resolves to the installation itself.
+        //
+
+        fs::write(installation_dir.join("jvms"), "").unwrap();
+        assert_eq!(find(&bin_dir.join("jvms"), Some(&bin_dir.join("jvms")), None), installation_dir);
+
+        //
+        // A binary symlinked into the installation from elsewhere is found through the path it was invoked as,
+        // falling back to JVMS_INSTALL_DIR and then to the directory of the binary itself.
+        //
+
+        fs::remove_file(installation_dir.join("jvms")).unwrap();
+        symlink(store_dir.join("jvms"), installation_dir.join("jvms")).unwrap();
+        assert_eq!(find(&bin_dir.join("jvms"), Some(&bin_dir.join("jvms")), None), installation_dir);
+        assert_eq!(find(&bin_dir.join("jvms"), Some(&store_dir.join("jvms")), Some(&installation_dir)), installation_dir);
+        assert_eq!(find(&bin_dir.join("jvms"), None, None), store_dir);
+    }
+
+    #[test]
+    fn reinstalling_shims_replaces_stale_and_missing_shims() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let installation = JvmsInstallation::new(temp_dir.path().to_path_buf());
+        assert!(matches!(installation.reinstall_shims(LinkMode::Copy), Err(JvmsError::InvalidConfiguration(_))));
+
+        fs::write(installation.get_binary_path("jvms"), "new jvms").unwrap();
+        fs::write(installation.get_binary_path("java"), "old jvms").unwrap();
+        fs::write(temp_dir.path().join(GENERATED_SHIMS_FILE_NAME), "jfr\n").unwrap();
+
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(temp_dir.path().join("removed-jvms"), installation.get_binary_path("javac")).unwrap();
+
+        assert_eq!(installation.reinstall_shims(LinkMode::Copy).unwrap(), Shim::get_shims().len() + 1);
+        for shim_name in Shim::get_shims().iter().map(Shim::name).chain(Some("jfr")) {
+            assert_eq!(fs::read_to_string(installation.get_binary_path(shim_name)).unwrap(), "new jvms", "shim {} was not replaced", shim_name);
+        }
+
+        assert!(installation.get_missing_shims().is_empty());
+        assert_eq!(fs::read_to_string(installation.get_binary_path("jvms")).unwrap(), "new jvms");
+    }
+
+    #[test]
+    fn overrides_are_listed_and_saved_sorted_by_path() {
+        let paths = ["/work/b", "/work/a/nested", "/home/project", "/work/a"];
+        let config_with_overrides = |paths: &[&str]| {
+            let mut config = config_with_toolchains(1);
+            for path in paths {
+                config.add_override(Path::new(path), "toolchain-0".to_owned());
+            }
+
+            config
+        };
+
+        let config = config_with_overrides(&paths);
+        let sorted: Vec<_> = config.iter_overrides_sorted().map(|o| o.path.clone()).collect();
+        assert_eq!(sorted, ["/home/project", "/work/a", "/work/a/nested", "/work/b"].iter().map(PathBuf::from).collect::<Vec<_>>());
+        assert_eq!(config.get_overrides().unwrap()[0].path, PathBuf::from("/work/b"));
+
+        let mut reversed_paths = paths;
+        reversed_paths.reverse();
+        let reversed_config = config_with_overrides(&reversed_paths);
+        assert_eq!(toml::to_string_pretty(&config).unwrap(), toml::to_string_pretty(&reversed_config).unwrap());
+        assert_eq!(serde_json::to_string(&config).unwrap(), serde_json::to_string(&reversed_config).unwrap());
+
+        let saved: JvmsConfiguration = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        let saved_paths: Vec<_> = saved.get_overrides().unwrap().iter().map(|o| o.path.clone()).collect();
+        assert_eq!(saved_paths, sorted);
+    }
+
+    #[test]
+    fn concurrent_readers_never_observe_a_partial_write() {
+        const SMALL: usize = 1;
+        const LARGE: usize = 200;
+        const WRITES: usize = 200;
+        const READERS: usize = 4;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_file_path = temp_dir.path().join("jvms.toml");
+        let installation_path = temp_dir.path().to_path_buf();
+        JvmsInstallation::new(installation_path.clone())
+            .with_config_file(&config_file_path)
+            .save_configuration(&config_with_toolchains(SMALL), true)
+            .unwrap();
+
+        let done = Arc::new(AtomicBool::new(false));
+        let readers: Vec<_> = (0..READERS)
+            .map(|_| {
+                let done = Arc::clone(&done);
+                let installation_path = installation_path.clone();
+                let config_file_path = config_file_path.clone();
+                thread::spawn(move || {
+                    let installation = JvmsInstallation::new(installation_path).with_config_file(config_file_path);
+                    let mut reads = 0;
+                    while !done.load(AtomicOrdering::SeqCst) {
+                        let count = installation.load_configuration().unwrap().get_toolchains().count();
+                        assert!(count == SMALL || count == LARGE, "read a configuration with {} toolchains", count);
+                        reads += 1;
+                    }
+
+                    reads
+                })
+            })
+            .collect();
+
+        let writer = JvmsInstallation::new(installation_path).with_config_file(&config_file_path);
+        for write in 0..WRITES {
+            let mut config = writer.load_configuration().unwrap();
+            config.toolchains = config_with_toolchains(if write % 2 == 0 { LARGE } else { SMALL }).toolchains;
+            writer.save_configuration(&config, true).unwrap();
+        }
+
+        done.store(true, AtomicOrdering::SeqCst);
+        for reader in readers {
+            assert!(reader.join().unwrap() > 0);
         }
     }
 
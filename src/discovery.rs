@@ -0,0 +1,355 @@
+
+use crate::error::Result;
+use crate::jdk::{self, JdkInfo};
+use crate::selector;
+use std::{env, fs};
+use std::path::{Path, PathBuf};
+
+#[cfg(target_os="macos")]
+use std::process;
+
+///
+/// Short names of well-known vendors, keyed by the IMPLEMENTOR of their release files.
+///
+static VENDOR_NAMES: [(&str, &str); 11] = [
+    ("Eclipse Adoptium", "temurin"),
+    ("AdoptOpenJDK", "adoptopenjdk"),
+    ("Amazon.com Inc.", "corretto"),
+    ("Azul Systems, Inc.", "zulu"),
+    ("BellSoft", "liberica"),
+    ("GraalVM Community", "graalvm"),
+    ("Microsoft", "microsoft"),
+    ("Oracle Corporation", "oracle"),
+    ("Red Hat, Inc.", "redhat"),
+    ("SAP SE", "sapmachine"),
+    ("Tencent", "kona")
+];
+
+///
+/// Glob patterns matched against the full path of discovered directories. A path is considered if it matches any
+/// include pattern (or there are none) and no exclude pattern.
+///
+#[derive(Default)]
+pub struct DiscoveryFilter {
+    pub include: Vec<String>,
+    pub exclude: Vec<String>
+}
+
+///
+/// A java home found by discovery.
+///
+pub struct DiscoveredJdk {
+    pub java_home: PathBuf,
+    pub info: JdkInfo
+}
+
+impl DiscoveryFilter {
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        let path = path.to_string_lossy();
+        self.exclude.iter().any(|pattern| selector::glob_matches_str(pattern, &path))
+    }
+
+    fn is_included(&self, path: &Path) -> bool {
+        let path_str = path.to_string_lossy();
+        !self.is_excluded(path)
+            && (self.include.is_empty() || self.include.iter().any(|pattern| selector::glob_matches_str(pattern, &path_str)))
+    }
+
+}
+
+fn looks_like_java_home(path: &Path) -> bool {
+    jdk::get_tool_path(path, "java").is_file()
+}
+
+///
+/// Collects the directories under the search path that look like java homes, without descending into them or into
+/// excluded directories.
+///
+fn collect_candidates(dir: &Path, recursive: bool, filter: &DiscoveryFilter, candidates: &mut Vec<PathBuf>) -> Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    entries.sort();
+
+    for path in entries {
+        if filter.is_excluded(&path) {
+            continue;
+        }
+
+        if looks_like_java_home(&path) {
+            candidates.push(path);
+
+        } else if recursive {
+            collect_candidates(&path, recursive, filter, candidates)?;
+        }
+    }
+
+    Ok(())
+}
+
+///
+/// Finds the JDKs at or directly below the search path, or anywhere below it when recursive. Candidates are filtered
+/// before they are inspected, so excluded directories are never probed.
+///
+pub fn discover(search_path: &Path, recursive: bool, filter: &DiscoveryFilter) -> Result<Vec<DiscoveredJdk>> {
+    let mut candidates = Vec::new();
+    if looks_like_java_home(search_path) {
+        candidates.push(search_path.to_path_buf());
+
+    } else {
+        collect_candidates(search_path, recursive, filter, &mut candidates)?;
+    }
+
+    Ok(candidates
+        .into_iter()
+        .filter(|java_home| filter.is_included(java_home))
+        .filter_map(|java_home| {
+            JdkInfo::inspect(&java_home)
+                .ok()
+                .map(|info| DiscoveredJdk { java_home, info })
+        })
+        .collect())
+}
+
+///
+/// Lists the immediate subdirectories of a directory, sorted, or nothing if it cannot be read.
+///
+fn list_subdirectories(dir: &Path) -> Vec<PathBuf> {
+    let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect(),
+        Err(_) => Vec::new()
+    };
+    entries.sort();
+    entries
+}
+
+///
+/// Returns the locations JDKs are commonly installed to on this platform that exist. Each location is either a java
+/// home or a directory whose direct children are java homes, as expected by `discover`.
+///
+/// macOS bundles and Homebrew kegs keep the java home nested inside the installed directory, so those are expanded
+/// to the nested java homes.
+///
+pub fn get_well_known_locations() -> Vec<PathBuf> {
+    let mut locations = Vec::new();
+
+    #[cfg(target_os="linux")]
+    {
+        locations.push(PathBuf::from("/usr/lib/jvm"));
+    }
+
+    #[cfg(target_os="macos")]
+    {
+        let mut bundle_dirs = vec![PathBuf::from("/Library/Java/JavaVirtualMachines")];
+        if let Some(home_dir) = dirs::home_dir() {
+            bundle_dirs.push(home_dir.join("Library/Java/JavaVirtualMachines"));
+        }
+
+        for bundle_dir in bundle_dirs {
+            for bundle in list_subdirectories(&bundle_dir) {
+                locations.push(bundle.join("Contents").join("Home"));
+            }
+        }
+    }
+
+    #[cfg(target_os="windows")]
+    {
+        if let Some(program_files) = env::var_os("ProgramFiles") {
+            locations.push(PathBuf::from(program_files).join("Java"));
+        }
+    }
+
+    let sdkman_dir = env::var_os("SDKMAN_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|home_dir| home_dir.join(".sdkman")));
+    if let Some(sdkman_dir) = sdkman_dir {
+        locations.push(sdkman_dir.join("candidates").join("java"));
+    }
+
+    let mut cellars: Vec<PathBuf> = env::var_os("HOMEBREW_CELLAR").map(PathBuf::from).into_iter().collect();
+    for cellar in &["/opt/homebrew/Cellar", "/usr/local/Cellar", "/home/linuxbrew/.linuxbrew/Cellar"] {
+        if !cellars.iter().any(|existing| existing == Path::new(cellar)) {
+            cellars.push(PathBuf::from(cellar));
+        }
+    }
+
+    for cellar in cellars {
+        let formulas = list_subdirectories(&cellar)
+            .into_iter()
+            .filter(|formula| formula.file_name().is_some_and(|name| name.to_string_lossy().starts_with("openjdk")));
+
+        for keg in formulas.flat_map(|formula| list_subdirectories(&formula)) {
+            let bundle_home = keg.join("libexec").join("openjdk.jdk").join("Contents").join("Home");
+            locations.push(if bundle_home.is_dir() { bundle_home } else { keg.join("libexec") });
+        }
+    }
+
+    locations.retain(|location| location.is_dir());
+    locations
+}
+
+///
+/// Parses a JDK line of the listing printed by macOS's `/usr/libexec/java_home -V`, returning its java home and the
+/// version, architecture, and vendor shown for it. Both the current layout and the one of macOS 10.15 and earlier
+/// are understood:
+///
+/// ```text
+///     21.0.1 (arm64) "Eclipse Adoptium" - "OpenJDK 21.0.1" /Library/Java/JavaVirtualMachines/temurin-21.jdk/Contents/Home
+///     11.0.2, x86_64:	"OpenJDK 11.0.2"	/Library/Java/JavaVirtualMachines/openjdk-11.0.2.jdk/Contents/Home
+/// ```
+///
+#[cfg(target_os="macos")]
+fn parse_java_home_line(line: &str) -> Option<(PathBuf, JdkInfo)> {
+    //
+    // JDK lines are indented, unlike the header and the trailing line naming the default java home.
+    //
+
+    if !line.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let line = line.trim();
+    let last_quote = line.rfind('"')?;
+    let java_home = line[last_quote + 1..].trim();
+    if !java_home.starts_with('/') {
+        return None;
+    }
+
+    let first_quote = line.find('"')?;
+    let mut fields = line[..first_quote]
+        .split(|c: char| c.is_whitespace() || ",:()".contains(c))
+        .filter(|field| !field.is_empty());
+    let version = fields.next().map(|version| version.to_owned());
+    let arch = fields.next().map(|arch| arch.to_owned());
+
+    // The vendor is only listed by the current layout, as the first of two quoted strings.
+    let quoted: Vec<&str> = line[first_quote..=last_quote].split('"').skip(1).step_by(2).collect();
+    let vendor = if quoted.len() >= 2 { Some(quoted[0].to_owned()) } else { None };
+
+    Some((PathBuf::from(java_home), JdkInfo { version, vendor, arch }))
+}
+
+///
+/// Finds the JDKs known to macOS through `/usr/libexec/java_home -V`, which lists the installed JDK bundles
+/// including Homebrew kegs linked into the JavaVirtualMachines directories. The version, vendor, and architecture
+/// come from the release file of each java home, falling back to those shown by java_home. Returns nothing on other
+/// platforms or when java_home can't be run.
+///
+pub fn discover_java_home(filter: &DiscoveryFilter) -> Vec<DiscoveredJdk> {
+    #[cfg(target_os="macos")]
+    {
+        let output = match process::Command::new("/usr/libexec/java_home").arg("-V").output() {
+            Ok(output) => output,
+            Err(_) => return Vec::new()
+        };
+
+        // The listing is printed to stderr, followed by the default java home on stdout.
+        String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .filter_map(parse_java_home_line)
+            .filter(|(java_home, _)| filter.is_included(java_home))
+            .filter_map(|(java_home, listed_info)| {
+                let info = JdkInfo::inspect(&java_home).ok()?;
+                let info = JdkInfo {
+                    version: info.version.or(listed_info.version),
+                    vendor: info.vendor.or(listed_info.vendor),
+                    arch: info.arch.or(listed_info.arch)
+                };
+
+                Some(DiscoveredJdk { java_home, info })
+            })
+            .collect()
+    }
+
+    #[cfg(not(target_os="macos"))]
+    {
+        let _ = filter;
+        Vec::new()
+    }
+}
+
+///
+/// Generates a toolchain name from the vendor and version of a JDK, such as "temurin-21.0.1". Vendors without a
+/// well-known short name use the first word of their name, and JDKs without a vendor are named "openjdk".
+///
+pub fn generate_toolchain_name(info: &JdkInfo) -> Option<String> {
+    let version = info.version.as_ref()?;
+    let vendor = match info.vendor.as_deref().filter(|vendor| !vendor.is_empty() && *vendor != "N/A") {
+        Some(vendor) => VENDOR_NAMES
+            .iter()
+            .find(|(implementor, _)| *implementor == vendor)
+            .map(|(_, name)| (*name).to_owned())
+            .unwrap_or_else(|| {
+                vendor
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .find(|word| !word.is_empty())
+                    .unwrap_or("openjdk")
+                    .to_lowercase()
+            }),
+        None => "openjdk".to_owned()
+    };
+
+    Some(format!("{}-{}", vendor, version))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jdk::tests::create_fake_jdk;
+
+    fn discovered_names(search_path: &Path, recursive: bool, filter: &DiscoveryFilter) -> Vec<String> {
+        discover(search_path, recursive, filter)
+            .unwrap()
+            .into_iter()
+            .map(|jdk| jdk.java_home.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn filter(include: &[&str], exclude: &[&str]) -> DiscoveryFilter {
+        DiscoveryFilter {
+            include: include.iter().map(|pattern| (*pattern).to_owned()).collect(),
+            exclude: exclude.iter().map(|pattern| (*pattern).to_owned()).collect()
+        }
+    }
+
+    #[test]
+    fn discovers_java_homes_below_the_search_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        create_fake_jdk(&temp_dir.path().join("temurin-17"), "17.0.2");
+        create_fake_jdk(&temp_dir.path().join("temurin-21"), "21.0.1");
+        create_fake_jdk(&temp_dir.path().join("ea").join("jdk-22"), "22.0.1");
+        fs::create_dir(temp_dir.path().join("empty")).unwrap();
+
+        let no_filter = DiscoveryFilter::default();
+        assert_eq!(discovered_names(temp_dir.path(), false, &no_filter), vec!["temurin-17", "temurin-21"]);
+        assert_eq!(discovered_names(temp_dir.path(), true, &no_filter), vec!["jdk-22", "temurin-17", "temurin-21"]);
+        assert_eq!(discovered_names(&temp_dir.path().join("temurin-21"), false, &no_filter), vec!["temurin-21"]);
+    }
+
+    #[test]
+    fn filters_discovered_java_homes_by_path() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        create_fake_jdk(&temp_dir.path().join("temurin-17"), "17.0.2");
+        create_fake_jdk(&temp_dir.path().join("temurin-21"), "21.0.1");
+        create_fake_jdk(&temp_dir.path().join("zulu-21"), "21.0.1");
+        create_fake_jdk(&temp_dir.path().join("ea").join("temurin-22"), "22.0.1");
+
+        assert_eq!(discovered_names(temp_dir.path(), true, &filter(&["*temurin-*"], &[])), vec!["temurin-22", "temurin-17", "temurin-21"]);
+        assert_eq!(discovered_names(temp_dir.path(), true, &filter(&["*-21"], &[])), vec!["temurin-21", "zulu-21"]);
+        assert_eq!(discovered_names(temp_dir.path(), true, &filter(&["*temurin-*"], &["*-17"])), vec!["temurin-22", "temurin-21"]);
+
+        //
+        // An excluded directory is not descended into, even if a java home inside it would be included.
+        //
+
+        assert_eq!(discovered_names(temp_dir.path(), true, &filter(&["*temurin-22"], &["*ea"])), Vec::<String>::new());
+    }
+
+}
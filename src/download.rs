@@ -0,0 +1,244 @@
+
+use crate::error::{JvmsError, Result};
+use log::debug;
+use sha2::{Digest, Sha256};
+use std::{env, fs, io};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process;
+
+///
+/// Number of bytes between progress updates while downloading.
+///
+const PROGRESS_INTERVAL: u64 = 1024 * 1024;
+
+///
+/// The outcome of checking a detached GPG signature.
+///
+pub enum SignatureStatus {
+    Valid,
+    /// The signature could not be checked, e.g. because gpg is not installed or lacks the signing key.
+    Unchecked(String)
+}
+
+fn to_mib(bytes: u64) -> f64 {
+    bytes as f64 / (1024.0 * 1024.0)
+}
+
+///
+/// Returns the first of the environment variables that is set and not empty.
+///
+fn get_env(names: &[&str]) -> Option<String> {
+    names.iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+///
+/// Extracts the host from a url, without user info, port, or IPv6 brackets.
+///
+fn get_url_host(url: &str) -> Option<&str> {
+    let authority = url.split_once("://")?.1.split(['/', '?', '#']).next()?;
+    let host_port = authority.rsplit('@').next()?;
+    match host_port.strip_prefix('[') {
+        Some(ipv6_host) => ipv6_host.split(']').next(),
+        None => host_port.split(':').next()
+    }
+}
+
+///
+/// Checks whether a NO_PROXY value excludes the host, by listing "*", the host, or a domain the host is part of.
+/// Ports in the entries are ignored.
+///
+fn is_proxy_excluded(host: &str, no_proxy: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    no_proxy
+        .split(',')
+        .map(|entry| entry.trim())
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+
+            let entry = entry.trim_start_matches('*').trim_start_matches('.');
+            let entry = match entry.strip_prefix('[') {
+                Some(ipv6_entry) => ipv6_entry.split(']').next().unwrap_or_default(),
+                None if entry.matches(':').count() == 1 => entry.split(':').next().unwrap_or_default(),
+                None => entry
+            };
+
+            let entry = entry.to_ascii_lowercase();
+            host == entry || host.ends_with(&format!(".{}", entry))
+        })
+}
+
+///
+/// Returns the proxy to reach the url through: https_proxy or HTTPS_PROXY for https urls and http_proxy or
+/// HTTP_PROXY otherwise, falling back to all_proxy or ALL_PROXY. No proxy is used for hosts excluded by no_proxy or
+/// NO_PROXY.
+///
+fn get_proxy(url: &str) -> Option<String> {
+    let host = get_url_host(url)?;
+    if get_env(&["no_proxy", "NO_PROXY"]).is_some_and(|no_proxy| is_proxy_excluded(host, &no_proxy)) {
+        return None;
+    }
+
+    let scheme_proxy = if url.starts_with("https://") {
+        get_env(&["https_proxy", "HTTPS_PROXY"])
+    } else {
+        get_env(&["http_proxy", "HTTP_PROXY"])
+    };
+
+    scheme_proxy.or_else(|| get_env(&["all_proxy", "ALL_PROXY"]))
+}
+
+///
+/// Creates a GET request for the url, going through the proxy configured in the environment for it, if any.
+///
+pub fn get(url: &str) -> Result<ureq::Request> {
+    let mut agent_builder = ureq::AgentBuilder::new().try_proxy_from_env(false);
+    if let Some(proxy) = get_proxy(url) {
+        let parsed_proxy = ureq::Proxy::new(&proxy)
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Invalid proxy {}: {}", proxy, error))
+            })?;
+
+        debug!("Requesting {} through the proxy {}", url, proxy);
+        agent_builder = agent_builder.proxy(parsed_proxy);
+
+    } else {
+        debug!("Requesting {}", url);
+    }
+
+    Ok(agent_builder.build().get(url))
+}
+
+///
+/// Downloads the url to the destination, printing progress against the expected size. A partial download is removed
+/// if the transfer fails.
+///
+pub fn download(url: &str, destination: &Path, expected_size: u64) -> Result<()> {
+    let response =
+        get(url)?
+            .call()
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Failed to download {}: {}", url, error))
+            })?;
+
+    let mut file = fs::File::create(destination)?;
+    if let Err(error) = copy_with_progress(&mut response.into_reader(), &mut file, expected_size) {
+        println!();
+        drop(file);
+        let _ = fs::remove_file(destination);
+        return Err(JvmsError::NetworkError(format!("Failed to download {}: {}", url, error)));
+    }
+
+    Ok(())
+}
+
+///
+/// Downloads a small file, such as a signature, into memory without reporting progress.
+///
+pub fn fetch(url: &str) -> Result<Vec<u8>> {
+    let response =
+        get(url)?
+            .call()
+            .map_err(|error| {
+                JvmsError::NetworkError(format!("Failed to download {}: {}", url, error))
+            })?;
+
+    let mut contents = Vec::new();
+    response.into_reader().read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+fn copy_with_progress(reader: &mut dyn Read, writer: &mut dyn Write, expected_size: u64) -> io::Result<()> {
+    let mut buffer = [0; 64 * 1024];
+    let mut downloaded = 0;
+    let mut next_progress = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        writer.write_all(&buffer[..read])?;
+        downloaded += read as u64;
+
+        if downloaded >= next_progress {
+            if expected_size != 0 {
+                print!("\rDownloaded {:.1} of {:.1} MiB ({}%)...", to_mib(downloaded), to_mib(expected_size), downloaded * 100 / expected_size);
+
+            } else {
+                print!("\rDownloaded {:.1} MiB...", to_mib(downloaded));
+            }
+
+            let _ = io::stdout().flush();
+            next_progress = downloaded + PROGRESS_INTERVAL;
+        }
+    }
+
+    writer.flush()?;
+    println!("\rDownloaded {:.1} MiB.", to_mib(downloaded));
+    Ok(())
+}
+
+///
+/// Computes the SHA-256 of the file at the path as a lowercase hex string.
+///
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(hasher.finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+///
+/// Checks that the SHA-256 of the file at the path matches the expected hex digest, ignoring case.
+///
+pub fn verify_sha256(path: &Path, expected: &str) -> Result<()> {
+    let actual = sha256_file(path)?;
+    if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(JvmsError::InvalidToolchain(format!("Checksum mismatch for {:?}, expected {} but got {}.", path, expected, actual)));
+    }
+
+    Ok(())
+}
+
+///
+/// Checks a detached GPG signature of the file at the path. A signature that does not match fails, while one that
+/// gpg can't check is reported as unchecked.
+///
+pub fn verify_gpg_signature(path: &Path, signature_path: &Path) -> Result<SignatureStatus> {
+    let output = process::Command::new("gpg")
+        .arg("--batch")
+        .arg("--verify")
+        .arg(signature_path)
+        .arg(path)
+        .output();
+
+    let output = match output {
+        Ok(output) => output,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(SignatureStatus::Unchecked("gpg is not installed".to_owned())),
+        Err(error) => return Err(error.into())
+    };
+
+    // gpg exits with 1 for a bad signature and 2 when it could not check it, e.g. for a missing public key.
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stderr.lines()
+        .filter_map(|line| line.strip_prefix("gpg: "))
+        .next_back()
+        .unwrap_or_default()
+        .trim()
+        .to_owned();
+    match output.status.code() {
+        Some(0) => Ok(SignatureStatus::Valid),
+        Some(1) => Err(JvmsError::InvalidToolchain(format!("The GPG signature of {:?} does not match: {}", path, message))),
+        _ => Ok(SignatureStatus::Unchecked(message))
+    }
+}
@@ -1,11 +1,145 @@
 
-use std::io;
+use std::{error, fmt, io};
+use std::path::PathBuf;
+
+///
+/// Exit code for failures without a more specific code, including checks that did not pass.
+///
+pub const EXIT_FAILURE: i32 = 1;
+
+///
+/// Exit code for invalid arguments, the same code clap exits with for arguments it rejects.
+///
+pub const EXIT_USAGE_ERROR: i32 = 2;
+
+///
+/// Exit code when the configuration cannot be read, parsed, or fails validation.
+///
+pub const EXIT_CONFIG_ERROR: i32 = 5;
+
+///
+/// Exit code when no usable toolchain is found, e.g. for an unknown name or a java home that no longer exists.
+///
+pub const EXIT_NO_TOOLCHAIN: i32 = 3;
+
+///
+/// Exit code for IO errors other than those covered by the other codes.
+///
+pub const EXIT_IO_ERROR: i32 = 4;
+
+///
+/// Exit code when a child process could not be started, following the convention of shells.
+///
+pub const EXIT_PROCESS_NOT_EXECUTABLE: i32 = 126;
+
+///
+/// Exit code when the program of a child process does not exist, following the convention of shells.
+///
+pub const EXIT_PROCESS_NOT_FOUND: i32 = 127;
 
 #[derive(Debug)]
 pub enum JvmsError {
     IoError(io::Error),
+    ConfigParse { path: PathBuf, error: serde_json::Error },
+    DanglingOverride { path: PathBuf, toolchain: String },
+    DuplicateJavaHome { path: PathBuf, toolchain: String },
+    HookFailed { hook: String, code: Option<i32> },
+    InvalidArguments(String),
     InvalidConfiguration(String),
-    SerdeJsonError(serde_json::Error)
+    InvalidToolchain(String),
+    NetworkError(String),
+    NoDefaultConfigured,
+    NoToolchainResolved { path: PathBuf },
+    ProcessLaunch { program: PathBuf, error: io::Error },
+    SerdeJsonError(serde_json::Error),
+    TomlConfigParse { path: PathBuf, error: toml::de::Error },
+    ToolchainExists(String),
+    ToolchainHomeMissing { name: String, path: PathBuf },
+    ToolchainInUse { name: String, references: Vec<String> },
+    ToolchainNotExecutable { name: String, path: PathBuf },
+    UnknownProjectToolchain { path: PathBuf, toolchain: String },
+    UnknownToolchain(String),
+    UnknownToolchainSuggestion { name: String, suggestion: String }
+}
+
+impl fmt::Display for JvmsError {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JvmsError::IoError(error) => write!(f, "IO Error has occurred: {:?}", error),
+            JvmsError::ConfigParse { path, error } => write!(f, "Failed to parse configuration file {:?}: {}", path, error),
+            JvmsError::DanglingOverride { path, toolchain } => write!(f, "Override at {:?} references an unknown toolchain: {}", path, toolchain),
+            JvmsError::DuplicateJavaHome { path, toolchain } => write!(f, "JAVA_HOME {:?} is already registered as {}", path, toolchain),
+            JvmsError::HookFailed { hook, code: Some(code) } => write!(f, "The {} hook failed with exit code {}", hook, code),
+            JvmsError::HookFailed { hook, code: None } => write!(f, "The {} hook was terminated by a signal", hook),
+            JvmsError::InvalidArguments(string) => write!(f, "{}", string),
+            JvmsError::InvalidConfiguration(string) => write!(f, "Configuration error: {}", string),
+            JvmsError::InvalidToolchain(string) => write!(f, "Toolchain error: {}", string),
+            JvmsError::NetworkError(string) => write!(f, "Network error: {}", string),
+            JvmsError::NoDefaultConfigured => write!(f, "Configuration does not have a default toolchain."),
+            JvmsError::NoToolchainResolved { path } => write!(f, "No toolchain resolves for {:?}", path),
+            JvmsError::ProcessLaunch { program, error } => write!(f, "Failed to run {:?}: {}", program, error),
+            JvmsError::SerdeJsonError(error) => write!(f, "Serde error has occurred: {:?}", error),
+            JvmsError::TomlConfigParse { path, error } => write!(f, "Failed to parse configuration file {:?}: {}", path, error),
+            JvmsError::ToolchainExists(name) => write!(f, "Installation already found for name: {}", name),
+            JvmsError::ToolchainHomeMissing { name, path } => write!(f, "Toolchain {} does not point to a valid java home: {:?}", name, path),
+            JvmsError::ToolchainInUse { name, references } => write!(f, "Toolchain {} is still used by {}. Update them first or pass --cascade to remove them as well.", name, references.join(", ")),
+            JvmsError::ToolchainNotExecutable { name, path } => write!(f, "Toolchain {} has a java binary that is not executable: {:?}", name, path),
+            JvmsError::UnknownProjectToolchain { path, toolchain } => write!(f, "{:?} references an unknown toolchain: {}", path, toolchain),
+            JvmsError::UnknownToolchain(name) => write!(f, "No toolchain found for name: {}", name),
+            JvmsError::UnknownToolchainSuggestion { name, suggestion } => write!(f, "No toolchain found for name: {}. Did you mean {}?", name, suggestion)
+        }
+    }
+
+}
+
+impl JvmsError {
+
+    ///
+    /// Returns the exit code of the process when it fails with this error.
+    ///
+    pub fn get_exit_code(&self) -> i32 {
+        match self {
+            JvmsError::IoError(_) => EXIT_IO_ERROR,
+            JvmsError::InvalidArguments(_) => EXIT_USAGE_ERROR,
+            JvmsError::ConfigParse { .. }
+            | JvmsError::DanglingOverride { .. }
+            | JvmsError::InvalidConfiguration(_)
+            | JvmsError::SerdeJsonError(_)
+            | JvmsError::TomlConfigParse { .. } => EXIT_CONFIG_ERROR,
+            JvmsError::NoDefaultConfigured
+            | JvmsError::NoToolchainResolved { .. }
+            | JvmsError::ToolchainHomeMissing { .. }
+            | JvmsError::ToolchainNotExecutable { .. }
+            | JvmsError::UnknownProjectToolchain { .. }
+            | JvmsError::UnknownToolchain(_)
+            | JvmsError::UnknownToolchainSuggestion { .. } => EXIT_NO_TOOLCHAIN,
+            JvmsError::ProcessLaunch { error, .. } if error.kind() == io::ErrorKind::NotFound => EXIT_PROCESS_NOT_FOUND,
+            JvmsError::ProcessLaunch { .. } => EXIT_PROCESS_NOT_EXECUTABLE,
+            JvmsError::DuplicateJavaHome { .. }
+            | JvmsError::HookFailed { .. }
+            | JvmsError::InvalidToolchain(_)
+            | JvmsError::NetworkError(_)
+            | JvmsError::ToolchainExists(_)
+            | JvmsError::ToolchainInUse { .. } => EXIT_FAILURE
+        }
+    }
+
+}
+
+impl error::Error for JvmsError {
+
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            JvmsError::IoError(error) => Some(error),
+            JvmsError::ConfigParse { error, .. } => Some(error),
+            JvmsError::ProcessLaunch { error, .. } => Some(error),
+            JvmsError::SerdeJsonError(error) => Some(error),
+            JvmsError::TomlConfigParse { error, .. } => Some(error),
+            _ => None
+        }
+    }
+
 }
 
 impl From<io::Error> for JvmsError {
@@ -0,0 +1,139 @@
+
+use crate::error::{JvmsError, Result};
+use log::debug;
+use std::{io, process};
+use std::path::{Path, PathBuf};
+
+///
+/// Environment variable set on hooks to the name of the event they run for, e.g. `pre_default`.
+///
+const HOOK_ENV: &str = "JVMS_HOOK";
+
+///
+/// Environment variable set on hooks to the name of the toolchain the event is about.
+///
+const HOOK_TOOLCHAIN_ENV: &str = "JVMS_HOOK_TOOLCHAIN";
+
+///
+/// Environment variable set on hooks to the java home of the toolchain the event is about.
+///
+const HOOK_JAVA_HOME_ENV: &str = "JVMS_HOOK_JAVA_HOME";
+
+///
+/// Environment variable set on hooks to the name of the toolchain that was selected before the change, if any.
+///
+const HOOK_PREVIOUS_TOOLCHAIN_ENV: &str = "JVMS_HOOK_PREVIOUS_TOOLCHAIN";
+
+///
+/// Environment variable set on hooks to the directory of an override or of the launched tool.
+///
+const HOOK_DIRECTORY_ENV: &str = "JVMS_HOOK_DIRECTORY";
+
+///
+/// Environment variable set on launch hooks to the name of the tool about to be launched.
+///
+const HOOK_TOOL_ENV: &str = "JVMS_HOOK_TOOL";
+
+///
+/// The points at which a configured hook runs.
+///
+#[derive(Clone, Copy)]
+pub enum HookEvent {
+    /// Before the default toolchain is changed. Failing aborts the change.
+    PreDefault,
+    /// After the default toolchain was changed.
+    PostDefault,
+    /// Before an override is set for a directory. Failing aborts the change.
+    PreOverride,
+    /// After an override was set for a directory.
+    PostOverride,
+    /// Before a shim launches a tool. Failing keeps the tool from running.
+    PreLaunch
+}
+
+///
+/// What a hook is told about the event, passed to it as environment variables.
+///
+#[derive(Default)]
+pub struct HookContext<'a> {
+    pub toolchain: Option<&'a str>,
+    pub java_home: Option<&'a Path>,
+    pub previous_toolchain: Option<&'a str>,
+    pub directory: Option<&'a Path>,
+    pub tool: Option<&'a str>
+}
+
+impl HookEvent {
+
+    ///
+    /// Returns the name of the event, which is also its key in the hooks table of the configuration.
+    ///
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            HookEvent::PreDefault => "pre_default",
+            HookEvent::PostDefault => "post_default",
+            HookEvent::PreOverride => "pre_override",
+            HookEvent::PostOverride => "post_override",
+            HookEvent::PreLaunch => "pre_launch"
+        }
+    }
+
+    ///
+    /// Checks whether a failure of the hook aborts what it runs for.
+    ///
+    pub fn is_pre(&self) -> bool {
+        !matches!(self, HookEvent::PostDefault | HookEvent::PostOverride)
+    }
+
+}
+
+#[cfg(target_os="windows")]
+fn shell_command(command_line: &str) -> (PathBuf, process::Command) {
+    use std::os::windows::process::CommandExt;
+
+    // cmd does its own parsing of the command line, so it is passed on untouched rather than quoted as an argument.
+    let mut command = process::Command::new("cmd");
+    command.arg("/C").raw_arg(command_line);
+    (PathBuf::from("cmd"), command)
+}
+
+#[cfg(not(target_os="windows"))]
+fn shell_command(command_line: &str) -> (PathBuf, process::Command) {
+    let mut command = process::Command::new("sh");
+    command.arg("-c").arg(command_line);
+    (PathBuf::from("sh"), command)
+}
+
+///
+/// Runs the command line of a hook with the shell and waits for it. The output of the hook goes to stderr, so that
+/// it never mixes with the output of jvms or of a launched tool.
+///
+pub fn run_hook(command_line: &str, event: HookEvent, context: &HookContext) -> Result<()> {
+    let (program, mut command) = shell_command(command_line);
+    command.stdin(process::Stdio::null());
+    command.stdout(io::stderr());
+    command.env(HOOK_ENV, event.get_name());
+
+    let env_vars = [
+        (HOOK_TOOLCHAIN_ENV, context.toolchain.map(Path::new)),
+        (HOOK_JAVA_HOME_ENV, context.java_home),
+        (HOOK_PREVIOUS_TOOLCHAIN_ENV, context.previous_toolchain.map(Path::new)),
+        (HOOK_DIRECTORY_ENV, context.directory),
+        (HOOK_TOOL_ENV, context.tool.map(Path::new))
+    ];
+
+    for (key, value) in env_vars {
+        match value {
+            Some(value) => command.env(key, value),
+            None => command.env_remove(key)
+        };
+    }
+
+    debug!("Running the {} hook: {:?}", event.get_name(), command);
+    let status = command.status().map_err(|error| JvmsError::ProcessLaunch { program, error })?;
+    if !status.success() {
+        return Err(JvmsError::HookFailed { hook: event.get_name().to_owned(), code: status.code() });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,486 @@
+
+use crate::config;
+use crate::error::{JvmsError, Result};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::{env, fs, process};
+use std::path::{Path, PathBuf};
+
+///
+/// Tools that must be present in a java home for it to be considered a JDK.
+///
+static REQUIRED_TOOLS: [&str; 2] = ["java", "javac"];
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct JavaVersion {
+    components: Vec<u32>
+}
+
+#[derive(Default)]
+pub struct JdkInfo {
+    pub version: Option<String>,
+    pub vendor: Option<String>,
+    pub arch: Option<String>
+}
+
+pub fn get_tool_path(java_home: &Path, tool_name: &str) -> PathBuf {
+    let mut path = java_home.to_path_buf();
+    path.push("bin");
+    path.push(tool_name);
+
+    #[cfg(target_os="windows")]
+    {
+        assert!(path.set_extension("exe"));
+    }
+
+    path
+}
+
+///
+/// Checks whether the file at the path can be executed. Only Unix tracks this through permission bits, so on other
+/// platforms any existing file is considered executable.
+///
+pub fn is_executable(path: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path)
+            .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(unix))]
+    {
+        path.is_file()
+    }
+}
+
+///
+/// Marks the file at the path as executable, equivalent to `chmod +x`. This is a no-op on platforms other than Unix.
+///
+pub fn set_executable(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = fs::metadata(path)?.permissions();
+        permissions.set_mode(permissions.mode() | 0o111);
+        fs::set_permissions(path, permissions)?;
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+    }
+
+    Ok(())
+}
+
+///
+/// Sets JAVA_HOME for the command and, if requested, prepends the java home's bin directory to PATH.
+///
+/// Any existing occurrence of the bin directory is moved to the front rather than repeated, so that nested
+/// invocations don't keep growing PATH. If dedupe_path is set, other duplicate entries are collapsed as well.
+///
+pub fn apply_environment(java_home: &Path, command: &mut process::Command, prepend_bin: bool, dedupe_path: bool) -> Result<()> {
+    command.env("JAVA_HOME", java_home.as_os_str());
+
+    if prepend_bin {
+        let bin_path = java_home.join("bin");
+        let current_path = env::var_os("PATH").unwrap_or_default();
+        let new_path =
+            env::join_paths(prepend_path_entry(bin_path, &current_path, dedupe_path))
+                .map_err(|error| {
+                    JvmsError::InvalidToolchain(format!("Failed to add {:?} to PATH: {}", java_home, error))
+                })?;
+
+        command.env("PATH", new_path);
+    }
+
+    Ok(())
+}
+
+///
+/// Builds the entries of a PATH value with the entry first, dropping its later occurrences and, if dedupe is set,
+/// every repeated entry. Entries are compared like override paths, so that "/opt/bin/" and "/opt/bin" are the same,
+/// as are "C:\Java\bin" and "c:\java\bin" on Windows.
+///
+fn prepend_path_entry(entry: PathBuf, path: &OsStr, dedupe: bool) -> Vec<PathBuf> {
+    let entry_key = config::comparable_path(&entry, false);
+    let mut seen_keys = vec![entry_key.clone()];
+    let mut entries = vec![entry];
+    for existing in env::split_paths(path) {
+        let key = config::comparable_path(&existing, false);
+        if key == entry_key || (dedupe && seen_keys.contains(&key)) {
+            continue;
+        }
+
+        seen_keys.push(key);
+        entries.push(existing);
+    }
+
+    entries
+}
+
+///
+/// Determines the architecture of a JDK by running it and reading the os.arch system property, which unlike the
+/// release file reflects the binaries that were actually shipped.
+///
+pub fn probe_arch(java_home: &Path) -> Result<String> {
+    let java_path = get_tool_path(java_home, "java");
+    let output =
+        process::Command::new(&java_path)
+            .args(["-XshowSettings:properties", "-version"])
+            .output()?;
+
+    //
+    // The settings are printed to stderr along with the version banner.
+    //
+
+    let settings = String::from_utf8_lossy(&output.stderr);
+    parse_property(&settings, "os.arch")
+        .ok_or_else(|| {
+            JvmsError::InvalidToolchain(format!("Failed to find os.arch in the output of {:?}.", java_path))
+        })
+}
+
+///
+/// Where a detected java version was read from.
+///
+pub enum VersionSource {
+    ReleaseFile,
+    VersionFile,
+    DirectoryName,
+    Probe
+}
+
+///
+/// Detects the version of a java home from the first source that provides one: the release file, a plain text
+/// version file, the directory name (e.g. "jdk-21.0.1"), and, only if probe is set, the output of `java -version`.
+///
+pub fn detect_version(java_home: &Path, probe: bool) -> Option<(String, VersionSource)> {
+    if let Some(version) = read_release_file(java_home).ok().and_then(|mut release| release.remove("JAVA_VERSION")) {
+        return Some((version, VersionSource::ReleaseFile));
+    }
+
+    let version_file = fs::read_to_string(java_home.join("version")).ok();
+    if let Some(version) = version_file.as_deref().and_then(parse_version_text) {
+        return Some((version, VersionSource::VersionFile));
+    }
+
+    let directory_name = java_home.file_name().map(|name| name.to_string_lossy().into_owned());
+    if let Some(version) = directory_name.as_deref().and_then(parse_version_text) {
+        return Some((version, VersionSource::DirectoryName));
+    }
+
+    if probe {
+        if let Some(version) = probe_version(java_home) {
+            return Some((version, VersionSource::Probe));
+        }
+    }
+
+    None
+}
+
+///
+/// Finds the first version number in free-form text, such as "jdk-21.0.1+12" or "openjdk 17.0.2".
+///
+fn parse_version_text(text: &str) -> Option<String> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let version: String = text[start..]
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.' || *c == '_')
+        .collect();
+    let version = version.trim_end_matches(['.', '_']);
+
+    JavaVersion::parse(version).map(|_| version.to_owned())
+}
+
+///
+/// Runs `java -version` and reads the quoted version from its banner, e.g. `openjdk version "21.0.1" 2023-10-17`.
+///
+fn probe_version(java_home: &Path) -> Option<String> {
+    let output = process::Command::new(get_tool_path(java_home, "java"))
+        .arg("-version")
+        .output()
+        .ok()?;
+
+    //
+    // The banner is printed to stderr.
+    //
+
+    let banner = String::from_utf8_lossy(&output.stderr);
+    let version = banner.lines().next()?.split('"').nth(1)?;
+    JavaVersion::parse(version).map(|_| version.to_owned())
+}
+
+///
+/// Reads a property from the output of -XshowSettings:properties, where each property is printed as "key = value".
+///
+fn parse_property(settings: &str, key: &str) -> Option<String> {
+    settings
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .find(|(line_key, _)| line_key.trim() == key)
+        .map(|(_, value)| value.trim().to_owned())
+}
+
+fn read_release_file(java_home: &Path) -> Result<HashMap<String, String>> {
+    let release_path = java_home.join("release");
+    let contents =
+        fs::read_to_string(&release_path)
+            .map_err(|io_error| {
+                JvmsError::InvalidToolchain(format!("Failed to read release file {:?}: {:?}", release_path, io_error))
+            })?;
+
+    //
+    // The release file is a list of KEY="value" lines.
+    //
+
+    Ok(contents
+        .lines()
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_owned(), value.trim().trim_matches('"').to_owned()))
+        .collect())
+}
+
+impl JavaVersion {
+
+    ///
+    /// Parses a java version string such as "17.0.2", "21+35", or the legacy "1.8.0_292".
+    ///
+    /// Legacy versions have their leading "1." dropped so that "1.8.0_292" orders as major version 8. Build numbers and
+    /// pre-release labels, as in "21+35" or "17.0.2-ea", are not part of the version and are ignored.
+    ///
+    pub fn parse(version: &str) -> Option<JavaVersion> {
+        let version = version.split(['+', '-']).next().unwrap_or_default();
+        let mut components: Vec<u32> = version
+            .split(|c: char| !c.is_ascii_digit())
+            .filter(|component| !component.is_empty())
+            .map(|component| component.parse())
+            .collect::<std::result::Result<_, _>>()
+            .ok()?;
+
+        if components.len() > 1 && components[0] == 1 {
+            components.remove(0);
+        }
+
+        if components.is_empty() {
+            None
+
+        } else {
+            Some(JavaVersion { components })
+        }
+    }
+
+    pub fn major(&self) -> u32 {
+        self.components[0]
+    }
+
+    pub fn component_count(&self) -> usize {
+        self.components.len()
+    }
+
+    ///
+    /// Returns this version with at most the provided number of components.
+    ///
+    pub fn truncate(&self, component_count: usize) -> JavaVersion {
+        JavaVersion {
+            components: self.components.iter().take(component_count).cloned().collect()
+        }
+    }
+
+}
+
+impl JdkInfo {
+
+    ///
+    /// Reads the metadata of a java home from its release file without checking that it is a valid JDK.
+    ///
+    pub fn read(java_home: &Path) -> Result<JdkInfo> {
+        let mut release = read_release_file(java_home)?;
+        Ok(JdkInfo {
+            version: release.remove("JAVA_VERSION"),
+            vendor: release.remove("IMPLEMENTOR"),
+            arch: release.remove("OS_ARCH")
+        })
+    }
+
+    pub fn get_java_version(&self) -> Option<JavaVersion> {
+        self.version.as_deref().and_then(JavaVersion::parse)
+    }
+
+    ///
+    /// Checks that the provided java home looks like a JDK and reads its metadata from the release file.
+    ///
+    pub fn inspect(java_home: &Path) -> Result<JdkInfo> {
+        if !java_home.is_dir() {
+            return Err(JvmsError::InvalidToolchain(format!("{:?} is not a directory.", java_home)));
+        }
+
+        for tool_name in &REQUIRED_TOOLS {
+            let tool_path = get_tool_path(java_home, tool_name);
+            if !tool_path.is_file() {
+                return Err(JvmsError::InvalidToolchain(format!("{:?} does not contain {:?}.", java_home, tool_path)));
+            }
+        }
+
+        JdkInfo::read(java_home)
+    }
+
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    ///
+    /// Creates a fake JDK of the given version at the java home, with an executable but empty java and javac.
+    ///
+    pub(crate) fn create_fake_jdk(java_home: &Path, version: &str) {
+        for tool_name in &["java", "javac"] {
+            let tool_path = get_tool_path(java_home, tool_name);
+            fs::create_dir_all(tool_path.parent().unwrap()).unwrap();
+            fs::write(&tool_path, "").unwrap();
+            set_executable(&tool_path).unwrap();
+        }
+
+        fs::write(java_home.join("release"), format!("JAVA_VERSION=\"{}\"\n", version)).unwrap();
+    }
+
+    fn join(entries: &[&str]) -> std::ffi::OsString {
+        env::join_paths(entries).unwrap()
+    }
+
+    #[test]
+    fn parses_versions_without_their_build_number() {
+        assert_eq!(JavaVersion::parse("21+35"), JavaVersion::parse("21"));
+        assert_eq!(JavaVersion::parse("17.0.2+8-LTS"), JavaVersion::parse("17.0.2"));
+        assert_eq!(JavaVersion::parse("22-ea"), JavaVersion::parse("22"));
+        assert_eq!(JavaVersion::parse("21+35").unwrap().component_count(), 1);
+        assert!(JavaVersion::parse("21+35").unwrap() < JavaVersion::parse("21.0.1").unwrap());
+    }
+
+    #[test]
+    fn parses_legacy_versions() {
+        let version = JavaVersion::parse("1.8.0_292").unwrap();
+        assert_eq!(version.major(), 8);
+        assert_eq!(version, JavaVersion::parse("8.0.292").unwrap());
+        assert_eq!(JavaVersion::parse("1.8.0_292-b10"), Some(version));
+        assert_eq!(JavaVersion::parse("temurin"), None);
+        assert_eq!(JavaVersion::parse("+35"), None);
+    }
+
+    #[test]
+    fn finds_versions_in_free_form_text() {
+        assert_eq!(parse_version_text("jdk-21.0.1+12").as_deref(), Some("21.0.1"));
+        assert_eq!(parse_version_text("openjdk 17.0.2 2022-01-18\n").as_deref(), Some("17.0.2"));
+        assert_eq!(parse_version_text("java-1.8.0_292").as_deref(), Some("1.8.0_292"));
+        assert_eq!(parse_version_text("jdk-21.").as_deref(), Some("21"));
+        assert_eq!(parse_version_text("temurin"), None);
+    }
+
+    #[test]
+    fn detects_versions_from_the_first_source_that_has_one() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let java_home = temp_dir.path().join("jdk-21.0.1");
+        fs::create_dir(&java_home).unwrap();
+
+        let detect = || detect_version(&java_home, false).map(|(version, source)| {
+            let source = match source {
+                VersionSource::ReleaseFile => "release",
+                VersionSource::VersionFile => "version",
+                VersionSource::DirectoryName => "directory",
+                VersionSource::Probe => "probe"
+            };
+
+            (version, source)
+        });
+
+        assert_eq!(detect(), Some(("21.0.1".to_owned(), "directory")));
+
+        fs::write(java_home.join("version"), "21.0.2+13\n").unwrap();
+        assert_eq!(detect(), Some(("21.0.2".to_owned(), "version")));
+
+        fs::write(java_home.join("release"), "JAVA_VERSION=\"21.0.3\"\n").unwrap();
+        assert_eq!(detect(), Some(("21.0.3".to_owned(), "release")));
+
+        let unversioned_home = temp_dir.path().join("custom");
+        fs::create_dir(&unversioned_home).unwrap();
+        fs::write(unversioned_home.join("version"), "unknown\n").unwrap();
+        assert!(detect_version(&unversioned_home, false).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn probes_the_version_only_when_asked_to() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let java_home = temp_dir.path().join("custom");
+        let java_path = get_tool_path(&java_home, "java");
+        fs::create_dir_all(java_path.parent().unwrap()).unwrap();
+        fs::write(&java_path, "#!/bin/sh\necho 'openjdk version \"21.0.4\" 2024-07-16' >&2\n").unwrap();
+        set_executable(&java_path).unwrap();
+
+        assert!(detect_version(&java_home, false).is_none());
+        assert!(matches!(detect_version(&java_home, true), Some((version, VersionSource::Probe)) if version == "21.0.4"));
+    }
+
+    #[test]
+    fn reads_properties_from_the_settings_output() {
+        let settings = "Property settings:\n    file.encoding = UTF-8\n    os.arch = aarch64\n    os.name = Linux\n\nopenjdk version \"21.0.1\"\n";
+        assert_eq!(parse_property(settings, "os.arch").as_deref(), Some("aarch64"));
+        assert_eq!(parse_property(settings, "os.name").as_deref(), Some("Linux"));
+        assert_eq!(parse_property(settings, "os"), None);
+        assert_eq!(parse_property(settings, "java.home"), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn probes_the_arch_of_a_java_home() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let java_path = get_tool_path(temp_dir.path(), "java");
+        fs::create_dir_all(java_path.parent().unwrap()).unwrap();
+        fs::write(&java_path, "#!/bin/sh\necho 'Property settings:' >&2\necho '    os.arch = riscv64' >&2\n").unwrap();
+        set_executable(&java_path).unwrap();
+        assert_eq!(probe_arch(temp_dir.path()).unwrap(), "riscv64");
+
+        fs::write(&java_path, "#!/bin/sh\necho 'openjdk version \"21\"' >&2\n").unwrap();
+        assert!(matches!(probe_arch(temp_dir.path()), Err(JvmsError::InvalidToolchain(_))));
+    }
+
+    #[test]
+    fn prepending_moves_an_existing_bin_directory_to_the_front() {
+        let path = join(&["/usr/bin", "/opt/jdk/bin/", "/bin", "/opt/jdk/bin"]);
+        let entries = prepend_path_entry(PathBuf::from("/opt/jdk/bin"), &path, false);
+        assert_eq!(entries, vec![PathBuf::from("/opt/jdk/bin"), PathBuf::from("/usr/bin"), PathBuf::from("/bin")]);
+    }
+
+    #[test]
+    fn dedupe_collapses_other_duplicate_entries() {
+        let path = join(&["/usr/bin", "/bin", "/usr/bin/", "/opt/jdk/bin", "/bin"]);
+
+        let entries = prepend_path_entry(PathBuf::from("/opt/jdk/bin"), &path, true);
+        assert_eq!(entries, vec![PathBuf::from("/opt/jdk/bin"), PathBuf::from("/usr/bin"), PathBuf::from("/bin")]);
+
+        let entries = prepend_path_entry(PathBuf::from("/opt/jdk/bin"), &path, false);
+        assert_eq!(entries.len(), 5);
+    }
+
+    #[test]
+    fn nested_prepends_keep_path_the_same_size() {
+        let mut path = join(&["/usr/bin", "/bin", "/usr/bin"]);
+        for _ in 0..3 {
+            path = env::join_paths(prepend_path_entry(PathBuf::from("/opt/jdk/bin"), &path, true)).unwrap();
+        }
+
+        assert_eq!(env::split_paths(&path).count(), 3);
+    }
+
+    #[cfg(target_os="windows")]
+    #[test]
+    fn entries_differing_in_case_are_duplicates_on_windows() {
+        let path = join(&[r"C:\Java\JDK\bin", r"C:\Windows", r"c:\windows\"]);
+        let entries = prepend_path_entry(PathBuf::from(r"c:\java\jdk\bin"), &path, true);
+        assert_eq!(entries, vec![PathBuf::from(r"c:\java\jdk\bin"), PathBuf::from(r"C:\Windows")]);
+    }
+
+}
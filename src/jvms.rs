@@ -1,44 +1,312 @@
 
-use clap::Clap;
-use crate::error::Result;
-use crate::config::JvmsInstallation;
-use std::env;
-use std::path::PathBuf;
+use clap::{AppSettings, Clap, ErrorKind, IntoApp};
+use crate::adoptium::{self, AdoptiumRelease};
+use crate::completions::{self, CompletionShell};
+use crate::error::{self, JvmsError, Result};
+use crate::config::{self, JavaToolchain, JvmsConfiguration, JvmsInstallation, LinkMode, JvmsOverride, MergePolicy, OverrideCleanPolicy};
+use crate::discovery::{self, DiscoveredJdk, DiscoveryFilter};
+use crate::download::{self, SignatureStatus};
+use crate::hooks::{HookContext, HookEvent};
+use crate::jdk::{self, JavaVersion, JdkInfo, VersionSource};
+use crate::maven::{self, MavenToolchain};
+use crate::selector::ToolchainSelector;
+use crate::shim::Shim;
+use crate::user_path;
+use log::debug;
+use std::{env, fs, io, process};
+use std::io::{BufRead, IsTerminal, Write};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+///
+/// The first java version whose launcher can run a source file directly, which `jvms run` needs for jvms scripts.
+///
+const SOURCE_LAUNCHER_MIN_VERSION: u32 = 11;
 
 #[derive(Clap)]
 #[clap(version = "0.1")]
 pub struct Jvms {
+    ///
+    /// Fail any command that would need to access the network.
+    ///
+    #[clap(long = "offline", global = true)]
+    offline: bool,
+    ///
+    /// Stop searching for project files at this directory, overriding JVMS_ROOT.
+    ///
+    #[clap(long = "root", global = true)]
+    root: Option<PathBuf>,
+    ///
+    /// Print the output of list, current, which, info, and validation commands as JSON.
+    ///
+    #[clap(long = "json", global = true)]
+    json: bool,
+    ///
+    /// Use this configuration file instead of the one in the installation directory, overriding JVMS_CONFIG.
+    ///
+    #[clap(long = "config", global = true)]
+    config: Option<PathBuf>,
     #[clap(subcommand)]
     command: JvmsCommand
 }
 
 #[derive(Clap)]
 enum JvmsCommand {
+    ///
+    /// Inspect or clear the cache of this installation.
+    ///
+    #[clap(name = "cache")]
+    Cache(CacheCommand),
+
+    ///
+    /// Print a shell completion script for jvms.
+    ///
+    #[clap(name = "completions")]
+    Completions(CompletionsCommand),
+
+    ///
+    /// Inspect jvms configuration files.
+    ///
+    #[clap(name = "config")]
+    Config(ConfigCommand),
+
+    ///
+    /// Show the toolchain used in the current directory and why it was selected.
+    ///
+    #[clap(name = "current")]
+    Current(CurrentCommand),
+
     ///
     /// Change or read the current default installation name.
     ///
     #[clap(name = "default")]
     Default(DefaultCommand),
 
+    ///
+    /// Check the configuration and registered toolchains for problems.
+    ///
+    #[clap(name = "doctor")]
+    Doctor(DoctorCommand),
+
+    ///
+    /// Print shell commands that set JAVA_HOME to the toolchain resolved for the current directory.
+    ///
+    #[clap(name = "env")]
+    Env(EnvCommand),
+
+    ///
+    /// Execute a program with JAVA_HOME set for the named toolchain.
+    ///
+    #[clap(name = "exec")]
+    Exec(ExecCommand),
+
+    ///
+    /// Export the registered toolchains to the configuration of other tools.
+    ///
+    #[clap(name = "export")]
+    Export(ExportCommand),
+
+    ///
+    /// Delete the JDKs downloaded or copied by jvms that no registered toolchain uses anymore.
+    ///
+    #[clap(name = "gc")]
+    Gc(GcCommand),
+
+    ///
+    /// Show where the jvms installation and its configuration are, and whether its shims are in place.
+    ///
+    #[clap(name = "info")]
+    Info(InfoCommand),
+
     ///
     /// Install JVMS into a new directory.
     ///
     #[clap(name = "install")]
     Install(InstallCommand),
 
+    ///
+    /// List the JDK releases that `jvms toolchain install` can download.
+    ///
+    #[clap(name = "ls-remote")]
+    LsRemote(LsRemoteCommand),
+
     ///
     /// Add, remove, or list registered overrides.
     ///
     #[clap(name = "override")]
     Override(OverrideCommand),
 
+    ///
+    /// Print a short description of the toolchain for the current directory, for embedding in a shell prompt.
+    ///
+    #[clap(name = "prompt")]
+    Prompt(PromptCommand),
+
+    ///
+    /// Creates shims for every tool of the registered toolchains and removes the generated shims of tools that no
+    /// toolchain provides anymore.
+    ///
+    #[clap(name = "rehash")]
+    Rehash(RehashCommand),
+
+    ///
+    /// Recreates the shims of this installation against its jvms binary, without changing the binary or
+    /// configuration.
+    ///
+    #[clap(name = "reinstall-shims")]
+    ReinstallShims(ReinstallShimsCommand),
+
+    ///
+    /// Run a command with JAVA_HOME and PATH set for the selected toolchain.
+    ///
+    #[clap(name = "run")]
+    Run(RunCommand),
+
+    ///
+    /// Start a new shell with the provided toolchain active.
+    ///
+    #[clap(name = "shell")]
+    Shell(ShellCommand),
+
+    ///
+    /// Check that the configuration is valid and a toolchain resolves for the current directory.
+    ///
+    #[clap(name = "status")]
+    Status(StatusCommand),
+
     ///
     /// Add, remove, or list registered java toolchains.
     ///
     #[clap(name = "toolchain")]
-    Toolchain(ToolchainCommand)
+    Toolchain(ToolchainCommand),
+
+    ///
+    /// Browse the registered toolchains and change the default or the override of the current directory from an
+    /// interactive menu.
+    ///
+    #[clap(name = "ui")]
+    Ui(UiCommand),
+
+    ///
+    /// Remove the shims and jvms binary of an installation, and optionally its configuration.
+    ///
+    #[clap(name = "uninstall")]
+    Uninstall(UninstallCommand),
+
+    ///
+    /// Print shell commands that switch the toolchain of the current shell session, for use with eval.
+    ///
+    #[clap(name = "use")]
+    Use(UseCommand),
+
+    ///
+    /// Print the path of a java tool under the toolchain resolved for the current directory.
+    ///
+    #[clap(name = "which")]
+    Which(WhichCommand)
+}
+
+#[derive(Clap)]
+enum CacheCommand {
+    ///
+    /// Deletes the cache directory of this installation.
+    ///
+    #[clap(name = "clear")]
+    Clear(CacheClearCommand),
+    ///
+    /// Prints the cache directory of this installation.
+    ///
+    #[clap(name = "path")]
+    Path(CachePathCommand)
+}
+
+#[derive(Clap)]
+struct CacheClearCommand { }
+
+#[derive(Clap)]
+struct CachePathCommand { }
+
+#[derive(Clap)]
+struct CompletionsCommand {
+    ///
+    /// The shell to print the completion script for.
+    ///
+    #[clap(possible_values = &["bash", "zsh", "fish", "powershell"])]
+    shell: CompletionShell
+}
+
+#[derive(Clap)]
+enum ConfigCommand {
+    ///
+    /// Validates a configuration file, printing every problem found.
+    ///
+    #[clap(name = "validate")]
+    Validate(ConfigValidateCommand),
+    ///
+    /// Merges the toolchains, overrides, and default of another configuration file into the active configuration,
+    /// never removing entries.
+    ///
+    #[clap(name = "merge")]
+    Merge(ConfigMergeCommand),
+    ///
+    /// Converts a legacy JSON jvms.conf into a jvms.toml configuration, keeping the old file as jvms.conf.bak.
+    ///
+    #[clap(name = "migrate")]
+    Migrate(ConfigMigrateCommand)
+}
+
+#[derive(Clap)]
+struct ConfigMergeCommand {
+    ///
+    /// The configuration file to merge, or - for stdin.
+    ///
+    path: PathBuf,
+    ///
+    /// Which side wins when an entry exists in both configurations with different values.
+    ///
+    #[clap(long = "prefer", default_value = "existing", possible_values = &["existing", "imported"])]
+    prefer: MergePreference,
+    ///
+    /// Force save configuration changes, even if configuration is invalid.
+    ///
+    #[clap(short = "f", long = "force")]
+    force: bool
+}
+
+#[derive(Clap)]
+struct ConfigMigrateCommand { }
+
+enum MergePreference {
+    Existing,
+    Imported
+}
+
+impl FromStr for MergePreference {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "existing" => Ok(MergePreference::Existing),
+            "imported" => Ok(MergePreference::Imported),
+            _ => Err(format!("Unknown merge preference: {}", s))
+        }
+    }
+}
+
+#[derive(Clap)]
+struct ConfigValidateCommand {
+    ///
+    /// The configuration file to validate, defaults to the active configuration.
+    ///
+    path: Option<PathBuf>
 }
 
+#[derive(Clap)]
+struct CurrentCommand { }
+
 #[derive(Clap)]
 struct DefaultCommand {
     ///
@@ -46,28 +314,170 @@ struct DefaultCommand {
     ///
     toolchain: Option<String>,
     ///
-    /// Force save configuration changes, even if configuration is invalid.
+    /// Force save configuration changes, even if configuration is invalid or the toolchain's java home does not
+    /// exist.
     ///
     #[clap(short = "f", long = "force")]
     force: bool
 }
 
+#[derive(Clap)]
+struct DoctorCommand {
+    ///
+    /// Repair problems that can be fixed automatically: a java binary missing its executable bit, overrides for
+    /// missing directories or toolchains, a default naming a removed toolchain, and missing shims. Each repair is
+    /// confirmed first when running on a terminal. Toolchains added with --allow-missing whose java home is now
+    /// available are also marked as verified.
+    ///
+    #[clap(long = "fix")]
+    fix: bool,
+    ///
+    /// Apply every repair without asking for confirmation. Without it, repairs are skipped when not running on a
+    /// terminal.
+    ///
+    #[clap(short = "y", long = "yes")]
+    yes: bool
+}
+
+#[derive(Clap)]
+struct EnvCommand {
+    ///
+    /// Also print a command prepending the toolchain's bin directory to PATH.
+    ///
+    #[clap(long = "path")]
+    path: bool,
+    ///
+    /// The shell to print commands for, detected from SHELL if omitted.
+    ///
+    #[clap(long = "shell", possible_values = &["sh", "bash", "zsh", "fish", "powershell", "cmd"])]
+    shell: Option<ShellSyntax>
+}
+
+#[derive(Clap)]
+#[clap(setting = AppSettings::TrailingVarArg)]
+struct ExecCommand {
+    ///
+    /// The name of the toolchain to execute the program with.
+    ///
+    #[clap(short = "t", long = "toolchain")]
+    toolchain_name: String,
+    ///
+    /// Also prepend the toolchain's bin directory to PATH.
+    ///
+    #[clap(long = "path")]
+    path: bool,
+    ///
+    /// Whether duplicate PATH entries are collapsed when the bin directory is prepended, overriding dedupe_path of
+    /// the configuration.
+    ///
+    #[clap(long = "dedupe-path", possible_values = &["true", "false"])]
+    dedupe_path: Option<bool>,
+    ///
+    /// The program to execute followed by its arguments.
+    ///
+    #[clap(required = true, parse(from_os_str))]
+    command: Vec<OsString>
+}
+
+#[derive(Clap)]
+enum ExportCommand {
+    ///
+    /// Writes an entry for every registered toolchain into Maven's toolchains.xml, replacing the entries of earlier
+    /// exports and keeping those added by other means.
+    ///
+    #[clap(name = "maven-toolchains")]
+    MavenToolchains(ExportMavenToolchainsCommand)
+}
+
+#[derive(Clap)]
+struct ExportMavenToolchainsCommand {
+    ///
+    /// The toolchains.xml to merge into, defaults to ~/.m2/toolchains.xml.
+    ///
+    #[clap(long = "file")]
+    file: Option<PathBuf>,
+    ///
+    /// Print the merged toolchains.xml instead of writing it.
+    ///
+    #[clap(long = "dry-run")]
+    dry_run: bool
+}
+
+#[derive(Clap)]
+struct GcCommand {
+    ///
+    /// Only print the directories that would be deleted.
+    ///
+    #[clap(long = "dry-run")]
+    dry_run: bool
+}
+
+#[derive(Clap)]
+struct InfoCommand { }
+
 #[derive(Clap)]
 struct InstallCommand {
     ///
     /// The directory in which to install JVMS.
     ///
-    destination_path: PathBuf
+    destination_path: PathBuf,
+    ///
+    /// How shims are linked to the jvms binary: hard links, symlinks, copies, or the first of those that works.
+    /// Defaults to JVMS_LINK_MODE, or auto if it is not set.
+    ///
+    #[clap(long = "link-mode", possible_values = &["hard", "sym", "copy", "auto"])]
+    link_mode: Option<LinkMode>,
+    ///
+    /// Do not add the installation directory to PATH. On Windows it is otherwise added to the user PATH, elsewhere
+    /// adding it to the shell profile is offered.
+    ///
+    #[clap(long = "no-modify-path")]
+    no_modify_path: bool,
+    ///
+    /// Add the installation directory to the shell profile without asking for confirmation.
+    ///
+    #[clap(short = "y", long = "yes")]
+    yes: bool
+}
+
+#[derive(Clap)]
+struct LsRemoteCommand {
+    ///
+    /// Only list the releases of this version, such as 21 or 21.0. Without it the available major versions are
+    /// listed.
+    ///
+    version: Option<String>,
+    ///
+    /// Only list releases of this vendor. Only temurin, from Adoptium, is available for now.
+    ///
+    #[clap(long = "vendor")]
+    vendor: Option<String>,
+    ///
+    /// The architecture to list releases for, as named by Adoptium, defaults to the architecture of jvms.
+    ///
+    #[clap(long = "arch")]
+    arch: Option<String>
 }
 
 #[derive(Clap)]
 enum OverrideCommand {
     ///
-    /// Cleans the override list of any override for a directory that no longer exists.
+    /// Cleans the override list of any override for a directory that no longer exists, or for a toolchain that is
+    /// no longer registered.
     ///
     #[clap(name = "clean")]
     Clean(OverrideCleanCommand),
     ///
+    /// Exports all registered overrides as JSON.
+    ///
+    #[clap(name = "export")]
+    Export(OverrideExportCommand),
+    ///
+    /// Imports overrides from JSON, replacing any existing override for the same directory.
+    ///
+    #[clap(name = "import")]
+    Import(OverrideImportCommand),
+    ///
     /// Lists all registered overrides.
     ///
     #[clap(name = "list")]
@@ -78,17 +488,48 @@ enum OverrideCommand {
     #[clap(name = "remove")]
     Remove(OverrideRemoveCommand),
     ///
-    /// Sets the override for the current directory.
+    /// Sets the override for a directory, the current directory by default.
     ///
     #[clap(name = "set")]
     Set(OverrideSetCommand)
 }
 
 #[derive(Clap)]
-struct OverrideListCommand { }
+struct OverrideListCommand {
+    ///
+    /// Print only the number of overrides instead of listing them.
+    ///
+    #[clap(long = "count")]
+    count: bool,
+    ///
+    /// Exit with a non-zero status if there are no overrides to list.
+    ///
+    #[clap(long = "fail-if-empty")]
+    fail_if_empty: bool,
+    ///
+    /// Print each override as two NUL-terminated fields, its directory and toolchain, for use with `xargs -0`.
+    ///
+    #[clap(long = "print0", conflicts_with = "count")]
+    print0: bool
+}
 
 #[derive(Clap)]
 struct OverrideCleanCommand {
+    ///
+    /// Remove overrides for directories that no longer exist. This is the default if no policy is given.
+    ///
+    #[clap(long = "missing-dir")]
+    missing_dir: bool,
+    ///
+    /// Remove overrides referencing toolchains that are no longer registered.
+    ///
+    #[clap(long = "missing-toolchain")]
+    missing_toolchain: bool,
+    ///
+    /// Remove overrides for missing directories and missing toolchains.
+    ///
+    #[clap(long = "all")]
+    all: bool,
     ///
     /// Force save configuration changes, even if configuration is invalid.
     ///
@@ -97,11 +538,19 @@ struct OverrideCleanCommand {
 }
 
 #[derive(Clap)]
-struct OverrideRemoveCommand {
+struct OverrideExportCommand {
     ///
-    /// The directory to remove the override for, defaults to the current working directory.
+    /// The file to write the overrides to, or - for stdout.
     ///
-    path: Option<PathBuf>,
+    path: PathBuf
+}
+
+#[derive(Clap)]
+struct OverrideImportCommand {
+    ///
+    /// The file to read the overrides from, or - for stdin.
+    ///
+    path: PathBuf,
     ///
     /// Force save configuration changes, even if configuration is invalid.
     ///
@@ -110,11 +559,11 @@ struct OverrideRemoveCommand {
 }
 
 #[derive(Clap)]
-struct OverrideSetCommand {
+struct OverrideRemoveCommand {
     ///
-    /// The name of the toolchain to use for this directory.
+    /// The directory to remove the override for, defaults to the current working directory.
     ///
-    toolchain_name: String,
+    path: Option<PathBuf>,
     ///
     /// Force save configuration changes, even if configuration is invalid.
     ///
@@ -123,34 +572,32 @@ struct OverrideSetCommand {
 }
 
 #[derive(Clap)]
-enum ToolchainCommand {
+struct OverrideSetCommand {
     ///
-    /// Adds a new java toolchain.
+    /// The name of the toolchain to use for the directory.
     ///
-    #[clap(name = "add")]
-    Add(ToolchainAddCommand),
+    toolchain_name: String,
     ///
-    /// List registered java toolchains.
+    /// The directory to set the override for, defaults to the current working directory.
     ///
-    #[clap(name = "list")]
-    List(ToolchainListCommand),
+    path: Option<PathBuf>,
     ///
-    /// Removes a registered java toolchain.
+    /// Only apply the override to the directory itself, not to the directories below it.
     ///
-    #[clap(name = "remove")]
-    Remove(ToolchainRemoveCommand)
-}
-
-#[derive(Clap)]
-struct ToolchainAddCommand {
+    #[clap(long = "exact")]
+    exact: bool,
     ///
-    /// The name of the new toolchain.
+    /// Arguments passed to java in this directory, after those of the toolchain. They are split like a shell would,
+    /// so an argument containing spaces can be quoted, e.g. --java-opts "-Xmx2g '-Dapp.name=My App'".
     ///
-    toolchain_name: String,
+    #[clap(long = "java-opts", allow_hyphen_values = true)]
+    java_opts: Option<String>,
     ///
-    /// The path to the JAVA_HOME of the toolchain.
+    /// An environment variable set for tools in this directory, as KEY=VALUE. May be given multiple times. These take
+    /// precedence over the environment variables of the toolchain.
     ///
-    java_home: PathBuf,
+    #[clap(long = "env", number_of_values = 1)]
+    env: Vec<String>,
     ///
     /// Force save configuration changes, even if configuration is invalid.
     ///
@@ -159,136 +606,3393 @@ struct ToolchainAddCommand {
 }
 
 #[derive(Clap)]
-struct ToolchainListCommand { }
+struct PromptCommand {
+    ///
+    /// Do not color the output. Color is also disabled when NO_COLOR is set.
+    ///
+    #[clap(long = "no-color")]
+    no_color: bool
+}
 
 #[derive(Clap)]
-struct ToolchainRemoveCommand {
+struct RehashCommand {
     ///
-    /// The name of the toolchain to remove.
+    /// How new shims are linked to the jvms binary: hard links, symlinks, copies, or the first of those that works.
+    /// Defaults to JVMS_LINK_MODE, or auto if it is not set.
     ///
-    toolchain_name: String,
+    #[clap(long = "link-mode", possible_values = &["hard", "sym", "copy", "auto"])]
+    link_mode: Option<LinkMode>
+}
+
+#[derive(Clap)]
+struct ReinstallShimsCommand {
     ///
-    /// Force save configuration changes, even if configuration is invalid.
+    /// How shims are linked to the jvms binary: hard links, symlinks, copies, or the first of those that works.
+    /// Defaults to JVMS_LINK_MODE, or auto if it is not set.
     ///
-    #[clap(short = "f", long = "force")]
-    force: bool
+    #[clap(long = "link-mode", possible_values = &["hard", "sym", "copy", "auto"])]
+    link_mode: Option<LinkMode>
 }
 
-impl Jvms {
-
-    pub fn execute(jvms_installation: &JvmsInstallation) -> Result<()> {
-        let jvms_config = jvms_installation.load_configuration();
-
-        let opts: Jvms = Jvms::parse();
-        match opts.command {
-
-            //
-            // Default subcommand
-            //
+#[derive(Clap)]
+#[clap(setting = AppSettings::TrailingVarArg)]
+struct RunCommand {
+    ///
+    /// Do not print which toolchain was selected when the toolchain is given as a glob or version requirement.
+    ///
+    #[clap(short = "q", long = "quiet")]
+    quiet: bool,
+    ///
+    /// The toolchain to run the command with, given as an option instead of as the first argument.
+    ///
+    #[clap(short = "t", long = "toolchain")]
+    toolchain_option: Option<String>,
+    ///
+    /// Whether duplicate PATH entries are collapsed when the bin directory is prepended, overriding dedupe_path of
+    /// the configuration.
+    ///
+    #[clap(long = "dedupe-path", possible_values = &["true", "false"])]
+    dedupe_path: Option<bool>,
+    ///
+    /// The toolchain name, a glob over toolchain names, or a version requirement such as 17, 17+, or >=11. Omitted
+    /// when --toolchain is provided.
+    ///
+    toolchain: Option<String>,
+    ///
+    /// The command to run followed by its arguments. A jvms script, one starting with a shebang line such as
+    /// `#!/usr/bin/env -S jvms run 17 --`, is run as a java source file.
+    ///
+    #[clap(parse(from_os_str))]
+    command: Vec<OsString>
+}
+
+#[derive(Clap)]
+struct ShellCommand {
+    ///
+    /// The name of the toolchain to activate in the shell.
+    ///
+    toolchain_name: String,
+    ///
+    /// Whether duplicate PATH entries are collapsed when the bin directory is prepended, overriding dedupe_path of
+    /// the configuration.
+    ///
+    #[clap(long = "dedupe-path", possible_values = &["true", "false"])]
+    dedupe_path: Option<bool>
+}
+
+#[derive(Clap)]
+struct StatusCommand {
+    ///
+    /// Report health only through the exit code, printing nothing unless --verbose is provided.
+    ///
+    #[clap(long = "check")]
+    check: bool,
+    ///
+    /// Print the status report even when --check is provided.
+    ///
+    #[clap(short = "v", long = "verbose")]
+    verbose: bool
+}
+
+#[derive(Clap)]
+enum ToolchainCommand {
+    ///
+    /// Adds a new java toolchain.
+    ///
+    #[clap(name = "add")]
+    Add(ToolchainAddCommand),
+    ///
+    /// Finds JDKs under the provided directories and registers them, named after their directories.
+    ///
+    #[clap(name = "discover")]
+    Discover(ToolchainDiscoverCommand),
+    ///
+    /// Prints the bin directory of a toolchain, for adding it to PATH directly instead of using the shims.
+    ///
+    #[clap(name = "export-path")]
+    ExportPath(ToolchainExportPathCommand),
+    ///
+    /// Installs a JDK from Adoptium, such as "21" or "temurin-21" for the latest release of java 21.
+    ///
+    #[clap(name = "install")]
+    Install(ToolchainInstallCommand),
+    ///
+    /// List registered java toolchains.
+    ///
+    #[clap(name = "list")]
+    List(ToolchainListCommand),
+    ///
+    /// Lists Adoptium toolchains that are behind the latest patch release of their major version. Exits with a
+    /// failure if any is outdated or could not be checked.
+    ///
+    #[clap(name = "outdated")]
+    Outdated(ToolchainOutdatedCommand),
+    ///
+    /// Removes a registered java toolchain.
+    ///
+    #[clap(name = "remove")]
+    Remove(ToolchainRemoveCommand),
+    ///
+    /// Renames a registered java toolchain, updating the default and every override that references it.
+    ///
+    #[clap(name = "rename")]
+    Rename(ToolchainRenameCommand),
+    ///
+    /// Finds JDKs in the locations they are commonly installed to and registers them, named after their vendor and
+    /// version.
+    ///
+    #[clap(name = "scan")]
+    Scan(ToolchainScanCommand),
+    ///
+    /// Adds tags to a registered java toolchain.
+    ///
+    #[clap(name = "tag")]
+    Tag(ToolchainTagCommand),
+    ///
+    /// Removes tags from a registered java toolchain.
+    ///
+    #[clap(name = "untag")]
+    Untag(ToolchainTagCommand)
+}
+
+#[derive(Clap)]
+struct ToolchainAddCommand {
+    ///
+    /// The name of the new toolchain.
+    ///
+    toolchain_name: Option<String>,
+    ///
+    /// The path to the JAVA_HOME of the toolchain.
+    ///
+    java_home: Option<PathBuf>,
+    ///
+    /// Force save configuration changes, even if configuration is invalid.
+    ///
+    #[clap(short = "f", long = "force")]
+    force: bool,
+    ///
+    /// Only check that the JAVA_HOME is a valid JDK and print its details, without registering it. The toolchain
+    /// name can be left out, taking the only argument as the JAVA_HOME.
+    ///
+    #[clap(long = "validate-only")]
+    validate_only: bool,
+    ///
+    /// Set the new toolchain as the default if no default toolchain is configured yet.
+    ///
+    #[clap(long = "default-if-none")]
+    default_if_none: bool,
+    ///
+    /// Determine the architecture of the toolchain by running java instead of trusting its release file.
+    ///
+    #[clap(long = "probe-arch")]
+    probe_arch: bool,
+    ///
+    /// Copy the JAVA_HOME into the jvms installation and register the copy, which is deleted when the toolchain is
+    /// removed with --purge or by `jvms gc`.
+    ///
+    #[clap(long = "copy-into")]
+    copy_into: bool,
+    ///
+    /// Register the toolchain even if the JAVA_HOME does not exist yet, such as a lazily mounted network path. The
+    /// toolchain is marked as unverified instead of failing validation while its JAVA_HOME is unavailable.
+    ///
+    #[clap(long = "allow-missing")]
+    allow_missing: bool,
+    ///
+    /// Tag the new toolchain. May be given multiple times.
+    ///
+    #[clap(long = "tag", number_of_values = 1)]
+    tags: Vec<String>,
+    ///
+    /// Make the new toolchain the default for its detected major java version, which is used when a toolchain is
+    /// selected by major version alone.
+    ///
+    #[clap(long = "set-default-version")]
+    set_default_version: bool,
+    ///
+    /// Run java to determine the version of the toolchain if it cannot be read from its release file, version file,
+    /// or directory name.
+    ///
+    #[clap(long = "probe-version")]
+    probe_version: bool,
+    ///
+    /// Create an alias referring to the new toolchain, which can be used wherever a toolchain name is accepted. May
+    /// be given multiple times.
+    ///
+    #[clap(long = "alias", number_of_values = 1)]
+    aliases: Vec<String>,
+    ///
+    /// Refuse to register the toolchain if its JAVA_HOME already belongs to another toolchain, instead of only
+    /// warning about it.
+    ///
+    #[clap(long = "check-duplicate-home")]
+    check_duplicate_home: bool
+}
+
+#[derive(Clap)]
+struct ToolchainDiscoverCommand {
+    ///
+    /// The directories to search. Each may be a java home itself or a directory containing java homes.
+    ///
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+    ///
+    /// Search every subdirectory instead of only the direct children of each directory.
+    ///
+    #[clap(short = "r", long = "recursive")]
+    recursive: bool,
+    ///
+    /// Only consider java homes whose path matches this glob ('*' and '?'). May be given multiple times.
+    ///
+    #[clap(long = "include", number_of_values = 1)]
+    include: Vec<String>,
+    ///
+    /// Skip directories whose path matches this glob ('*' and '?'), without inspecting or searching them. May be
+    /// given multiple times.
+    ///
+    #[clap(long = "exclude", number_of_values = 1)]
+    exclude: Vec<String>,
+    ///
+    /// Only print the JDKs that would be registered.
+    ///
+    #[clap(long = "dry-run")]
+    dry_run: bool,
+    ///
+    /// Force save configuration changes, even if configuration is invalid.
+    ///
+    #[clap(short = "f", long = "force")]
+    force: bool
+}
+
+#[derive(Clap)]
+struct ToolchainExportPathCommand {
+    ///
+    /// The name of the toolchain, defaults to the toolchain resolved for the current directory.
+    ///
+    toolchain_name: Option<String>
+}
+
+#[derive(Clap)]
+struct ToolchainInstallCommand {
+    ///
+    /// The major java version to install, optionally prefixed with "temurin-".
+    ///
+    spec: String,
+    ///
+    /// The name of the new toolchain, defaults to "temurin-" followed by the installed version.
+    ///
+    #[clap(long = "name")]
+    name: Option<String>,
+    ///
+    /// The architecture to install, as named by Adoptium, defaults to the architecture of jvms.
+    ///
+    #[clap(long = "arch")]
+    arch: Option<String>,
+    ///
+    /// Print the archive that would be downloaded and where it would be installed, without downloading it.
+    ///
+    #[clap(long = "dry-run")]
+    dry_run: bool,
+    ///
+    /// Install the archive without verifying its checksum and signature. Only meant for providers or mirrors that
+    /// publish no checksums.
+    ///
+    #[clap(long = "no-verify")]
+    no_verify: bool
+}
+
+#[derive(Clap)]
+struct ToolchainListCommand {
+    ///
+    /// The key to sort toolchains by. Toolchains without a detected version or vendor are listed last.
+    ///
+    #[clap(long = "sort", default_value = "name", possible_values = &["name", "version", "vendor"])]
+    sort: ToolchainSortKey,
+    ///
+    /// Reverse the sort order.
+    ///
+    #[clap(long = "reverse")]
+    reverse: bool,
+    ///
+    /// Only list toolchains whose name contains the provided text, or matches it if it is a glob ('*' or '?').
+    ///
+    #[clap(long = "filter")]
+    filter: Option<String>,
+    ///
+    /// Only list toolchains whose vendor contains the provided text, ignoring case.
+    ///
+    #[clap(long = "vendor")]
+    vendor: Option<String>,
+    ///
+    /// Only list toolchains of the provided major version.
+    ///
+    #[clap(long = "major")]
+    major: Option<u32>,
+    ///
+    /// List at most this many toolchains, after sorting.
+    ///
+    #[clap(long = "limit")]
+    limit: Option<usize>,
+    ///
+    /// Only list toolchains with the provided tag.
+    ///
+    #[clap(long = "tag")]
+    tag: Option<String>,
+    ///
+    /// Print only the number of matching toolchains instead of listing them.
+    ///
+    #[clap(long = "count")]
+    count: bool,
+    ///
+    /// Exit with a non-zero status if there are no matching toolchains to list.
+    ///
+    #[clap(long = "fail-if-empty")]
+    fail_if_empty: bool,
+    ///
+    /// Only list toolchains whose JAVA_HOME or java binary is missing.
+    ///
+    #[clap(long = "missing-only", conflicts_with = "present-only")]
+    missing_only: bool,
+    ///
+    /// Only list toolchains whose JAVA_HOME and java binary are present.
+    ///
+    #[clap(long = "present-only")]
+    present_only: bool,
+    ///
+    /// Print each toolchain as five NUL-terminated fields, its name, JAVA_HOME, version, vendor, and architecture,
+    /// for use with `xargs -0`. Unknown fields are empty.
+    ///
+    #[clap(long = "print0", conflicts_with = "count")]
+    print0: bool,
+    ///
+    /// Only print the name of each toolchain, one per line, e.g. for shell completion.
+    ///
+    #[clap(long = "names", conflicts_with_all = &["count", "print0"])]
+    names: bool
+}
+
+enum ToolchainSortKey {
+    Name,
+    Version,
+    Vendor
+}
+
+impl FromStr for ToolchainSortKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(ToolchainSortKey::Name),
+            "version" => Ok(ToolchainSortKey::Version),
+            "vendor" => Ok(ToolchainSortKey::Vendor),
+            _ => Err(format!("Unknown sort key: {}", s))
+        }
+    }
+}
+
+#[derive(Clap)]
+struct ToolchainOutdatedCommand { }
+
+#[derive(Clap)]
+struct ToolchainRemoveCommand {
+    ///
+    /// The name of the toolchain to remove.
+    ///
+    #[clap(required_unless_present = "tag", conflicts_with = "tag")]
+    toolchain_name: Option<String>,
+    ///
+    /// Remove every toolchain with the provided tag instead of a single toolchain. As with a single toolchain, nothing
+    /// is removed while one of them is still in use, unless --cascade is passed.
+    ///
+    #[clap(long = "tag")]
+    tag: Option<String>,
+    ///
+    /// Confirm removing the toolchains selected by --tag. Without it the toolchains are only listed.
+    ///
+    #[clap(short = "y", long = "yes")]
+    yes: bool,
+    ///
+    /// Also remove the overrides and clear the default and version defaults that would be left without a toolchain.
+    /// Without it, a toolchain that is still in use is not removed.
+    ///
+    #[clap(long = "cascade")]
+    cascade: bool,
+    ///
+    /// Also delete the files of toolchains downloaded or copied by jvms. Without it they are kept until `jvms gc`
+    /// is run.
+    ///
+    #[clap(long = "purge")]
+    purge: bool,
+    ///
+    /// Force save configuration changes, even if configuration is invalid.
+    ///
+    #[clap(short = "f", long = "force")]
+    force: bool
+}
+
+#[derive(Clap)]
+struct ToolchainRenameCommand {
+    ///
+    /// The current name of the toolchain.
+    ///
+    old_name: String,
+    ///
+    /// The new name of the toolchain.
+    ///
+    new_name: String,
+    ///
+    /// Also update .java-version, .jvmsrc, and .tool-versions files in override directories that reference the old
+    /// name.
+    ///
+    #[clap(long = "update-project-files")]
+    update_project_files: bool,
+    ///
+    /// Update project files without asking for confirmation.
+    ///
+    #[clap(short = "y", long = "yes")]
+    yes: bool,
+    ///
+    /// Force save configuration changes, even if configuration is invalid.
+    ///
+    #[clap(short = "f", long = "force")]
+    force: bool
+}
+
+#[derive(Clap)]
+struct ToolchainScanCommand {
+    ///
+    /// Skip directories whose path matches this glob ('*' and '?'), without inspecting them. May be given multiple
+    /// times.
+    ///
+    #[clap(long = "exclude", number_of_values = 1)]
+    exclude: Vec<String>,
+    ///
+    /// Only print the JDKs that would be registered.
+    ///
+    #[clap(long = "dry-run")]
+    dry_run: bool,
+    ///
+    /// Force save configuration changes, even if configuration is invalid.
+    ///
+    #[clap(short = "f", long = "force")]
+    force: bool
+}
+
+#[derive(Clap)]
+struct ToolchainTagCommand {
+    ///
+    /// The name of the toolchain to change.
+    ///
+    toolchain_name: String,
+    ///
+    /// The tags to add or remove.
+    ///
+    #[clap(required = true)]
+    tags: Vec<String>,
+    ///
+    /// Force save configuration changes, even if configuration is invalid.
+    ///
+    #[clap(short = "f", long = "force")]
+    force: bool
+}
+
+#[derive(Clap)]
+struct UiCommand { }
+
+#[derive(Clap)]
+struct UninstallCommand {
+    ///
+    /// The installation directory to uninstall, defaults to the installation of this jvms binary.
+    ///
+    installation_path: Option<PathBuf>,
+    ///
+    /// Also delete the configuration, the toolchains installed by jvms, and the cache of the installation.
+    ///
+    #[clap(long = "remove-config")]
+    remove_config: bool
+}
+
+#[derive(Clap)]
+struct UseCommand {
+    ///
+    /// The toolchain name, alias, or a version requirement such as 17 or 17+.
+    ///
+    toolchain: Option<String>,
+    ///
+    /// Print commands that end the session's toolchain selection instead.
+    ///
+    #[clap(long = "unset", conflicts_with = "toolchain")]
+    unset: bool,
+    ///
+    /// The shell to print commands for, detected from SHELL if omitted.
+    ///
+    #[clap(long = "shell", possible_values = &["sh", "bash", "zsh", "fish", "powershell", "cmd"])]
+    shell: Option<ShellSyntax>
+}
+
+#[derive(Clone, Copy)]
+enum ShellSyntax {
+    Posix,
+    Fish,
+    PowerShell,
+    Cmd
+}
+
+impl FromStr for ShellSyntax {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "sh" | "bash" | "zsh" => Ok(ShellSyntax::Posix),
+            "fish" => Ok(ShellSyntax::Fish),
+            "powershell" => Ok(ShellSyntax::PowerShell),
+            "cmd" => Ok(ShellSyntax::Cmd),
+            _ => Err(format!("Unknown shell: {}", s))
+        }
+    }
+}
+
+impl ShellSyntax {
+
+    ///
+    /// Formats a command setting the environment variable, quoting the value for the shell.
+    ///
+    fn format_set(self, name: &str, value: &str) -> String {
+        match self {
+            ShellSyntax::Posix => format!("export {}='{}';", name, value.replace('\'', "'\\''")),
+            ShellSyntax::Fish => format!("set -gx {} '{}';", name, value.replace('\\', "\\\\").replace('\'', "\\'")),
+            ShellSyntax::PowerShell => format!("$env:{} = '{}'", name, value.replace('\'', "''")),
+            ShellSyntax::Cmd => format!("set \"{}={}\"", name, value)
+        }
+    }
+
+    ///
+    /// Formats a command removing the environment variable.
+    ///
+    fn format_unset(self, name: &str) -> String {
+        match self {
+            ShellSyntax::Posix => format!("unset {};", name),
+            ShellSyntax::Fish => format!("set -e {};", name),
+            ShellSyntax::PowerShell => format!("Remove-Item Env:{} -ErrorAction SilentlyContinue", name),
+            ShellSyntax::Cmd => format!("set {}=", name)
+        }
+    }
+
+    ///
+    /// Formats a command prepending the directory to PATH.
+    ///
+    fn format_prepend_path(self, directory: &str) -> String {
+        match self {
+            ShellSyntax::Posix => format!("export PATH='{}':\"$PATH\";", directory.replace('\'', "'\\''")),
+            ShellSyntax::Fish => format!("set -gx PATH '{}' $PATH;", directory.replace('\\', "\\\\").replace('\'', "\\'")),
+            ShellSyntax::PowerShell => format!("$env:PATH = '{}' + [IO.Path]::PathSeparator + $env:PATH", directory.replace('\'', "''")),
+            ShellSyntax::Cmd => format!("set \"PATH={};%PATH%\"", directory)
+        }
+    }
+
+    ///
+    /// Formats a command that applies the output of the jvms command line to the current shell.
+    ///
+    fn format_apply(self, command_line: &str) -> String {
+        match self {
+            ShellSyntax::Posix => format!("eval \"$({})\"", command_line),
+            ShellSyntax::Fish => format!("{} | source", command_line),
+            ShellSyntax::PowerShell => format!("{} | Out-String | Invoke-Expression", command_line),
+            ShellSyntax::Cmd => format!("for /f \"delims=\" %i in ('{}') do %i", command_line)
+        }
+    }
+
+}
+
+#[derive(Clap)]
+struct WhichCommand {
+    ///
+    /// The tool to locate.
+    ///
+    #[clap(default_value = "java")]
+    tool: String,
+    ///
+    /// Print the path of every shimmed tool, marking any that are missing from the toolchain.
+    ///
+    #[clap(long = "all")]
+    all: bool,
+    ///
+    /// Print each tool as three NUL-terminated fields, its name, path, and either "present" or "missing", for use
+    /// with `xargs -0`.
+    ///
+    #[clap(long = "print0")]
+    print0: bool
+}
+
+impl JvmsCommand {
+
+    ///
+    /// Whether the command saves the configuration, in which case the configuration lock is taken before loading it
+    /// so that concurrent edits are applied one after another. Installing a toolchain is left out so that a slow
+    /// download does not block other commands, its save fails instead if the configuration changed meanwhile.
+    ///
+    fn modifies_configuration(&self) -> bool {
+        match self {
+            JvmsCommand::Config(ConfigCommand::Merge(_)) | JvmsCommand::Config(ConfigCommand::Migrate(_)) => true,
+            JvmsCommand::Default(cmd) => cmd.toolchain.is_some(),
+            JvmsCommand::Doctor(cmd) => cmd.fix,
+            JvmsCommand::Override(OverrideCommand::List(_)) | JvmsCommand::Override(OverrideCommand::Export(_)) => false,
+            JvmsCommand::Override(_) => true,
+            JvmsCommand::Toolchain(ToolchainCommand::Add(cmd)) => !cmd.validate_only,
+            JvmsCommand::Toolchain(ToolchainCommand::Discover(cmd)) => !cmd.dry_run,
+            JvmsCommand::Toolchain(ToolchainCommand::Scan(cmd)) => !cmd.dry_run,
+            JvmsCommand::Toolchain(ToolchainCommand::Remove(_))
+                | JvmsCommand::Toolchain(ToolchainCommand::Rename(_))
+                | JvmsCommand::Toolchain(ToolchainCommand::Tag(_))
+                | JvmsCommand::Toolchain(ToolchainCommand::Untag(_)) => true,
+            _ => false
+        }
+    }
+
+}
+
+impl Jvms {
+
+    ///
+    /// Parses the arguments of the current process and runs the command, returning the exit code of the process.
+    ///
+    pub fn execute(jvms_installation: &JvmsInstallation) -> Result<i32> {
+        let mut opts: Jvms = Jvms::parse_from(Jvms::split_shebang_args(env::args_os().collect()));
+        match opts.config.take() {
+            Some(config_file_path) => {
+                let jvms_installation = JvmsInstallation::new(jvms_installation.get_installation_path().to_path_buf())
+                    .with_config_file(config_file_path);
+
+                //
+                // Shims and other jvms processes started by this command use the same configuration.
+                //
+
+                env::set_var(config::CONFIG_ENV, jvms_installation.get_config_file_path());
+                Jvms::execute_opts(opts, &jvms_installation)
+            },
+            None => Jvms::execute_opts(opts, jvms_installation)
+        }
+    }
+
+    ///
+    /// Runs the command of already parsed options. The actions picked in `jvms ui` go through here as well.
+    ///
+    /// Failures are returned as errors. The returned exit code is only non-zero for checks that did not pass, such
+    /// as `--fail-if-empty`, and for commands that report the exit code of a program they ran.
+    ///
+    fn execute_opts(opts: Jvms, jvms_installation: &JvmsInstallation) -> Result<i32> {
+        if opts.command.modifies_configuration() {
+            jvms_installation.lock_configuration()?;
+        }
+
+        let jvms_config = jvms_installation.load_configuration();
+        let offline = opts.offline;
+        let json = opts.json;
+        let search_root = config::get_search_root(opts.root);
+        match opts.command {
+
+            //
+            // Cache subcommands
+            //
+
+            JvmsCommand::Cache(CacheCommand::Clear(_)) => {
+                match jvms_installation.get_cache_path() {
+                    Some(cache_path) if cache_path.exists() => {
+                        fs::remove_dir_all(&cache_path)?;
+                        println!("Cleared cache {:?}", cache_path);
+                    },
+                    _ => println!("Cache is already empty")
+                }
+            },
+            JvmsCommand::Cache(CacheCommand::Path(_)) => {
+                match jvms_installation.get_cache_path() {
+                    Some(cache_path) => println!("{}", cache_path.display()),
+                    None => return Err(JvmsError::InvalidConfiguration("No cache location is available on this platform.".to_owned()))
+                }
+            },
+
+            //
+            // Completions subcommand
+            //
+
+            JvmsCommand::Completions(cmd) => {
+                completions::write_completions(cmd.shell, &mut Jvms::into_app(), &mut io::stdout())?;
+            },
+
+            //
+            // Config subcommands
+            //
+
+            JvmsCommand::Config(ConfigCommand::Merge(cmd)) => {
+                let mut config = jvms_config?;
+                let other = if cmd.path == Path::new("-") {
+                    serde_json::from_reader(Jvms::open_input(&cmd.path)?)
+                        .map_err(|error| JvmsError::ConfigParse { path: cmd.path.clone(), error })?
+
+                } else {
+                    JvmsConfiguration::load(&cmd.path)?
+                };
+
+                let policy = match cmd.prefer {
+                    MergePreference::Existing => MergePolicy::PreferExisting,
+                    MergePreference::Imported => MergePolicy::PreferImported
+                };
+
+                let report = config.merge(other, policy);
+                jvms_installation.save_configuration(&config, cmd.force)?;
+
+                for (label, entries) in &[("Added", &report.added), ("Updated", &report.updated), ("Skipped", &report.skipped)] {
+                    for entry in entries.iter() {
+                        println!("{} {}", label, entry);
+                    }
+                }
+
+                println!("Merge complete: {} added, {} updated, {} skipped.", report.added.len(), report.updated.len(), report.skipped.len());
+            },
+            JvmsCommand::Config(ConfigCommand::Migrate(_)) => {
+                let backup_path = jvms_installation.migrate_configuration()?;
+                println!("Migrated the configuration to {:?}, the previous configuration was kept as {:?}", jvms_installation.get_config_file_path(), backup_path);
+            },
+            JvmsCommand::Config(ConfigCommand::Validate(cmd)) => {
+                let config = match &cmd.path {
+                    Some(path) => JvmsConfiguration::load(path),
+                    None => jvms_config
+                };
+
+                let mut notes = Vec::new();
+                let errors: Vec<String> = match config {
+                    Ok(config) => {
+                        let mut toolchains: Vec<_> = config.get_toolchains().collect();
+                        toolchains.sort_by(|a, b| a.0.cmp(b.0));
+                        for (name, toolchain) in toolchains {
+                            if !toolchain.is_verified() && !toolchain.java_home.exists() {
+                                notes.push(format!("Unverified toolchain {} is not currently available at {:?}", name, toolchain.java_home));
+                            }
+                        }
+
+                        config.collect_validation_errors().iter().map(|error| error.to_string()).collect()
+                    },
+                    Err(error) => vec![error.to_string()]
+                };
+
+                if json {
+                    Jvms::print_json(&errors)?;
+
+                } else if errors.is_empty() {
+                    println!("Configuration is valid.");
+
+                } else {
+                    println!("Configuration has {} problem(s):", errors.len());
+                    for error in &errors {
+                        println!("  - {}", error);
+                    }
+                }
+
+                if !json {
+                    for note in &notes {
+                        eprintln!("Note: {}", note);
+                    }
+                }
+
+                if !errors.is_empty() {
+                    return Ok(error::EXIT_CONFIG_ERROR);
+                }
+            },
+
+            //
+            // Current subcommand
+            //
+
+            JvmsCommand::Current(_) => {
+                let config = jvms_config?;
+                let current_dir = env::current_dir()?;
+                match config.resolve_toolchain_with_source(&current_dir, search_root.as_deref())? {
+                    Some(resolved) if json => {
+                        Jvms::print_json(&serde_json::json!({
+                            "toolchain": resolved.name,
+                            "source": resolved.source.get_label(),
+                            "source_path": resolved.source.get_path(),
+                            "java_home": resolved.toolchain.java_home,
+                            "version": resolved.toolchain.read_jdk_info().and_then(|info| info.version)
+                        }))?;
+                    },
+                    Some(resolved) => {
+                        match resolved.name {
+                            Some(name) => println!("Active toolchain: {}", name),
+                            None => println!("Active java home: {:?}", resolved.toolchain.java_home)
+                        }
+
+                        println!("  - Source = {}", resolved.source.get_description());
+                        println!("  - JAVA_HOME = {:?}", resolved.toolchain.java_home);
+                        if let Some(version) = resolved.toolchain.read_jdk_info().and_then(|info| info.version) {
+                            println!("  - Version = {}", version);
+                        }
+                    },
+                    None => return Err(JvmsError::NoToolchainResolved { path: current_dir })
+                }
+            },
+
+            //
+            // Default subcommand
+            //
+
+            JvmsCommand::Default(cmd) => {
+                let mut config = jvms_config?;
+                if let Some(toolchain_name) = cmd.toolchain {
+                    if let Some((selected_name, toolchain)) = config.select_toolchain(&toolchain_name) {
+                        //
+                        // Refuse a default whose java home is gone, otherwise the problem only shows up at launch.
+                        //
+
+                        if !cmd.force && !toolchain.java_home.is_dir() {
+                            return Err(JvmsError::ToolchainHomeMissing { name: selected_name.to_owned(), path: toolchain.java_home.clone() });
+                        }
+
+                        let java_home = toolchain.java_home.clone();
+                        let previous_toolchain = config.get_default_toolchain_name().map(|name| name.to_owned());
+                        let hook_context = HookContext {
+                            toolchain: Some(&toolchain_name),
+                            java_home: Some(&java_home),
+                            previous_toolchain: previous_toolchain.as_deref(),
+                            ..HookContext::default()
+                        };
+
+                        config.run_hook(HookEvent::PreDefault, &hook_context)?;
+
+                        //
+                        // A version requirement is stored as is and matched again at launch, so that the default
+                        // follows upgrades of the toolchains it matches.
+                        //
+
+                        if selected_name != toolchain_name {
+                            println!("Setting default installation to {}, currently {}", toolchain_name, selected_name);
+
+                        } else {
+                            println!("Setting default installation to {}", toolchain_name);
+                        }
+
+                        config.set_default_toolchain_name(toolchain_name.clone());
+                        jvms_installation.save_configuration(&config, cmd.force)?;
+                        config.run_hook(HookEvent::PostDefault, &hook_context)?;
+
+                    } else {
+                        return Err(config.unknown_toolchain_error(toolchain_name));
+                    }
+
+                } else {
+                    println!("Default installation: {}", config.get_default_toolchain_name().unwrap_or("None"));
+                }
+            }
+
+            //
+            // Doctor subcommand
+            //
+
+            JvmsCommand::Doctor(cmd) => {
+                let mut config = jvms_config?;
+                let mut problem_count = 0;
+                let mut config_changed = false;
+
+                println!("Toolchains:");
+                let mut toolchains: Vec<_> = config.get_toolchains().collect();
+                toolchains.sort_by(|a, b| a.0.cmp(b.0));
+
+                let mut available_toolchains = Vec::new();
+                for (name, toolchain) in toolchains {
+                    let java_path = jdk::get_tool_path(&toolchain.java_home, "java");
+                    if !toolchain.is_verified() && toolchain.java_home.is_dir() {
+                        available_toolchains.push(name.clone());
+                    }
+
+                    if !toolchain.is_verified() && !toolchain.java_home.exists() {
+                        println!("  - {}: Unverified java home {:?} is not currently available.", name, toolchain.java_home);
+
+                    } else if !toolchain.java_home.is_dir() {
+                        println!("  - {}: Java home {:?} does not exist.", name, toolchain.java_home);
+                        problem_count += 1;
+
+                    } else if !java_path.is_file() {
+                        println!("  - {}: Java binary {:?} does not exist.", name, java_path);
+                        problem_count += 1;
+
+                    } else if !jdk::is_executable(&java_path) {
+                        if cmd.fix && Jvms::confirm(&format!("Make {:?} executable?", java_path), cmd.yes)? {
+                            jdk::set_executable(&java_path)?;
+                            println!("  - {}: Java binary {:?} was not executable, fixed.", name, java_path);
+
+                        } else {
+                            println!("  - {}: Java binary {:?} is not executable, run with --fix to repair.", name, java_path);
+                            problem_count += 1;
+                        }
+
+                    } else {
+                        println!("  - {}: OK", name);
+                    }
+                }
+
+                //
+                // Once a java home registered with --allow-missing shows up, it is checked like any other from then on.
+                //
+
+                for name in available_toolchains {
+                    if !cmd.fix {
+                        println!("  - {}: Unverified java home is now available, run with --fix to mark it as verified.", name);
+
+                    } else if config.get_toolchain_mut(&name).is_some_and(JavaToolchain::verify) {
+                        println!("  - {}: Unverified java home is now available, marked as verified.", name);
+                        config_changed = true;
+                    }
+                }
+
+                println!("Overrides:");
+                let dangling_override_count = config.get_overrides()
+                    .unwrap_or(&[])
+                    .iter()
+                    .filter(|o| !o.path.exists() || config.select_toolchain(&o.toolchain).is_none())
+                    .count();
+
+                if dangling_override_count == 0 {
+                    println!("  - OK");
+
+                } else if cmd.fix && Jvms::confirm(&format!("Remove {} dangling override(s)?", dangling_override_count), cmd.yes)? {
+                    for (jvms_override, reason) in config.clean_overrides(OverrideCleanPolicy::All) {
+                        println!("  - Removed override {:?} -> {}: {}", jvms_override.path, jvms_override.toolchain, reason);
+                    }
+
+                    config_changed = true;
+
+                } else {
+                    for o in config.get_overrides().unwrap_or(&[]) {
+                        if !o.path.exists() {
+                            println!("  - {:?}: Directory no longer exists.", o.path);
+
+                        } else if config.select_toolchain(&o.toolchain).is_none() {
+                            println!("  - {:?}: Toolchain {} no longer exists.", o.path, o.toolchain);
+                        }
+                    }
+
+                    problem_count += dangling_override_count;
+                }
+
+                println!("Default:");
+                match config.get_default_toolchain_name().map(|name| name.to_owned()) {
+                    Some(default) if config.select_toolchain(&default).is_none() => {
+                        if cmd.fix && Jvms::confirm(&format!("Unset default toolchain {}?", default), cmd.yes)? {
+                            config.clear_default_toolchain_name();
+                            config_changed = true;
+                            println!("  - Default toolchain {} no longer exists, unset.", default);
+
+                        } else {
+                            println!("  - Default toolchain {} no longer exists.", default);
+                            problem_count += 1;
+                        }
+                    },
+                    Some(default) => println!("  - {}: OK", default),
+                    None => println!("  - None")
+                }
+
+                println!("Shims:");
+                let missing_shims = jvms_installation.get_missing_shims();
+                if missing_shims.is_empty() {
+                    println!("  - OK");
+                }
+
+                for shim in missing_shims {
+                    if cmd.fix && Jvms::confirm(&format!("Recreate missing shim {}?", shim.name()), cmd.yes)? {
+                        jvms_installation.link_shim(shim, JvmsInstallation::get_default_link_mode()?)?;
+                        println!("  - {}: Missing, recreated.", shim.name());
+
+                    } else {
+                        println!("  - {}: Missing.", shim.name());
+                        problem_count += 1;
+                    }
+                }
+
+                if config_changed {
+                    jvms_installation.save_configuration(&config, true)?;
+                }
+
+                //
+                // Validate last so that the result reflects any fixes made above.
+                //
+
+                if let Err(error) = config.validate_configuration() {
+                    println!("Configuration: {}", error);
+                    problem_count += 1;
+
+                } else {
+                    println!("Configuration: OK");
+                }
+
+                if problem_count != 0 {
+                    return Ok(error::EXIT_FAILURE);
+                }
+            },
+
+            //
+            // Env subcommand
+            //
+
+            JvmsCommand::Env(cmd) => {
+                let config = jvms_config?;
+                let shell = cmd.shell.unwrap_or_else(Jvms::detect_shell_syntax);
+                let current_dir = env::current_dir()?;
+                let resolved = config.resolve_home_with_toolchain(&current_dir, search_root.as_deref(), None)?;
+
+                println!("{}", shell.format_set("JAVA_HOME", &resolved.java_home.to_string_lossy()));
+                if cmd.path {
+                    println!("{}", shell.format_prepend_path(&resolved.java_home.join("bin").to_string_lossy()));
+                }
+            },
+
+            //
+            // Exec subcommand
+            //
+
+            JvmsCommand::Exec(cmd) => {
+                let config = jvms_config?;
+                let (_, toolchain) = config.select_toolchain(&cmd.toolchain_name)
+                    .ok_or_else(|| config.unknown_toolchain_error(cmd.toolchain_name.clone()))?;
+
+                return Jvms::exec_with_toolchain(&config, toolchain, &cmd.command[0], &cmd.command[1..], cmd.path, cmd.dedupe_path);
+            },
+
+            //
+            // Export subcommands
+            //
+
+            JvmsCommand::Export(ExportCommand::MavenToolchains(cmd)) => {
+                let config = jvms_config?;
+                let file = match cmd.file.or_else(maven::get_user_toolchains_path) {
+                    Some(file) => file,
+                    None => return Err(JvmsError::InvalidArguments("No home directory found, pass --file with the toolchains.xml to write.".to_owned()))
+                };
+
+                let mut toolchains: Vec<_> = config.get_toolchains().collect();
+                toolchains.sort_by(|a, b| a.0.cmp(b.0));
+
+                let toolchains: Vec<_> = toolchains
+                    .into_iter()
+                    .map(|(name, toolchain)| {
+                        let jdk_info = toolchain.read_jdk_info().unwrap_or_default();
+                        MavenToolchain {
+                            id: name.clone(),
+                            version: jdk_info.version,
+                            vendor: jdk_info.vendor,
+                            jdk_home: toolchain.java_home.clone()
+                        }
+                    })
+                    .collect();
+
+                let existing = match fs::read_to_string(&file) {
+                    Ok(contents) => Some(contents),
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                    Err(error) => return Err(error.into())
+                };
+
+                let merged = maven::merge_toolchains(existing.as_deref(), &toolchains)?;
+                if cmd.dry_run {
+                    print!("{}", merged);
+
+                } else {
+                    if let Some(parent) = file.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+
+                    fs::write(&file, merged)?;
+                    println!("Exported {} toolchains to {:?}", toolchains.len(), file);
+                }
+            },
+
+            //
+            // Gc subcommand
+            //
+
+            JvmsCommand::Gc(cmd) => {
+                let config = jvms_config?;
+                let unreferenced_dirs = jvms_installation.find_unreferenced_toolchain_dirs(&config)?;
+                if unreferenced_dirs.is_empty() {
+                    println!("No unused JDKs found in {:?}", jvms_installation.get_toolchains_path());
+                }
+
+                for dir in unreferenced_dirs {
+                    if cmd.dry_run {
+                        println!("Would delete {:?}", dir);
+
+                    } else {
+                        println!("Deleting {:?}", dir);
+                        fs::remove_dir_all(&dir)?;
+                    }
+                }
+            },
+
+            //
+            // Info subcommand
+            //
+
+            JvmsCommand::Info(_) => {
+                let installation_path = jvms_installation.get_installation_path();
+                let config_file_path = jvms_installation.get_config_file_path();
+                let system_config_file_path = jvms_installation.get_system_config_file_path();
+                let missing_shims: Vec<&str> = jvms_installation.get_missing_shims()
+                    .iter()
+                    .map(|shim| shim.name())
+                    .collect();
+
+                if json {
+                    Jvms::print_json(&serde_json::json!({
+                        "installation_path": installation_path,
+                        "config_file_path": config_file_path,
+                        "config_file_exists": config_file_path.is_file(),
+                        "system_config_file_path": system_config_file_path,
+                        "missing_shims": missing_shims
+                    }))?;
+
+                } else {
+                    println!("Installation: {:?}", installation_path);
+                    if let Some(system_config_file_path) = system_config_file_path.as_ref() {
+                        println!("System configuration: {:?}", system_config_file_path);
+                    }
+
+                    if config_file_path.is_file() {
+                        println!("Configuration: {:?}", config_file_path);
+                    } else {
+                        println!("Configuration: {:?} (does not exist)", config_file_path);
+                    }
+
+                    if missing_shims.is_empty() {
+                        println!("Shims: OK");
+                    } else {
+                        println!("Shims: missing {} (run jvms doctor --fix to recreate them)", missing_shims.join(", "));
+                    }
+                }
+            },
+
+            //
+            // Install subcommand
+            //
+
+            JvmsCommand::Install(cmd) => {
+                let link_mode = match cmd.link_mode {
+                    Some(link_mode) => link_mode,
+                    None => JvmsInstallation::get_default_link_mode()?
+                };
+
+                let new_installation = JvmsInstallation::new(cmd.destination_path);
+                println!("Copying binaries to {:?}", new_installation.get_installation_path());
+                new_installation.install_binaries(link_mode)?;
+
+                if !cmd.no_modify_path {
+                    Jvms::add_to_path(&config::make_absolute(new_installation.get_installation_path()), cmd.yes)?;
+                }
+
+                println!("Finished installing jvms to {:?}", new_installation.get_installation_path());
+            },
+
+            //
+            // LsRemote subcommand
+            //
+
+            JvmsCommand::LsRemote(cmd) => {
+                if let Some(vendor) = cmd.vendor.as_deref() {
+                    if !vendor.eq_ignore_ascii_case("temurin") && !adoptium::is_adoptium_vendor(vendor) {
+                        return Err(JvmsError::InvalidToolchain(format!("Releases of {} are not available, only temurin is supported for now.", vendor)));
+                    }
+                }
+
+                if offline {
+                    return Err(JvmsError::NetworkError("Cannot list remote releases while offline.".to_owned()));
+                }
+
+                let config = jvms_config.ok();
+                let mirror = config.as_ref().and_then(|config| config.get_mirror(adoptium::PROVIDER_NAME));
+                let version = match cmd.version.as_deref() {
+                    Some(version) => version.strip_prefix("temurin-").unwrap_or(version),
+                    None => {
+                        println!("Available Eclipse Temurin major versions:");
+                        for (major_version, lts) in adoptium::list_major_versions(mirror)? {
+                            println!("  - {}{}", major_version, if lts { " (LTS)" } else { "" });
+                        }
+
+                        return Ok(0);
+                    }
+                };
+
+                let major_version: u32 = version
+                    .split('.')
+                    .next()
+                    .and_then(|major_version| major_version.parse().ok())
+                    .ok_or_else(|| JvmsError::InvalidToolchain(format!("{} is not a java version.", version)))?;
+
+                let arch = cmd.arch.as_deref().unwrap_or_else(|| adoptium::get_current_arch());
+                let versions: Vec<String> = adoptium::list_versions(major_version, arch, mirror)?
+                    .into_iter()
+                    .filter(|release_version| release_version == version || release_version.starts_with(&format!("{}.", version)))
+                    .collect();
+
+                if versions.is_empty() {
+                    return Err(JvmsError::InvalidToolchain(format!("No Eclipse Temurin releases of java {} found for {}.", version, arch)));
+                }
+
+                //
+                // Releases are marked as installed when a toolchain has the name `toolchain install` would give them.
+                //
+
+                println!("Eclipse Temurin releases of java {} for {}:", version, arch);
+                for release_version in versions {
+                    let installed = config.as_ref().is_some_and(|config| config.has_toolchain(&format!("temurin-{}", release_version)));
+                    println!("  - {}{}", release_version, if installed { " (installed)" } else { "" });
+                }
+            },
+
+            //
+            // Override subcommands
+            //
+
+            JvmsCommand::Override(OverrideCommand::Clean(cmd)) => {
+                let mut config = jvms_config?;
+                let policy = if cmd.all || (cmd.missing_dir && cmd.missing_toolchain) {
+                    OverrideCleanPolicy::All
+                } else if cmd.missing_toolchain {
+                    OverrideCleanPolicy::MissingToolchain
+                } else {
+                    OverrideCleanPolicy::MissingDirectory
+                };
+
+                for (jvms_override, reason) in config.clean_overrides(policy) {
+                    println!("Removed override {:?} -> {}: {}", jvms_override.path, jvms_override.toolchain, reason);
+                }
+
+                jvms_installation.save_configuration(&config, cmd.force)?;
+            },
+            JvmsCommand::Override(OverrideCommand::Export(cmd)) => {
+                let config = jvms_config?;
+                let writer = Jvms::open_output(&cmd.path)?;
+                let overrides: Vec<_> = config.iter_overrides_sorted().collect();
+                serde_json::to_writer_pretty(writer, &overrides)?;
+            },
+            JvmsCommand::Override(OverrideCommand::Import(cmd)) => {
+                let mut config = jvms_config?;
+                let overrides: Vec<JvmsOverride> = serde_json::from_reader(Jvms::open_input(&cmd.path)?)?;
+                for o in overrides {
+                    if config.select_toolchain(&o.toolchain).is_none() {
+                        eprintln!("Warning: Override at {:?} references an unknown toolchain: {}", o.path, o.toolchain);
+                    }
+
+                    config.remove_override(&o.path);
+                    config.add_override_entry(o);
+                }
+
+                jvms_installation.save_configuration(&config, cmd.force)?;
+            },
+            JvmsCommand::Override(OverrideCommand::List(cmd)) => {
+                let config = jvms_config?;
+                let overrides: Vec<_> = config.iter_overrides_sorted().collect();
+                if cmd.count {
+                    println!("{}", overrides.len());
+
+                } else if json {
+                    Jvms::print_json(&overrides)?;
+
+                } else if cmd.print0 {
+                    for o in &overrides {
+                        Jvms::print_record0(&[o.path.as_os_str(), OsStr::new(&o.toolchain)])?;
+                    }
+
+                } else {
+                    println!("Registered overrides:");
+                    for o in &overrides {
+                        println!("  - {:?}:", o.path);
+                        println!("    - Toolchain: {}", o.toolchain);
+                        if o.is_exact() {
+                            println!("    - Exact: only this directory");
+                        }
+
+                        if let Some(java_opts) = o.java_opts.as_ref() {
+                            println!("    - Java options: {}", java_opts.join(" "));
+                        }
+
+                        if let Some(env) = o.env.as_ref() {
+                            let mut env: Vec<_> = env.iter().collect();
+                            env.sort();
+                            for (key, value) in env {
+                                println!("    - Environment: {}={}", key, value);
+                            }
+                        }
+                    }
+                }
+
+                if cmd.fail_if_empty && overrides.is_empty() {
+                    return Ok(error::EXIT_FAILURE);
+                }
+            },
+            JvmsCommand::Override(OverrideCommand::Remove(cmd)) => {
+                let mut config = jvms_config?;
+                let current_dir = env::current_dir().expect("Failed to get current working directory.");
+                config.remove_override(&cmd.path.unwrap_or(current_dir));
+                jvms_installation.save_configuration(&config, cmd.force)?;
+            },
+            JvmsCommand::Override(OverrideCommand::Set(cmd)) => {
+                let mut config = jvms_config?;
+                if config.select_toolchain(&cmd.toolchain_name).is_some() {
+                    let path = match cmd.path {
+                        Some(path) => config::make_absolute(path),
+                        None => env::current_dir().expect("Failed to get current working directory.")
+                    };
+
+                    if !path.is_dir() {
+                        eprintln!("Warning: {:?} is not an existing directory", path);
+                    }
+
+                    let mut jvms_override = JvmsOverride::new(path, cmd.toolchain_name);
+                    if cmd.exact {
+                        jvms_override.exact = Some(true);
+                    }
+
+                    if let Some(java_opts) = cmd.java_opts {
+                        jvms_override.java_opts = Some(Jvms::split_java_opts(&java_opts)?);
+                    }
+
+                    if !cmd.env.is_empty() {
+                        let mut env_vars = HashMap::new();
+                        for env_var in &cmd.env {
+                            match env_var.split_once('=') {
+                                Some((key, value)) if !key.is_empty() => env_vars.insert(key.to_owned(), value.to_owned()),
+                                _ => return Err(JvmsError::InvalidConfiguration(format!("Expected KEY=VALUE for --env but got: {}", env_var)))
+                            };
+                        }
+
+                        jvms_override.env = Some(env_vars);
+                    }
+
+                    let java_home = config.select_toolchain(&jvms_override.toolchain).map(|(_, t)| t.java_home.clone());
+                    let previous_toolchain = config.get_environment_override(&jvms_override.path).map(|o| o.toolchain.clone());
+                    let hook_context = HookContext {
+                        toolchain: Some(&jvms_override.toolchain),
+                        java_home: java_home.as_deref(),
+                        previous_toolchain: previous_toolchain.as_deref(),
+                        directory: Some(&jvms_override.path),
+                        ..HookContext::default()
+                    };
+
+                    config.run_hook(HookEvent::PreOverride, &hook_context)?;
+                    config.remove_override(&jvms_override.path);
+                    config.add_override_entry(jvms_override.clone());
+                    jvms_installation.save_configuration(&config, cmd.force)?;
+                    config.run_hook(HookEvent::PostOverride, &hook_context)?;
+
+                } else {
+                    return Err(config.unknown_toolchain_error(cmd.toolchain_name));
+                }
+            },
+
+            //
+            // Prompt subcommand
+            //
+            // This runs on every prompt render, so it should stay well under a millisecond: it only reads the
+            // configuration and the release file of the selected toolchain, skips validation, and prints nothing
+            // rather than an error so that a broken configuration never clutters the prompt.
+            //
+
+            JvmsCommand::Prompt(cmd) => {
+                let config = match jvms_config {
+                    Ok(config) => config,
+                    Err(_) => return Ok(0)
+                };
+
+                let current_dir = env::current_dir()?;
+                let (description, source) = match config.resolve_toolchain_with_source(&current_dir, search_root.as_deref()) {
+                    Ok(Some(resolved)) => {
+                        let description = resolved.toolchain.get_java_version()
+                            .map(|version| version.major().to_string())
+                            .or_else(|| resolved.name.map(|name| name.to_owned()))
+                            .unwrap_or_else(|| "?".to_owned());
+                        (description, resolved.source.get_label())
+                    },
+                    _ => return Ok(0)
+                };
+
+                if cmd.no_color || env::var_os("NO_COLOR").is_some() {
+                    println!("java:{} ({})", description, source);
+
+                } else {
+                    println!("\x1b[36mjava:{}\x1b[0m ({})", description, source);
+                }
+            },
+
+            //
+            // Rehash subcommand
+            //
+
+            JvmsCommand::Rehash(cmd) => {
+                let config = jvms_config?;
+                let link_mode = match cmd.link_mode {
+                    Some(link_mode) => link_mode,
+                    None => JvmsInstallation::get_default_link_mode()?
+                };
+
+                let (created_shims, removed_shims) = jvms_installation.rehash(&config, link_mode)?;
+                println!("Created {} and removed {} shims in {:?}", created_shims.len(), removed_shims.len(), jvms_installation.get_installation_path());
+            },
+
+            //
+            // Reinstall shims subcommand
+            //
+
+            JvmsCommand::ReinstallShims(cmd) => {
+                let link_mode = match cmd.link_mode {
+                    Some(link_mode) => link_mode,
+                    None => JvmsInstallation::get_default_link_mode()?
+                };
+
+                let shim_count = jvms_installation.reinstall_shims(link_mode)?;
+                println!("Reinstalled {} shims in {:?}", shim_count, jvms_installation.get_installation_path());
+            },
+
+            //
+            // Run subcommand
+            //
+
+            JvmsCommand::Run(mut cmd) => {
+                //
+                // With --toolchain, the first argument is the command rather than the toolchain.
+                //
+
+                let requested_toolchain = match (cmd.toolchain_option.take(), cmd.toolchain.take()) {
+                    (Some(toolchain_option), Some(program)) => {
+                        cmd.command.insert(0, OsString::from(program));
+                        toolchain_option
+                    },
+                    (Some(toolchain_option), None) => toolchain_option,
+                    (None, Some(toolchain)) => toolchain,
+                    (None, None) => {
+                        return Err(JvmsError::InvalidArguments("No toolchain was provided, pass it as the first argument or with --toolchain.".to_owned()));
+                    }
+                };
+
+                if cmd.command.is_empty() {
+                    return Err(JvmsError::InvalidArguments(format!("No command was provided to run with {}.", requested_toolchain)));
+                }
+
+                let config = jvms_config?;
+                let (toolchain_name, toolchain) = config.select_toolchain(&requested_toolchain)
+                    .ok_or_else(|| config.unknown_toolchain_error(requested_toolchain.clone()))?;
+
+                if !cmd.quiet && toolchain_name != requested_toolchain {
+                    eprintln!("Selected toolchain {} for {}", toolchain_name, requested_toolchain);
+                }
+
+                //
+                // When invoked from the shebang line of a script, the kernel appends the script path and its arguments.
+                // Running the script again would loop back into jvms, so it is run as a java source file instead.
+                //
+
+                if let Some(script_index) = Jvms::find_jvms_script(&cmd.command) {
+                    let script = &cmd.command[script_index];
+                    let major_version = toolchain.get_java_version()
+                        .ok_or_else(|| JvmsError::InvalidToolchain(format!("Cannot run {:?}, the java version of {} is unknown.", script, toolchain_name)))?
+                        .major();
+
+                    if major_version < SOURCE_LAUNCHER_MIN_VERSION {
+                        return Err(JvmsError::InvalidToolchain(format!("Cannot run {:?} with {}, running java source files needs java {} or later.", script, toolchain_name, SOURCE_LAUNCHER_MIN_VERSION)));
+                    }
+
+                    let args = Jvms::get_source_file_args(&cmd.command, script_index, major_version);
+                    return Jvms::exec_with_toolchain(&config, toolchain, OsStr::new("java"), &args, true, cmd.dedupe_path);
+
+                } else {
+                    return Jvms::exec_with_toolchain(&config, toolchain, &cmd.command[0], &cmd.command[1..], true, cmd.dedupe_path);
+                }
+            },
+
+            //
+            // Shell subcommand
+            //
+
+            JvmsCommand::Shell(cmd) => {
+                let config = jvms_config?;
+                let (toolchain_name, toolchain) = config.select_toolchain(&cmd.toolchain_name)
+                    .ok_or_else(|| config.unknown_toolchain_error(cmd.toolchain_name.clone()))?;
+
+                println!("Starting shell with toolchain {}, exit the shell to return.", toolchain_name);
+
+                //
+                // Shims started from the new shell use the toolchain too, regardless of overrides and project files.
+                //
+
+                env::set_var(config::TOOLCHAIN_ENV, toolchain_name);
+                return Jvms::exec_with_toolchain(&config, toolchain, Jvms::get_user_shell().as_os_str(), &[], true, cmd.dedupe_path);
+            },
+
+            //
+            // Status subcommand
+            //
+
+            JvmsCommand::Status(cmd) => {
+                let status = Jvms::check_status(jvms_config, search_root.as_deref(), !cmd.check || cmd.verbose)?;
+                if cmd.check {
+                    return Ok(status);
+                }
+            },
+
+            //
+            // Toolchain subcommands
+            //
+
+            JvmsCommand::Toolchain(ToolchainCommand::Add(cmd)) if cmd.validate_only => {
+                //
+                // No name is needed to validate a java home, so a single argument is the java home.
+                //
+
+                let java_home = match (cmd.toolchain_name, cmd.java_home) {
+                    (_, Some(java_home)) => java_home,
+                    (Some(java_home), None) => PathBuf::from(java_home),
+                    (None, None) => return Err(Jvms::missing_toolchain_add_arguments(false))
+                };
+
+                let jdk_info = JdkInfo::inspect(&java_home)?;
+                println!("{:?} is a valid java home:", java_home);
+                println!("  - Version: {}", jdk_info.version.as_deref().unwrap_or("Unknown"));
+                println!("  - Vendor: {}", jdk_info.vendor.as_deref().unwrap_or("Unknown"));
+                println!("  - Architecture: {}", jdk_info.arch.as_deref().unwrap_or("Unknown"));
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::Add(cmd)) => {
+                let mut config = jvms_config?;
+                let (toolchain_name, java_home) = match (cmd.toolchain_name, cmd.java_home) {
+                    (Some(toolchain_name), Some(java_home)) => (toolchain_name, java_home),
+                    (None, None) if io::stdin().is_terminal() => {
+                        match Jvms::prompt_toolchain_add(&config)? {
+                            Some(arguments) => arguments,
+                            None => return Ok(0)
+                        }
+                    },
+                    (toolchain_name, _) => return Err(Jvms::missing_toolchain_add_arguments(toolchain_name.is_none()))
+                };
+
+                if config.has_toolchain(&toolchain_name) {
+                    return Err(JvmsError::ToolchainExists(toolchain_name));
+
+                } else {
+                    //
+                    // A copied home is registered at its own path, so only homes registered in place can collide.
+                    //
+
+                    let existing_name = if cmd.copy_into { None } else { config.find_toolchain_by_home(&java_home) };
+                    if let Some(existing_name) = existing_name {
+                        if cmd.check_duplicate_home {
+                            return Err(JvmsError::DuplicateJavaHome { path: java_home, toolchain: existing_name.to_owned() });
+                        }
+
+                        eprintln!("Warning: JAVA_HOME {:?} is already registered as {}", java_home, existing_name);
+                    }
+
+                    for (index, alias) in cmd.aliases.iter().enumerate() {
+                        let conflict = if *alias == toolchain_name || cmd.aliases[..index].contains(alias) {
+                            Some("is given more than once")
+
+                        } else if config.get_alias_target(alias).is_some() {
+                            Some("is already an alias")
+
+                        } else if config.has_toolchain(alias) {
+                            Some("is already a toolchain")
+
+                        } else {
+                            None
+                        };
+
+                        if let Some(conflict) = conflict {
+                            return Err(JvmsError::InvalidArguments(format!("Cannot create alias {}, the name {}.", alias, conflict)));
+                        }
+                    }
+
+                    if cmd.default_if_none && config.get_default_toolchain_name().is_none() {
+                        println!("Setting default installation to {}", toolchain_name);
+                        config.set_default_toolchain_name(toolchain_name.clone());
+                    }
+
+                    //
+                    // Everything that can fail runs before the java home is copied, so that only a failed save can
+                    // leave a copy behind, which is then removed.
+                    //
+
+                    let arch = if cmd.probe_arch {
+                        let arch = jdk::probe_arch(&java_home)?;
+                        println!("Detected architecture {}", arch);
+                        Some(arch)
+
+                    } else {
+                        None
+                    };
+
+                    let copied_home = if cmd.copy_into {
+                        Some(jvms_installation.copy_toolchain(&toolchain_name, &java_home)?)
+
+                    } else {
+                        None
+                    };
+
+                    let mut toolchain = JavaToolchain::new(copied_home.clone().unwrap_or(java_home));
+                    toolchain.managed = copied_home.as_ref().map(|_| true);
+                    if cmd.allow_missing && !toolchain.java_home.exists() {
+                        eprintln!("JAVA_HOME {:?} does not exist, registering {} as unverified", toolchain.java_home, toolchain_name);
+                        toolchain.verified = Some(false);
+                    }
+
+                    for tag in &cmd.tags {
+                        toolchain.add_tag(tag);
+                    }
+
+                    toolchain.arch = arch;
+                    toolchain.record_metadata();
+                    match jdk::detect_version(&toolchain.java_home, cmd.probe_version) {
+                        Some((_, VersionSource::ReleaseFile)) => {},
+                        Some((version, source)) => {
+                            let source = match source {
+                                VersionSource::VersionFile => "the version file",
+                                VersionSource::DirectoryName => "the directory name",
+                                _ => "java -version"
+                            };
+
+                            println!("Detected version {} from {}", version, source);
+                            toolchain.version = Some(version);
+                        },
+                        None if toolchain.java_home.exists() => {
+                            eprintln!("Warning: Could not detect the java version of {}", toolchain_name);
+                        },
+                        None => {}
+                    }
+
+                    if cmd.set_default_version {
+                        match toolchain.get_java_version() {
+                            Some(version) => {
+                                println!("Setting default for java {} to {}", version.major(), toolchain_name);
+                                config.set_version_default(version.major(), toolchain_name.clone());
+                            },
+                            None => eprintln!("Warning: Could not detect the java version of {}, not setting it as a version default", toolchain_name)
+                        }
+                    }
+
+                    for alias in cmd.aliases {
+                        println!("Adding alias {} for {}", alias, toolchain_name);
+                        config.add_alias(alias, toolchain_name.clone());
+                    }
+
+                    config.add_toolchain(toolchain_name, toolchain);
+                    if let Err(error) = jvms_installation.save_configuration(&config, cmd.force) {
+                        if let Some(copied_home) = copied_home {
+                            let _ = fs::remove_dir_all(copied_home);
+                        }
+
+                        return Err(error);
+                    }
+                }
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::Discover(cmd)) => {
+                let mut config = jvms_config?;
+                let filter = DiscoveryFilter { include: cmd.include, exclude: cmd.exclude };
+
+                let mut registered_count = 0;
+                for search_path in &cmd.paths {
+                    for discovered in discovery::discover(search_path, cmd.recursive, &filter)? {
+                        let toolchain_name = discovered.java_home.file_name().map(|name| name.to_string_lossy().into_owned());
+                        if Jvms::register_discovered(&mut config, discovered, toolchain_name, cmd.dry_run) {
+                            registered_count += 1;
+                        }
+                    }
+                }
+
+                if registered_count != 0 {
+                    jvms_installation.save_configuration(&config, cmd.force)?;
+                }
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::Scan(cmd)) => {
+                let mut config = jvms_config?;
+                let filter = DiscoveryFilter { include: Vec::new(), exclude: cmd.exclude };
+
+                //
+                // Locations that can't be read, such as directories of other users, are skipped rather than failing
+                // the whole scan.
+                //
+
+                let mut discovered_jdks = Vec::new();
+                for location in discovery::get_well_known_locations() {
+                    match discovery::discover(&location, false, &filter) {
+                        Ok(location_jdks) => discovered_jdks.extend(location_jdks),
+                        Err(error) => eprintln!("Warning: Failed to scan {:?}: {}", location, error)
+                    }
+                }
+
+                //
+                // JDKs listed by macOS's java_home are usually in the locations above already, possibly through a
+                // symlink, so they are only added when their resolved java home wasn't found there.
+                //
+
+                let canonical_homes: Vec<PathBuf> = discovered_jdks.iter()
+                    .filter_map(|discovered| fs::canonicalize(&discovered.java_home).ok())
+                    .collect();
+                for discovered in discovery::discover_java_home(&filter) {
+                    let canonical_home = fs::canonicalize(&discovered.java_home).unwrap_or_else(|_| discovered.java_home.clone());
+                    if !canonical_homes.contains(&canonical_home) {
+                        discovered_jdks.push(discovered);
+                    }
+                }
+
+                let mut registered_count = 0;
+                for discovered in discovered_jdks {
+                    let toolchain_name = discovery::generate_toolchain_name(&discovered.info);
+                    if Jvms::register_discovered(&mut config, discovered, toolchain_name, cmd.dry_run) {
+                        registered_count += 1;
+                    }
+                }
+
+                if registered_count != 0 {
+                    jvms_installation.save_configuration(&config, cmd.force)?;
+                }
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::ExportPath(cmd)) => {
+                let config = jvms_config?;
+                let current_dir = env::current_dir()?;
+                let resolved = config.resolve_home_with_toolchain(&current_dir, search_root.as_deref(), cmd.toolchain_name.as_deref())?;
+                let bin_path = resolved.java_home.join("bin");
+
+                if !bin_path.is_dir() {
+                    return Err(JvmsError::ToolchainHomeMissing { name: resolved.get_display_name(), path: bin_path });
+                }
+
+                println!("{}", bin_path.display());
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::Install(cmd)) => {
+                let mut config = jvms_config?;
+                let major_version: u32 = cmd.spec
+                    .strip_prefix("temurin-")
+                    .unwrap_or(&cmd.spec)
+                    .parse()
+                    .map_err(|_| JvmsError::InvalidToolchain(format!("{} is not a major java version.", cmd.spec)))?;
+
+                if offline {
+                    return Err(JvmsError::NetworkError("Cannot resolve a release to install while offline.".to_owned()));
+                }
+
+                let arch = cmd.arch.as_deref().unwrap_or_else(|| adoptium::get_current_arch());
+                let release = adoptium::resolve_release(major_version, arch, config.get_mirror(adoptium::PROVIDER_NAME))?
+                    .ok_or_else(|| JvmsError::InvalidToolchain(format!("No Adoptium release found for java {} on {}.", major_version, arch)))?;
+
+                let toolchain_name = cmd.name.unwrap_or_else(|| format!("temurin-{}", release.version));
+                if config.has_toolchain(&toolchain_name) {
+                    return Err(JvmsError::ToolchainExists(toolchain_name));
+                }
+
+                //
+                // Resolving the release is separate from fetching it so that the plan can be shown on its own.
+                //
+
+                println!("Release: {}", release.release_name);
+                println!("  - Toolchain name: {}", toolchain_name);
+                println!("  - Archive: {}", release.file_name);
+                println!("  - URL: {}", release.url);
+                println!("  - Size: {:.1} MiB", release.size as f64 / (1024.0 * 1024.0));
+                println!("  - SHA-256: {}", release.checksum.as_deref().unwrap_or("Unknown"));
+                println!("  - Signature: {}", release.signature_url.as_deref().unwrap_or("None"));
+                println!("  - Install directory: {:?}", jvms_installation.get_toolchains_path().join(&toolchain_name));
+
+                if cmd.dry_run {
+                    return Ok(0);
+                }
+
+                let download_path = jvms_installation.get_cache_path()
+                    .map(|cache_path| cache_path.join("downloads"))
+                    .unwrap_or_else(|| jvms_installation.get_toolchains_path());
+                fs::create_dir_all(&download_path)?;
+
+                let archive_path = download_path.join(&release.file_name);
+                println!("Downloading {}", release.url);
+                download::download(&release.url, &archive_path, release.size)?;
+
+                let verified = if cmd.no_verify {
+                    eprintln!("Warning: Skipping verification of {} as --no-verify was given", release.file_name);
+                    Ok(())
+
+                } else {
+                    Jvms::verify_release(&release, &archive_path)
+                };
+
+                let installed_path = verified.and_then(|_| jvms_installation.extract_toolchain(&toolchain_name, &archive_path));
+                let _ = fs::remove_file(&archive_path);
+                let installed_path = installed_path?;
+
+                //
+                // The java home may be nested inside the archive, e.g. under Contents/Home in macOS archives.
+                //
+
+                let java_home = discovery::discover(&installed_path, true, &DiscoveryFilter::default())?
+                    .into_iter()
+                    .next()
+                    .map(|discovered| discovered.java_home);
+                let java_home = match java_home {
+                    Some(java_home) => java_home,
+                    None => {
+                        let _ = fs::remove_dir_all(&installed_path);
+                        return Err(JvmsError::InvalidToolchain(format!("No JDK found in {}.", release.file_name)));
+                    }
+                };
+
+                println!("Registering {} at {:?}", toolchain_name, java_home);
+                let mut toolchain = JavaToolchain::new(java_home);
+                toolchain.managed = Some(true);
+                toolchain.record_metadata();
+                config.add_toolchain(toolchain_name, toolchain);
+                if let Err(error) = jvms_installation.save_configuration(&config, false) {
+                    let _ = fs::remove_dir_all(&installed_path);
+                    return Err(error);
+                }
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::List(cmd)) => {
+                let config = jvms_config?;
+                let name_filter = cmd.filter.as_deref().map(|filter| {
+                    if filter.contains('*') || filter.contains('?') {
+                        ToolchainSelector::Glob(filter.to_owned())
+                    } else {
+                        ToolchainSelector::Glob(format!("*{}*", filter))
+                    }
+                });
+                let vendor_filter = cmd.vendor.as_ref().map(|vendor| vendor.to_lowercase());
+
+                let mut toolchains: Vec<_> = config.get_toolchains()
+                    .filter(|(name, _)| name_filter.as_ref().map(|filter| filter.matches(name, None)).unwrap_or(true))
+                    .filter(|(_, toolchain)| cmd.tag.as_ref().map(|tag| toolchain.has_tag(tag)).unwrap_or(true))
+                    .filter(|(_, toolchain)| {
+                        if !cmd.missing_only && !cmd.present_only {
+                            return true;
+                        }
+
+                        let present = toolchain.java_home.is_dir() && jdk::get_tool_path(&toolchain.java_home, "java").is_file();
+                        present == cmd.present_only
+                    })
+                    .map(|(name, toolchain)| (name, toolchain, toolchain.read_jdk_info()))
+                    .filter(|(_, _, jdk_info)| {
+                        vendor_filter.as_ref().map(|vendor_filter| {
+                            jdk_info.as_ref()
+                                .and_then(|info| info.vendor.as_ref())
+                                .map(|vendor| vendor.to_lowercase().contains(vendor_filter.as_str()))
+                                .unwrap_or(false)
+                        }).unwrap_or(true)
+                    })
+                    .filter(|(_, _, jdk_info)| {
+                        cmd.major.map(|major| {
+                            jdk_info.as_ref()
+                                .and_then(|info| info.get_java_version())
+                                .map(|version| version.major() == major)
+                                .unwrap_or(false)
+                        }).unwrap_or(true)
+                    })
+                    .collect();
+
+                //
+                // Sort by name first so that entries with equal or missing sort keys keep a deterministic order.
+                //
+
+                toolchains.sort_by(|a, b| a.0.cmp(b.0));
+                match cmd.sort {
+                    ToolchainSortKey::Name => {
+                        if cmd.reverse {
+                            toolchains.reverse();
+                        }
+                    },
+                    ToolchainSortKey::Version => {
+                        toolchains.sort_by(|a, b| {
+                            let a_version = a.2.as_ref().and_then(|info| info.get_java_version());
+                            let b_version = b.2.as_ref().and_then(|info| info.get_java_version());
+                            Jvms::compare_sort_keys(a_version, b_version, cmd.reverse)
+                        });
+                    },
+                    ToolchainSortKey::Vendor => {
+                        toolchains.sort_by(|a, b| {
+                            let a_vendor = a.2.as_ref().and_then(|info| info.vendor.as_ref());
+                            let b_vendor = b.2.as_ref().and_then(|info| info.vendor.as_ref());
+                            Jvms::compare_sort_keys(a_vendor, b_vendor, cmd.reverse)
+                        });
+                    }
+                }
+
+                if let Some(limit) = cmd.limit {
+                    toolchains.truncate(limit);
+                }
+
+                if cmd.fail_if_empty && toolchains.is_empty() {
+                    if cmd.count {
+                        println!("0");
+                    } else {
+                        println!("Available toolchains:");
+                    }
+
+                    return Ok(error::EXIT_FAILURE);
+                }
+
+                if cmd.count {
+                    println!("{}", toolchains.len());
+                    return Ok(0);
+                }
+
+                if json {
+                    let toolchains_json: Vec<_> = toolchains.iter()
+                        .map(|(name, toolchain, jdk_info)| serde_json::json!({
+                            "name": name,
+                            "java_home": toolchain.java_home,
+                            "version": jdk_info.as_ref().and_then(|info| info.version.as_ref()),
+                            "vendor": jdk_info.as_ref().and_then(|info| info.vendor.as_ref()),
+                            "arch": toolchain.arch.as_ref().or_else(|| jdk_info.as_ref().and_then(|info| info.arch.as_ref())),
+                            "tags": toolchain.tags.as_deref().unwrap_or_default(),
+                            "managed": toolchain.is_managed()
+                        }))
+                        .collect();
+
+                    Jvms::print_json(&toolchains_json)?;
+                    return Ok(0);
+                }
+
+                if cmd.names {
+                    for (name, _, _) in toolchains {
+                        println!("{}", name);
+                    }
+
+                    return Ok(0);
+                }
+
+                if cmd.print0 {
+                    for (name, toolchain, jdk_info) in toolchains {
+                        let field = |value: Option<&String>| value.map_or_else(OsString::new, OsString::from);
+                        let version = field(jdk_info.as_ref().and_then(|info| info.version.as_ref()));
+                        let vendor = field(jdk_info.as_ref().and_then(|info| info.vendor.as_ref()));
+                        let arch = field(toolchain.arch.as_ref().or_else(|| jdk_info.as_ref().and_then(|info| info.arch.as_ref())));
+                        Jvms::print_record0(&[OsStr::new(name), toolchain.java_home.as_os_str(), &version, &vendor, &arch])?;
+                    }
+
+                    return Ok(0);
+                }
+
+                println!("Available toolchains:");
+                for (name, toolchain, jdk_info) in toolchains {
+                    println!("  - {}:", name);
+                    println!("    - JAVA_HOME = {:?}", toolchain.java_home);
+                    if let Some(version) = jdk_info.as_ref().and_then(|info| info.version.as_ref()) {
+                        println!("    - Version = {}", version);
+                    }
+
+                    if let Some(vendor) = jdk_info.as_ref().and_then(|info| info.vendor.as_ref()) {
+                        println!("    - Vendor = {}", vendor);
+                    }
+
+                    if let Some(arch) = toolchain.arch.as_ref().or_else(|| jdk_info.as_ref().and_then(|info| info.arch.as_ref())) {
+                        println!("    - Architecture = {}", arch);
+                    }
+
+                    if let Some(tags) = toolchain.tags.as_ref() {
+                        println!("    - Tags = {}", tags.join(", "));
+                    }
+
+                    let aliases = config.get_aliases_for(name);
+                    if !aliases.is_empty() {
+                        println!("    - Aliases = {}", aliases.join(", "));
+                    }
+                }
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::Outdated(_)) => {
+                let config = jvms_config?;
+                if offline {
+                    return Err(JvmsError::NetworkError("Cannot check for outdated toolchains while offline.".to_owned()));
+                }
+
+                //
+                // A failed lookup is reported for its toolchain only, so that one unreachable release does not hide
+                // the state of the others.
+                //
+
+                let mut toolchains: Vec<_> = config.get_toolchains().collect();
+                toolchains.sort_by(|a, b| a.0.cmp(b.0));
+
+                let mut any_outdated = false;
+                let mut any_failed = false;
+                for (name, toolchain) in toolchains {
+                    let jdk_info = JdkInfo::read(&toolchain.java_home).ok();
+                    let is_adoptium = jdk_info.as_ref()
+                        .and_then(|info| info.vendor.as_deref())
+                        .map(adoptium::is_adoptium_vendor)
+                        .unwrap_or(false);
+
+                    let current_version = jdk_info.as_ref().and_then(|info| info.get_java_version());
+                    let current_version = match current_version {
+                        Some(current_version) if is_adoptium => current_version,
+                        _ => {
+                            println!("  - {}: Skipped, not a recognized Adoptium toolchain.", name);
+                            continue;
+                        }
+                    };
+
+                    let display_version = jdk_info.as_ref().and_then(|info| info.version.as_deref()).unwrap_or_default();
+                    let latest_version = match adoptium::get_latest_version(current_version.major(), config.get_mirror(adoptium::PROVIDER_NAME)) {
+                        Ok(latest_version) => latest_version,
+                        Err(error) => {
+                            println!("  - {}: Failed to check for updates: {}", name, error);
+                            any_failed = true;
+                            continue;
+                        }
+                    };
+
+                    let is_outdated = latest_version.as_deref()
+                        .and_then(JavaVersion::parse)
+                        .map(|latest_version| latest_version > current_version)
+                        .unwrap_or(false);
+
+                    match latest_version {
+                        Some(latest_version) if is_outdated => {
+                            println!("  - {}: Outdated, {} is installed but {} is available.", name, display_version, latest_version);
+                            any_outdated = true;
+                        },
+                        Some(_) => {
+                            println!("  - {}: Up to date at {}.", name, display_version);
+                        },
+                        None => {
+                            println!("  - {}: No releases found for major version {}.", name, current_version.major());
+                        }
+                    }
+                }
+
+                if any_outdated || any_failed {
+                    return Ok(error::EXIT_FAILURE);
+                }
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::Remove(cmd)) => {
+                let mut config = jvms_config?;
+                let removed_toolchains = if let Some(tag) = cmd.tag.as_ref() {
+                    let mut toolchain_names: Vec<String> = config.get_toolchains()
+                        .filter(|(_, toolchain)| toolchain.has_tag(tag))
+                        .map(|(name, _)| name.clone())
+                        .collect();
+                    toolchain_names.sort();
+
+                    if toolchain_names.is_empty() {
+                        eprintln!("No toolchains are tagged {}", tag);
+                        return Ok(0);
+                    }
+
+                    if !cmd.yes {
+                        eprintln!("The following toolchains are tagged {}, run with --yes to remove them:", tag);
+                        for name in &toolchain_names {
+                            eprintln!("  - {}", name);
+                        }
+
+                        return Ok(error::EXIT_FAILURE);
+                    }
+
+                    //
+                    // Nothing is saved if any of the toolchains is refused, so the removal is all or nothing.
+                    //
+
+                    let mut removed_toolchains = Vec::new();
+                    for name in toolchain_names {
+                        let (toolchain, references) = config.remove_toolchain_cascading(&name, cmd.cascade)?;
+                        for reference in references {
+                            println!("Removed {}", reference.get_description());
+                        }
+
+                        removed_toolchains.push((name, toolchain));
+                    }
+
+                    removed_toolchains
+
+                } else {
+                    let toolchain_name = cmd.toolchain_name.unwrap();
+                    let (toolchain, references) = config.remove_toolchain_cascading(&toolchain_name, cmd.cascade)?;
+                    for reference in references {
+                        println!("Removed {}", reference.get_description());
+                    }
+
+                    vec![(toolchain_name, toolchain)]
+                };
+
+                for (name, _) in &removed_toolchains {
+                    for alias in config.remove_aliases_for(name) {
+                        println!("Removed alias {} -> {}", alias, name);
+                    }
+                }
+
+                jvms_installation.save_configuration(&config, cmd.force)?;
+
+                //
+                // A managed directory is only deleted once no remaining toolchain points into it.
+                //
+
+                let unreferenced_dirs = if cmd.purge {
+                    jvms_installation.find_unreferenced_toolchain_dirs(&config)?
+                } else {
+                    Vec::new()
+                };
+
+                for (name, toolchain) in removed_toolchains {
+                    println!("Removed toolchain {}", name);
+                    if !toolchain.is_managed() {
+                        continue;
+                    }
+
+                    match jvms_installation.get_managed_toolchain_dir(&toolchain.java_home) {
+                        Some(toolchain_dir) if unreferenced_dirs.contains(&toolchain_dir) => {
+                            println!("Deleting managed java home {:?}", toolchain_dir);
+                            fs::remove_dir_all(&toolchain_dir)?;
+                        },
+                        Some(toolchain_dir) if cmd.purge => println!("Keeping {:?}, another toolchain still uses it", toolchain_dir),
+                        Some(toolchain_dir) => println!("Kept managed java home {:?}, pass --purge or run jvms gc to delete it", toolchain_dir),
+                        None => println!("Kept {:?}, it is not stored in {:?}", toolchain.java_home, jvms_installation.get_toolchains_path())
+                    }
+                }
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::Rename(cmd)) => {
+                let mut config = jvms_config?;
+
+                //
+                // Project files live in the repositories themselves, so only those in override directories are known.
+                //
+
+                let mut project_files = Vec::new();
+                if cmd.update_project_files {
+                    for o in config.get_overrides().unwrap_or(&[]) {
+                        for file_name in &[config::JAVA_VERSION_FILE_NAME, config::PROJECT_RC_FILE_NAME, config::TOOL_VERSIONS_FILE_NAME] {
+                            let path = o.path.join(file_name);
+                            if let Some(contents) = config::rename_in_project_file(&path, &cmd.old_name, &cmd.new_name)? {
+                                project_files.push((path, contents));
+                            }
+                        }
+                    }
+                }
+
+                config.rename_toolchain(&cmd.old_name, &cmd.new_name)?;
+                jvms_installation.save_configuration(&config, cmd.force)?;
+                println!("Renamed toolchain {} to {}", cmd.old_name, cmd.new_name);
+
+                if cmd.update_project_files && project_files.is_empty() {
+                    println!("No project files in override directories reference {}", cmd.old_name);
+
+                } else if !project_files.is_empty() {
+                    println!("The following project files reference {}:", cmd.old_name);
+                    for (path, _) in &project_files {
+                        println!("  - {:?}", path);
+                    }
+
+                    if Jvms::confirm(&format!("Update them to reference {}?", cmd.new_name), cmd.yes)? {
+                        for (path, contents) in project_files {
+                            fs::write(&path, contents)?;
+                            println!("Updated {:?}", path);
+                        }
+
+                    } else {
+                        println!("Project files were left unchanged");
+                    }
+                }
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::Tag(cmd)) => {
+                let mut config = jvms_config?;
+                match config.get_toolchain_mut(&cmd.toolchain_name) {
+                    Some(toolchain) => {
+                        for tag in &cmd.tags {
+                            if toolchain.add_tag(tag) {
+                                println!("Tagged {} with {}", cmd.toolchain_name, tag);
+                            }
+                        }
+                    },
+                    None => return Err(config.unknown_toolchain_error(cmd.toolchain_name))
+                }
+
+                jvms_installation.save_configuration(&config, cmd.force)?;
+            },
+            JvmsCommand::Toolchain(ToolchainCommand::Untag(cmd)) => {
+                let mut config = jvms_config?;
+                match config.get_toolchain_mut(&cmd.toolchain_name) {
+                    Some(toolchain) => {
+                        for tag in &cmd.tags {
+                            if toolchain.remove_tag(tag) {
+                                println!("Removed tag {} from {}", tag, cmd.toolchain_name);
+                            }
+                        }
+                    },
+                    None => return Err(config.unknown_toolchain_error(cmd.toolchain_name))
+                }
+
+                jvms_installation.save_configuration(&config, cmd.force)?;
+            },
+
+            //
+            // Ui subcommand
+            //
+
+            JvmsCommand::Ui(_) => {
+                if !io::stdin().is_terminal() {
+                    return Err(JvmsError::InvalidArguments("jvms ui needs an interactive terminal, use the default, override, and toolchain subcommands in scripts.".to_owned()));
+                }
+
+                jvms_config?;
+                Jvms::run_ui(jvms_installation, offline, search_root)?;
+            },
+
+            //
+            // Uninstall subcommand
+            //
+
+            JvmsCommand::Uninstall(cmd) => {
+                let other_installation;
+                let installation = match cmd.installation_path {
+                    Some(installation_path) => {
+                        other_installation = JvmsInstallation::new(installation_path);
+                        &other_installation
+                    },
+                    None => jvms_installation
+                };
+
+                installation.uninstall(cmd.remove_config)?;
+                Jvms::remove_from_path(&config::make_absolute(installation.get_installation_path()))?;
+
+                println!("Finished uninstalling jvms from {:?}", installation.get_installation_path());
+            },
+
+            //
+            // Use subcommand
+            //
+
+            JvmsCommand::Use(cmd) => {
+                let shell = cmd.shell.unwrap_or_else(Jvms::detect_shell_syntax);
+                if cmd.unset {
+                    for name in &[config::TOOLCHAIN_ENV, "JAVA_HOME"] {
+                        println!("{}", shell.format_unset(name));
+                    }
+
+                    return Ok(0);
+                }
+
+                let spec = match cmd.toolchain {
+                    Some(spec) => spec,
+                    None => return Err(JvmsError::InvalidArguments("A toolchain must be provided unless --unset is used.".to_owned()))
+                };
+
+                let config = jvms_config?;
+                let toolchain_name = config.select_toolchain(&spec)
+                    .map(|(toolchain_name, _)| toolchain_name)
+                    .ok_or_else(|| config.unknown_toolchain_error(spec.clone()))?;
+
+                let current_dir = env::current_dir()?;
+                let resolved = config.resolve_home_with_toolchain(&current_dir, search_root.as_deref(), Some(toolchain_name))?;
+                let java_home = resolved.java_home.to_string_lossy();
+
+                for (name, value) in &[(config::TOOLCHAIN_ENV, toolchain_name), ("JAVA_HOME", &*java_home)] {
+                    println!("{}", shell.format_set(name, value));
+                }
+
+                if io::stdout().is_terminal() {
+                    let apply_command = shell.format_apply(&format!("jvms use {}", spec));
+                    eprintln!("Run `{}` to switch the toolchain of the current shell.", apply_command);
+                }
+            },
+
+            //
+            // Which subcommand
+            //
+
+            JvmsCommand::Which(cmd) => {
+                let config = jvms_config?;
+                let current_dir = env::current_dir()?;
+                let resolved = config.resolve_toolchain_with_source(&current_dir, search_root.as_deref())?
+                    .ok_or_else(|| JvmsError::NoToolchainResolved { path: current_dir.clone() })?;
+                let toolchain = &resolved.toolchain;
+
+                //
+                // Besides the always installed shims, list the tools of every toolchain that `jvms rehash` shims.
+                //
+
+                let shims = if cmd.all { Shim::get_toolchain_shims(&config) } else { Vec::new() };
+                let tool_names: Vec<&str> = if cmd.all {
+                    shims.iter().map(Shim::name).collect()
+                } else {
+                    vec![cmd.tool.as_str()]
+                };
+
+                let tools: Vec<_> = tool_names
+                    .into_iter()
+                    .map(|tool_name| {
+                        let tool_path = jdk::get_tool_path(&toolchain.java_home, tool_name);
+                        let exists = tool_path.is_file();
+                        (tool_name, tool_path, exists)
+                    })
+                    .collect();
+
+                if json {
+                    let tools_json: Vec<_> = tools.iter()
+                        .map(|(tool_name, tool_path, exists)| serde_json::json!({
+                            "tool": tool_name,
+                            "path": tool_path,
+                            "exists": exists
+                        }))
+                        .collect();
+
+                    Jvms::print_json(&serde_json::json!({
+                        "toolchain": resolved.name,
+                        "tools": tools_json
+                    }))?;
+
+                } else if cmd.print0 {
+                    for (tool_name, tool_path, exists) in &tools {
+                        let status = if *exists { "present" } else { "missing" };
+                        Jvms::print_record0(&[OsStr::new(tool_name), tool_path.as_os_str(), OsStr::new(status)])?;
+                    }
+
+                } else if cmd.all {
+                    println!("Tools of toolchain {}:", resolved.get_display_name());
+                    for (tool_name, tool_path, exists) in &tools {
+                        if *exists {
+                            println!("  - {} = {:?}", tool_name, tool_path);
+                        } else {
+                            println!("  - {} = {:?} (missing)", tool_name, tool_path);
+                        }
+                    }
+
+                } else {
+                    for (_, tool_path, _) in &tools {
+                        println!("{}", tool_path.display());
+                    }
+                }
+
+                if tools.iter().any(|(_, _, exists)| !exists) {
+                    return Ok(error::EXIT_FAILURE);
+                }
+            }
+        }
+
+        Ok(0)
+    }
+
+    fn compare_sort_keys<T: Ord>(a: Option<T>, b: Option<T>, reverse: bool) -> Ordering {
+        match (a, b) {
+            (Some(a), Some(b)) if reverse => b.cmp(&a),
+            (Some(a), Some(b)) => a.cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal
+        }
+    }
+
+    ///
+    /// Executes the program with the environment of the toolchain, inheriting stdio.
+    ///
+    /// On Unix the current process is replaced by the program so that signals and exit codes behave as if it was
+    /// run directly, and this only returns if the program could not be started. Elsewhere the program is waited on
+    /// and its exit code returned, for the current process to exit with.
+    ///
+    fn exec_with_toolchain(config: &JvmsConfiguration, toolchain: &JavaToolchain, program: &OsStr, args: &[OsString], prepend_bin: bool, dedupe_path: Option<bool>) -> Result<i32> {
+        let mut command = process::Command::new(program);
+        command.args(args);
+        let dedupe_path = dedupe_path.unwrap_or_else(|| config.is_dedupe_path());
+        jdk::apply_environment(&toolchain.java_home, &mut command, prepend_bin, dedupe_path)?;
+        debug!("Running {:?}", command);
+
+        #[cfg(unix)]
+        let error = {
+            use std::os::unix::process::CommandExt;
+            command.exec()
+        };
+
+        #[cfg(not(unix))]
+        let error = match command.status() {
+            Ok(status) => return Ok(status.code().unwrap_or(error::EXIT_FAILURE)),
+            Err(error) => error
+        };
+
+        Err(JvmsError::ProcessLaunch { program: PathBuf::from(program), error })
+    }
+
+    ///
+    /// Verifies a downloaded release against the SHA-256 checksum published for it, failing if there is none, and
+    /// against its GPG signature when one is published and gpg can check it.
+    ///
+    fn verify_release(release: &AdoptiumRelease, archive_path: &Path) -> Result<()> {
+        let checksum = release.checksum.as_deref()
+            .ok_or_else(|| {
+                JvmsError::InvalidToolchain(format!("No checksum was published for {}, pass --no-verify to install it anyway.", release.file_name))
+            })?;
+
+        download::verify_sha256(archive_path, checksum)?;
+        println!("Verified SHA-256 checksum");
+
+        let signature_url = match release.signature_url.as_deref() {
+            Some(signature_url) => signature_url,
+            None => return Ok(())
+        };
+
+        let signature_path = archive_path.with_file_name(format!("{}.sig", release.file_name));
+        let status = download::fetch(signature_url)
+            .and_then(|signature| Ok(fs::write(&signature_path, signature)?))
+            .and_then(|_| download::verify_gpg_signature(archive_path, &signature_path));
+        let _ = fs::remove_file(&signature_path);
+
+        match status {
+            Ok(SignatureStatus::Valid) => println!("Verified GPG signature"),
+            Ok(SignatureStatus::Unchecked(reason)) => eprintln!("Warning: Could not check the GPG signature of {}: {}", release.file_name, reason),
+            Err(JvmsError::NetworkError(reason)) => eprintln!("Warning: Could not check the GPG signature of {}: {}", release.file_name, reason),
+            Err(error) => return Err(error)
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Registers a discovered JDK under the provided name unless it or the name is already registered, or only prints
+    /// it when dry_run is set. Returns whether the configuration was changed.
+    ///
+    fn register_discovered(config: &mut JvmsConfiguration, discovered: DiscoveredJdk, toolchain_name: Option<String>, dry_run: bool) -> bool {
+        let version = discovered.info.version.as_deref().unwrap_or("Unknown version");
+        if let Some(existing_name) = config.find_toolchain_by_home(&discovered.java_home) {
+            println!("Skipping {:?}, already registered as {}", discovered.java_home, existing_name);
+            return false;
+        }
+
+        let toolchain_name = match toolchain_name {
+            Some(toolchain_name) => toolchain_name,
+            None => {
+                println!("Skipping {:?}, could not generate a name for it", discovered.java_home);
+                return false;
+            }
+        };
+
+        if config.has_toolchain(&toolchain_name) {
+            println!("Skipping {:?}, the name {} is already registered", discovered.java_home, toolchain_name);
+            return false;
+        }
+
+        if dry_run {
+            println!("Would register {} ({}) at {:?}", toolchain_name, version, discovered.java_home);
+
+        } else {
+            println!("Registering {} ({}) at {:?}", toolchain_name, version, discovered.java_home);
+        }
+
+        //
+        // Dry runs register the toolchain too so that later duplicates are reported the same way, callers only save
+        // the configuration if something was registered for real.
+        //
+
+        let mut toolchain = JavaToolchain::new(discovered.java_home);
+        toolchain.record_metadata();
+        config.add_toolchain(toolchain_name, toolchain);
+        !dry_run
+    }
+
+    ///
+    /// Writes the fields to stdout, each followed by a NUL byte. Paths are written as their raw bytes on Unix.
+    ///
+    fn print_record0(fields: &[&OsStr]) -> Result<()> {
+        let mut stdout = io::stdout().lock();
+        for field in fields {
+            #[cfg(unix)]
+            {
+                use std::os::unix::ffi::OsStrExt;
+                stdout.write_all(field.as_bytes())?;
+            }
+
+            #[cfg(not(unix))]
+            {
+                stdout.write_all(field.to_string_lossy().as_bytes())?;
+            }
+
+            stdout.write_all(b"\0")?;
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Splits the arguments of a shebang line that reached jvms as a single argument.
+    ///
+    /// The kernel passes everything after the interpreter of a shebang line as one argument, so a script starting
+    /// with `#!/usr/local/bin/jvms run 17 --` invokes jvms with "run 17 --" followed by the script path. Global
+    /// options may come first, as in `#!/usr/local/bin/jvms --config /etc/jvms.toml run 17 --`. `env -S` splits the
+    /// line itself, in which case the arguments are left alone.
+    ///
+    fn split_shebang_args(mut args: Vec<OsString>) -> Vec<OsString> {
+        let shebang_args = match args.get(1).and_then(|arg| arg.to_str()) {
+            Some(arg) if arg.split_whitespace().nth(1).is_some() && arg.split_whitespace().any(|word| word == "run") => {
+                arg.split_whitespace().map(OsString::from).collect::<Vec<_>>()
+            },
+            _ => return args
+        };
+
+        args.splice(1..2, shebang_args);
+        args
+    }
+
+    ///
+    /// Splits the java options given on the command line into arguments, honoring shell quoting.
+    ///
+    fn split_java_opts(java_opts: &str) -> Result<Vec<String>> {
+        shell_words::split(java_opts)
+            .map_err(|error| JvmsError::InvalidArguments(format!("Cannot split the java options {:?}: {}", java_opts, error)))
+    }
+
+    ///
+    /// Finds the jvms script in the command of `jvms run`, which is either the command itself or follows the java
+    /// options of its shebang line, as in `#!/usr/bin/env -S jvms run 21 -- --enable-preview`.
+    ///
+    fn find_jvms_script(command: &[OsString]) -> Option<usize> {
+        let starts_with_option = command.first()?.to_str().is_some_and(|arg| arg.starts_with('-'));
+        let candidates = if starts_with_option { command.len() } else { 1 };
+        command.iter()
+            .take(candidates)
+            .position(|arg| Jvms::is_jvms_script(Path::new(arg)))
+    }
+
+    ///
+    /// Builds the java arguments that run the jvms script at the provided index of the command as a java source file.
+    /// The source launcher only skips the shebang line with `--source`, so it is set to the major version of the
+    /// toolchain unless the java options of the shebang line set it already.
+    ///
+    fn get_source_file_args(command: &[OsString], script_index: usize, major_version: u32) -> Vec<OsString> {
+        let (java_options, script_args) = command.split_at(script_index);
+        let has_source = java_options.iter().any(|arg| arg == "--source" || arg.to_str().is_some_and(|arg| arg.starts_with("--source=")));
+
+        let mut args = Vec::new();
+        if !has_source {
+            args.push(OsString::from("--source"));
+            args.push(OsString::from(major_version.to_string()));
+        }
+
+        args.extend(java_options.iter().cloned());
+        args.extend(script_args.iter().cloned());
+        args
+    }
+
+    ///
+    /// Checks whether the file is a script whose shebang line runs it with `jvms run`.
+    ///
+    fn is_jvms_script(path: &Path) -> bool {
+        let first_line = fs::File::open(path)
+            .ok()
+            .and_then(|file| io::BufReader::new(file).lines().next())
+            .and_then(|line| line.ok());
+
+        match first_line {
+            Some(line) => {
+                let mut words = line.strip_prefix("#!").unwrap_or_default().split_whitespace();
+                words.any(|word| word == "jvms" || word.ends_with("/jvms")) && words.next() == Some("run")
+            },
+            None => false
+        }
+    }
+
+    ///
+    /// Adds the installation directory to the user PATH on Windows, or after confirmation to the shell profile
+    /// elsewhere. Nothing is changed if the directory is already on PATH.
+    ///
+    fn add_to_path(installation_path: &Path, yes: bool) -> Result<()> {
+        if user_path::is_on_path(installation_path) {
+            return Ok(());
+        }
+
+        #[cfg(target_os="windows")]
+        {
+            let _ = yes;
+            if user_path::add_to_user_path(installation_path)? {
+                println!("Added {:?} to the user PATH, it takes effect in newly opened terminals", installation_path);
+            }
+        }
+
+        #[cfg(not(target_os="windows"))]
+        {
+            if let Some(profile) = user_path::get_shell_profile() {
+                if Jvms::confirm(&format!("Add {:?} to PATH in {:?}?", installation_path, profile), yes)? {
+                    if user_path::add_to_shell_profile(&profile, installation_path)? {
+                        println!("Added {:?} to PATH in {:?}, it takes effect in newly started shells", installation_path, profile);
+                    }
+
+                    return Ok(());
+                }
+            }
+
+            println!("Add {:?} to PATH to use the shims", installation_path);
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Removes the installation directory from the user PATH on Windows, or from the shell profile it was added to
+    /// elsewhere.
+    ///
+    fn remove_from_path(installation_path: &Path) -> Result<()> {
+        #[cfg(target_os="windows")]
+        {
+            if user_path::remove_from_user_path(installation_path)? {
+                println!("Removed {:?} from the user PATH", installation_path);
+            }
+        }
+
+        #[cfg(not(target_os="windows"))]
+        {
+            if let Some(profile) = user_path::get_shell_profile() {
+                if user_path::remove_from_shell_profile(&profile, installation_path)? {
+                    println!("Removed {:?} from PATH in {:?}", installation_path, profile);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Asks the user to confirm an action, which is assumed when --yes was given and refused when not running on a
+    /// terminal.
+    ///
+    fn confirm(message: &str, yes: bool) -> Result<bool> {
+        if yes {
+            return Ok(true);
+        }
+
+        if !io::stdin().is_terminal() {
+            return Ok(false);
+        }
+
+        match Jvms::prompt(&format!("{} [y/N]: ", message))? {
+            Some(answer) => Ok(answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")),
+            None => Ok(false)
+        }
+    }
+
+    ///
+    /// Reads a line from stdin after printing the prompt, returning None at the end of input.
+    ///
+    fn prompt(message: &str) -> Result<Option<String>> {
+        print!("{}", message);
+        io::stdout().flush()?;
 
-            JvmsCommand::Default(cmd) => {
-                let mut config = jvms_config?;
-                if let Some(toolchain_name) = cmd.toolchain {
-                    if config.has_toolchain(&toolchain_name) {
-                        println!("Setting default installation to {}", toolchain_name);
-                        config.set_default_toolchain_name(toolchain_name);
-                        jvms_installation.save_configuration(&config, cmd.force)?;
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
 
-                    } else {
-                        println!("No valid toolchain found for name: {}", toolchain_name);
-                    }
+        Ok(Some(line.trim().to_owned()))
+    }
 
-                } else {
-                    println!("Default installation: {}", config.get_default_toolchain_name().unwrap_or("None"));
-                }
+    ///
+    /// Builds the usage error for `jvms toolchain add` run without its arguments outside of a terminal, in the same
+    /// form clap reports missing arguments.
+    ///
+    fn missing_toolchain_add_arguments(missing_name: bool) -> JvmsError {
+        let mut missing = Vec::new();
+        if missing_name {
+            missing.push("<toolchain-name>");
+        }
+        missing.push("<java-home>");
+
+        let usage = ToolchainAddCommand::into_app().bin_name("jvms toolchain add").generate_usage();
+        let description = format!(
+            "The following required arguments were not provided:\n    {}\n\n{}\n\nFor more information try --help",
+            missing.join("\n    "), usage
+        );
+        JvmsError::InvalidArguments(clap::Error::with_description(description, ErrorKind::MissingRequiredArgument).to_string())
+    }
+
+    ///
+    /// Interactively asks for the JAVA_HOME and name of a new toolchain, validating the JAVA_HOME and confirming
+    /// before returning. Returns None if the user cancels.
+    ///
+    fn prompt_toolchain_add(config: &JvmsConfiguration) -> Result<Option<(String, PathBuf)>> {
+        let java_home = loop {
+            let java_home = match Jvms::prompt("Path to JAVA_HOME (empty to cancel): ")? {
+                Some(java_home) if !java_home.is_empty() => PathBuf::from(java_home),
+                _ => return Ok(None)
+            };
+
+            match JdkInfo::inspect(&java_home) {
+                Ok(jdk_info) => {
+                    println!("Found a java home:");
+                    println!("  - Version: {}", jdk_info.version.as_deref().unwrap_or("Unknown"));
+                    println!("  - Vendor: {}", jdk_info.vendor.as_deref().unwrap_or("Unknown"));
+                    println!("  - Architecture: {}", jdk_info.arch.as_deref().unwrap_or("Unknown"));
+                    break java_home;
+                },
+                Err(error) => eprintln!("{}", error)
             }
+        };
 
-            //
-            // Install subcommand
-            //
+        let proposed_name = java_home
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .filter(|name| !config.has_toolchain(name));
 
-            JvmsCommand::Install(cmd) => {
-                let new_installation = JvmsInstallation::new(cmd.destination_path);
-                println!("Copying binaries to {:?}", new_installation.get_installation_path());
-                if let Err(error) = new_installation.install_binaries() {
-                    println!("Failed to copy binaries: {:?}", error);
-                    return Ok(());
-                }
+        let toolchain_name = loop {
+            let message = match proposed_name.as_ref() {
+                Some(proposed_name) => format!("Toolchain name [{}]: ", proposed_name),
+                None => "Toolchain name: ".to_owned()
+            };
 
-                println!("Finished installing jvms to {:?}", new_installation.get_installation_path());
-            },
+            let toolchain_name = match Jvms::prompt(&message)? {
+                Some(toolchain_name) if !toolchain_name.is_empty() => toolchain_name,
+                Some(_) if proposed_name.is_some() => proposed_name.clone().unwrap(),
+                Some(_) => continue,
+                None => return Ok(None)
+            };
 
-            //
-            // Override subcommands
-            //
+            if config.has_toolchain(&toolchain_name) {
+                eprintln!("Installation already found for name: {}", toolchain_name);
+                continue;
+            }
 
-            JvmsCommand::Override(OverrideCommand::Clean(cmd)) => {
-                let mut config = jvms_config?;
-                config.clean_overrides();
-                jvms_installation.save_configuration(&config, cmd.force)?;
+            break toolchain_name;
+        };
+
+        match Jvms::prompt(&format!("Register {} at {:?}? [y/N]: ", toolchain_name, java_home))? {
+            Some(answer) if answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes") => {
+                Ok(Some((toolchain_name, java_home)))
             },
-            JvmsCommand::Override(OverrideCommand::List(_)) => {
-                let config = jvms_config?;
-                println!("Registered overrides:");
-                for o in config.get_overrides().unwrap_or(&[]) {
-                    println!("  - {:?}:", o.path);
-                    println!("    - Toolchain: {}", o.toolchain);
+            _ => Ok(None)
+        }
+    }
+
+    ///
+    /// Runs the menu of `jvms ui` until the user quits. Each action runs the equivalent subcommand, so its output and
+    /// checks are the same as on the command line, and the configuration is loaded again before the menu is shown.
+    ///
+    fn run_ui(jvms_installation: &JvmsInstallation, offline: bool, search_root: Option<PathBuf>) -> Result<()> {
+        let mut global_args = vec![OsString::from("jvms")];
+        if offline {
+            global_args.push(OsString::from("--offline"));
+        }
+
+        if let Some(search_root) = search_root.as_ref() {
+            global_args.push(OsString::from("--root"));
+            global_args.push(search_root.into());
+        }
+
+        loop {
+            let config = jvms_installation.load_configuration()?;
+            let current_dir = env::current_dir()?;
+            let toolchain_names = Jvms::print_ui_toolchains(&config, &current_dir, search_root.as_deref());
+
+            println!();
+            let action = match Jvms::prompt("[d]efault, [o]verride here, [r]emove override, [s]can, [i]nstall, [q]uit: ")? {
+                Some(action) => action.to_lowercase(),
+                None => return Ok(())
+            };
+
+            let version: String;
+            let args = match action.as_str() {
+                "d" | "default" => match Jvms::prompt_ui_toolchain(&toolchain_names)? {
+                    Some(toolchain_name) => vec!["default", toolchain_name],
+                    None => continue
+                },
+                "o" | "override" => match Jvms::prompt_ui_toolchain(&toolchain_names)? {
+                    Some(toolchain_name) => vec!["override", "set", toolchain_name],
+                    None => continue
+                },
+                "r" | "remove" => vec!["override", "remove"],
+                "s" | "scan" => vec!["toolchain", "scan"],
+                "i" | "install" => match Jvms::prompt("Java version to install, e.g. 21 (empty to cancel): ")? {
+                    Some(answer) if !answer.is_empty() => {
+                        version = answer;
+                        vec!["toolchain", "install", &version]
+                    },
+                    _ => continue
+                },
+                "q" | "quit" => return Ok(()),
+                "" => continue,
+                _ => {
+                    eprintln!("Unknown action: {}", action);
+                    println!();
+                    continue;
                 }
-            },
-            JvmsCommand::Override(OverrideCommand::Remove(cmd)) => {
-                let mut config = jvms_config?;
-                let current_dir = env::current_dir().expect("Failed to get current working directory.");
-                config.remove_override(&cmd.path.unwrap_or(current_dir));
-                jvms_installation.save_configuration(&config, cmd.force)?;
-            },
-            JvmsCommand::Override(OverrideCommand::Set(cmd)) => {
-                let mut config = jvms_config?;
-                if config.has_toolchain(&cmd.toolchain_name) {
-                    let current_dir = env::current_dir().expect("Failed to get current working directory.");
-                    config.remove_override(&current_dir);
-                    config.add_override(&current_dir, cmd.toolchain_name);
-                    jvms_installation.save_configuration(&config, cmd.force)?;
+            };
 
-                } else {
-                    println!("No toolchain found for name: {}", cmd.toolchain_name);
+            let args = global_args.iter().cloned().chain(args.into_iter().map(OsString::from));
+            Jvms::run_ui_action(args.collect(), jvms_installation);
+        }
+    }
+
+    ///
+    /// Runs an action of `jvms ui`, printing its error instead of ending the session. The configuration lock taken
+    /// by the action is released afterwards, so that other jvms processes are not held up while the menu is open.
+    ///
+    fn run_ui_action(args: Vec<OsString>, jvms_installation: &JvmsInstallation) {
+        println!();
+        let result = Jvms::try_parse_from(args)
+            .map_err(|error| JvmsError::InvalidArguments(error.to_string()))
+            .and_then(|opts| Jvms::execute_opts(opts, jvms_installation));
+
+        jvms_installation.unlock_configuration();
+        if let Err(error) = result {
+            eprintln!("{}", error);
+        }
+
+        println!();
+    }
+
+    ///
+    /// Prints the numbered list of toolchains shown by `jvms ui`, marking the default and the toolchain selected for
+    /// the current directory, and returns their names in the order they were numbered.
+    ///
+    fn print_ui_toolchains(config: &JvmsConfiguration, current_dir: &Path, search_root: Option<&Path>) -> Vec<String> {
+        let mut toolchains: Vec<_> = config.get_toolchains().collect();
+        toolchains.sort_by(|a, b| a.0.cmp(b.0));
+
+        println!("Current directory: {:?}", current_dir);
+        if toolchains.is_empty() {
+            println!("No toolchains are registered yet, scan for installed JDKs or install one.");
+            return Vec::new();
+        }
+
+        let active = config.resolve_toolchain_with_source(current_dir, search_root).ok().flatten();
+        let name_width = toolchains.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        println!("Toolchains:");
+        for (index, (name, toolchain)) in toolchains.iter().enumerate() {
+            let jdk_info = toolchain.read_jdk_info();
+            let version = jdk_info.as_ref().and_then(|info| info.version.as_deref()).unwrap_or("?");
+            let vendor = jdk_info.as_ref().and_then(|info| info.vendor.as_deref()).unwrap_or("?");
+            let arch = toolchain.arch.as_deref().or_else(|| jdk_info.as_ref().and_then(|info| info.arch.as_deref())).unwrap_or("?");
+
+            let mut notes = Vec::new();
+            if config.get_default_toolchain_name() == Some(name.as_str()) {
+                notes.push("default".to_owned());
+            }
+
+            if let Some(active) = active.as_ref().filter(|active| active.name == Some(name.as_str())) {
+                notes.push(format!("active here through the {}", active.source.get_description()));
+            }
+
+            let notes = if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) };
+            println!("  {:>2}. {:<width$}  {:<10}  {:<20}  {}{}", index + 1, name, version, vendor, arch, notes, width = name_width);
+        }
+
+        toolchains.into_iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    ///
+    /// Asks for a toolchain by its number in the list of `jvms ui` or by name. Returns None if the user cancels.
+    ///
+    fn prompt_ui_toolchain(toolchain_names: &[String]) -> Result<Option<&str>> {
+        loop {
+            let answer = match Jvms::prompt("Toolchain number or name (empty to cancel): ")? {
+                Some(answer) if !answer.is_empty() => answer,
+                _ => return Ok(None)
+            };
+
+            let selected = match answer.parse::<usize>() {
+                Ok(number) => number.checked_sub(1).and_then(|index| toolchain_names.get(index)),
+                Err(_) => toolchain_names.iter().find(|name| **name == answer)
+            };
+
+            match selected {
+                Some(toolchain_name) => return Ok(Some(toolchain_name)),
+                None => eprintln!("No toolchain found for: {}", answer)
+            }
+        }
+    }
+
+    ///
+    /// Picks the syntax of the shell jvms was started from: PowerShell on Windows, fish if SHELL names it, and POSIX
+    /// sh syntax otherwise.
+    ///
+    fn detect_shell_syntax() -> ShellSyntax {
+        if cfg!(target_os="windows") {
+            return ShellSyntax::PowerShell;
+        }
+
+        match Jvms::get_user_shell().file_name().and_then(|name| name.to_str()) {
+            Some("fish") => ShellSyntax::Fish,
+            _ => ShellSyntax::Posix
+        }
+    }
+
+    fn get_user_shell() -> PathBuf {
+        #[cfg(target_os="windows")]
+        let (shell_var, default_shell) = ("COMSPEC", "cmd.exe");
+
+        #[cfg(not(target_os="windows"))]
+        let (shell_var, default_shell) = ("SHELL", "/bin/sh");
+
+        env::var_os(shell_var)
+            .filter(|shell| !shell.is_empty())
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(default_shell))
+    }
+
+    fn open_input(path: &Path) -> Result<Box<dyn io::Read>> {
+        if path == Path::new("-") {
+            Ok(Box::new(io::stdin()))
+
+        } else {
+            Ok(Box::new(fs::File::open(path)?))
+        }
+    }
+
+    fn print_json<T: serde::Serialize + ?Sized>(value: &T) -> Result<()> {
+        serde_json::to_writer_pretty(io::stdout(), value)?;
+        println!();
+        Ok(())
+    }
+
+    fn open_output(path: &Path) -> Result<Box<dyn io::Write>> {
+        if path == Path::new("-") {
+            Ok(Box::new(io::stdout()))
+
+        } else {
+            Ok(Box::new(fs::File::create(path)?))
+        }
+    }
+
+    fn check_status(jvms_config: Result<JvmsConfiguration>, search_root: Option<&Path>, report: bool) -> Result<i32> {
+        let config = match jvms_config.and_then(|config| config.validate_configuration().map(|_| config)) {
+            Ok(config) => config,
+            Err(error) => {
+                if report {
+                    println!("Configuration is invalid: {}", error);
                 }
-            },
 
-            //
-            // Toolchain subcommands
-            //
+                return Ok(error::EXIT_CONFIG_ERROR);
+            }
+        };
 
-            JvmsCommand::Toolchain(ToolchainCommand::Add(cmd)) => {
-                let mut config = jvms_config?;
-                if config.has_toolchain(&cmd.toolchain_name) {
-                    println!("Installation already found for name: {}", cmd.toolchain_name);
+        if report {
+            println!("Configuration is valid.");
+        }
 
-                } else {
-                    config.add_toolchain(cmd.toolchain_name, cmd.java_home);
-                    jvms_installation.save_configuration(&config, cmd.force)?;
+        let current_dir = env::current_dir()?;
+        let resolved_toolchain = match config.resolve_toolchain_with_source(&current_dir, search_root) {
+            Ok(resolved_toolchain) => resolved_toolchain,
+            Err(error) => {
+                if report {
+                    println!("Failed to resolve toolchain for {:?}: {}", current_dir, error);
                 }
-            },
-            JvmsCommand::Toolchain(ToolchainCommand::List(_)) => {
-                let config = jvms_config?;
-                println!("Available toolchains:");
-                for toolchain in config.get_toolchains() {
-                    println!("  - {}:", toolchain.0);
-                    println!("    - JAVA_HOME = {:?}", toolchain.1.java_home);
+
+                return Ok(error.get_exit_code());
+            }
+        };
+
+        match resolved_toolchain {
+            Some(resolved) if resolved.toolchain.java_home.is_dir() => {
+                if report {
+                    println!("Toolchain {} resolved for {:?}", resolved.get_display_name(), current_dir);
+                    println!("  - Source = {}", resolved.source.get_description());
+                    println!("  - JAVA_HOME = {:?}", resolved.toolchain.java_home);
                 }
+
+                Ok(0)
             },
-            JvmsCommand::Toolchain(ToolchainCommand::Remove(cmd)) => {
-                let mut config = jvms_config?;
-                if config.has_toolchain(&cmd.toolchain_name) {
-                    config.remove_toolchain(&cmd.toolchain_name);
-                    jvms_installation.save_configuration(&config, cmd.force)?;
+            Some(resolved) => {
+                if report {
+                    println!("Toolchain {} resolved for {:?} but its java home does not exist: {:?}", resolved.get_display_name(), current_dir, resolved.toolchain.java_home);
+                }
 
-                } else {
-                    println!("No toolchain found for name: {}", cmd.toolchain_name);
+                Ok(error::EXIT_NO_TOOLCHAIN)
+            },
+            None => {
+                if report {
+                    println!("No toolchain resolves for {:?}", current_dir);
                 }
+
+                Ok(error::EXIT_NO_TOOLCHAIN)
             }
         }
+    }
 
-        Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn os_args(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    fn write_script(dir: &Path, name: &str, shebang: &str) -> OsString {
+        let path = dir.join(name);
+        fs::write(&path, format!("{}\nclass Main {{ public static void main(String[] args) {{ }} }}\n", shebang)).unwrap();
+        path.into_os_string()
+    }
+
+    fn create_jdk(java_home: &Path, version: &str) -> String {
+        jdk::tests::create_fake_jdk(java_home, version);
+        java_home.to_str().unwrap().to_owned()
+    }
+
+    fn test_installation(dir: &Path) -> JvmsInstallation {
+        JvmsInstallation::new(dir.join("installation")).with_config_file(dir.join("jvms.toml"))
+    }
+
+    fn add_jdk(jvms_installation: &JvmsInstallation, dir: &Path, name: &str, version: &str, options: &[&str]) -> PathBuf {
+        let java_home = dir.join(format!("jdk-{}", name));
+        let java_home_arg = create_jdk(&java_home, version);
+        let args: Vec<&str> = ["toolchain", "add"].iter().chain(options).chain(&[name, java_home_arg.as_str()]).cloned().collect();
+        assert_eq!(run(jvms_installation, &args).unwrap(), 0);
+        java_home
+    }
+
+    fn install_shims(jvms_installation: &JvmsInstallation, dir: &Path) {
+        fs::create_dir_all(dir.join("installation")).unwrap();
+        fs::write(dir.join("installation").join("jvms"), "").unwrap();
+        for shim in jvms_installation.get_missing_shims() {
+            jvms_installation.link_shim(shim, LinkMode::Copy).unwrap();
+        }
+    }
+
+    fn run(jvms_installation: &JvmsInstallation, args: &[&str]) -> Result<i32> {
+        let opts = Jvms::try_parse_from(std::iter::once("jvms").chain(args.iter().cloned())).unwrap();
+        Jvms::execute_opts(opts, jvms_installation)
+    }
+
+    #[test]
+    fn splits_a_shebang_line_passed_as_one_argument() {
+        let args = os_args(&["/usr/local/bin/jvms", "run 17 --", "./script", "a b"]);
+        assert_eq!(Jvms::split_shebang_args(args), os_args(&["/usr/local/bin/jvms", "run", "17", "--", "./script", "a b"]));
+
+        let args = os_args(&["jvms", "--config /etc/jvms.toml run -q 17 --", "./script"]);
+        assert_eq!(Jvms::split_shebang_args(args), os_args(&["jvms", "--config", "/etc/jvms.toml", "run", "-q", "17", "--", "./script"]));
+    }
+
+    #[test]
+    fn leaves_arguments_split_by_env_alone() {
+        let args = os_args(&["jvms", "run", "-q", "17", "--", "./script", "run 17 --"]);
+        assert_eq!(Jvms::split_shebang_args(args.clone()), args);
+
+        let args = os_args(&["jvms", "run", "17 --", "./script"]);
+        assert_eq!(Jvms::split_shebang_args(args.clone()), args);
+    }
+
+    #[test]
+    fn parses_the_run_command_of_a_shebang_line() {
+        let args = Jvms::split_shebang_args(os_args(&["jvms", "run -q 17 --", "./script", "--flag", "value"]));
+        match Jvms::try_parse_from(args).unwrap().command {
+            JvmsCommand::Run(cmd) => {
+                assert!(cmd.quiet);
+                assert_eq!(cmd.toolchain.as_deref(), Some("17"));
+                assert_eq!(cmd.command, os_args(&["./script", "--flag", "value"]));
+            },
+            _ => panic!("expected the run command")
+        }
+    }
+
+    #[test]
+    fn finds_jvms_scripts_by_their_shebang_line() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let env_script = write_script(temp_dir.path(), "env-script", "#!/usr/bin/env -S jvms run 17 --");
+        let direct_script = write_script(temp_dir.path(), "direct-script", "#!/usr/local/bin/jvms run 17 --");
+        let other_script = write_script(temp_dir.path(), "other-script", "#!/bin/sh");
+
+        assert_eq!(Jvms::find_jvms_script(std::slice::from_ref(&env_script)), Some(0));
+        assert_eq!(Jvms::find_jvms_script(&[direct_script.clone(), OsString::from("arg")]), Some(0));
+        assert_eq!(Jvms::find_jvms_script(&[other_script]), None);
+        assert_eq!(Jvms::find_jvms_script(&os_args(&["--enable-preview"]).into_iter().chain(Some(env_script.clone())).collect::<Vec<_>>()), Some(1));
+
+        // A script passed as an argument to another command is not run as java.
+        assert_eq!(Jvms::find_jvms_script(&[OsString::from("cat"), env_script]), None);
+    }
+
+    #[test]
+    fn splits_java_opts_like_a_shell() {
+        assert_eq!(Jvms::split_java_opts("-Xmx2g  -Dfile.encoding=UTF-8").unwrap(), vec!["-Xmx2g", "-Dfile.encoding=UTF-8"]);
+        assert_eq!(Jvms::split_java_opts("-Xmx2g '-Dapp.name=My App'").unwrap(), vec!["-Xmx2g", "-Dapp.name=My App"]);
+        assert_eq!(Jvms::split_java_opts("-Dapp.home=\"C:\\\\Program Files\\\\App\"").unwrap(), vec!["-Dapp.home=C:\\Program Files\\App"]);
+        assert!(Jvms::split_java_opts("").unwrap().is_empty());
+        assert!(Jvms::split_java_opts("-Dapp.name='unterminated").is_err());
+    }
+
+    #[test]
+    fn runs_scripts_with_the_source_of_the_toolchain() {
+        let command = os_args(&["./script", "a"]);
+        assert_eq!(Jvms::get_source_file_args(&command, 0, 17), os_args(&["--source", "17", "./script", "a"]));
+
+        let command = os_args(&["--enable-preview", "./script"]);
+        assert_eq!(Jvms::get_source_file_args(&command, 1, 21), os_args(&["--source", "21", "--enable-preview", "./script"]));
+
+        let command = os_args(&["--source", "11", "./script"]);
+        assert_eq!(Jvms::get_source_file_args(&command, 2, 17), os_args(&["--source", "11", "./script"]));
+
+        let command = os_args(&["--source=11", "./script"]);
+        assert_eq!(Jvms::get_source_file_args(&command, 1, 17), os_args(&["--source=11", "./script"]));
+    }
+
+    #[test]
+    fn default_if_none_only_sets_a_default_when_there_is_none() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        let jdk_17 = create_jdk(&temp_dir.path().join("jdk-17"), "17.0.9");
+        let jdk_21 = create_jdk(&temp_dir.path().join("jdk-21"), "21.0.1");
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "add", "--default-if-none", "17", &jdk_17]).unwrap(), 0);
+        assert_eq!(jvms_installation.load_configuration().unwrap().get_default_toolchain_name(), Some("17"));
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "add", "--default-if-none", "21", &jdk_21]).unwrap(), 0);
+        let config = jvms_installation.load_configuration().unwrap();
+        assert_eq!(config.get_default_toolchain_name(), Some("17"));
+        assert!(config.has_toolchain("21"));
+    }
+
+    #[test]
+    fn adding_the_first_toolchain_without_a_default_fails() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        let jdk_17 = create_jdk(&temp_dir.path().join("jdk-17"), "17.0.9");
+
+        assert!(matches!(run(&jvms_installation, &["toolchain", "add", "17", &jdk_17]), Err(JvmsError::NoDefaultConfigured)));
+        assert!(!jvms_installation.get_config_file_path().exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn doctor_makes_java_binaries_executable_with_fix() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        install_shims(&jvms_installation, temp_dir.path());
+
+        let java_home = temp_dir.path().join("jdk-21");
+        create_jdk(&java_home, "21.0.1");
+        assert_eq!(run(&jvms_installation, &["toolchain", "add", "--default-if-none", "21", java_home.to_str().unwrap()]).unwrap(), 0);
+        assert_eq!(run(&jvms_installation, &["doctor"]).unwrap(), 0);
+
+        let java_path = jdk::get_tool_path(&java_home, "java");
+        fs::set_permissions(&java_path, fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(run(&jvms_installation, &["doctor"]).unwrap(), error::EXIT_FAILURE);
+        assert!(!jdk::is_executable(&java_path));
+
+        assert_eq!(run(&jvms_installation, &["doctor", "--fix", "--yes"]).unwrap(), 0);
+        assert!(jdk::is_executable(&java_path));
+    }
+
+    #[test]
+    fn tags_and_untags_toolchains() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        add_jdk(&jvms_installation, temp_dir.path(), "21", "21.0.1", &["--default-if-none", "--tag", "lts"]);
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "tag", "21", "work", "lts"]).unwrap(), 0);
+        let config = jvms_installation.load_configuration().unwrap();
+        assert_eq!(config.select_toolchain("21").unwrap().1.tags.as_deref(), Some(&["lts".to_owned(), "work".to_owned()][..]));
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "untag", "21", "lts", "missing"]).unwrap(), 0);
+        let config = jvms_installation.load_configuration().unwrap();
+        assert!(!config.select_toolchain("21").unwrap().1.has_tag("lts"));
+        assert!(config.select_toolchain("21").unwrap().1.has_tag("work"));
+
+        assert!(matches!(run(&jvms_installation, &["toolchain", "tag", "missing", "lts"]), Err(JvmsError::UnknownToolchain(_))));
+    }
+
+    #[test]
+    fn removing_by_tag_is_all_or_nothing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        add_jdk(&jvms_installation, temp_dir.path(), "22", "22.0.1", &["--tag", "ea", "--default-if-none"]);
+        add_jdk(&jvms_installation, temp_dir.path(), "17", "17.0.9", &["--tag", "lts"]);
+        add_jdk(&jvms_installation, temp_dir.path(), "21", "21.0.1", &["--tag", "lts"]);
+
+        let project_dir = temp_dir.path().join("project");
+        fs::create_dir(&project_dir).unwrap();
+        assert_eq!(run(&jvms_installation, &["override", "set", "17", project_dir.to_str().unwrap()]).unwrap(), 0);
+
+        let toolchain_count = || jvms_installation.load_configuration().unwrap().get_toolchains().count();
+        assert_eq!(run(&jvms_installation, &["toolchain", "remove", "--tag", "lts"]).unwrap(), error::EXIT_FAILURE);
+        assert_eq!(toolchain_count(), 3);
+
+        assert!(matches!(run(&jvms_installation, &["toolchain", "remove", "--tag", "lts", "--yes"]), Err(JvmsError::ToolchainInUse { .. })));
+        assert_eq!(toolchain_count(), 3);
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "remove", "--tag", "lts", "--yes", "--cascade"]).unwrap(), 0);
+        let config = jvms_installation.load_configuration().unwrap();
+        assert_eq!(config.get_toolchains().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), vec!["22"]);
+        assert!(config.get_overrides().unwrap_or(&[]).is_empty());
+        assert_eq!(config.get_default_toolchain_name(), Some("22"));
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "remove", "--tag", "lts", "--yes"]).unwrap(), 0);
+    }
+
+    #[test]
+    fn set_default_version_makes_the_toolchain_the_default_of_its_major_version() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        add_jdk(&jvms_installation, temp_dir.path(), "17.0.1", "17.0.1", &["--default-if-none", "--set-default-version"]);
+        add_jdk(&jvms_installation, temp_dir.path(), "17.0.9", "17.0.9", &[]);
+
+        let config = jvms_installation.load_configuration().unwrap();
+        assert_eq!(config.get_version_default(17).map(|(name, _)| name), Some("17.0.1"));
+        assert_eq!(config.select_toolchain("17").map(|(name, _)| name), Some("17.0.1"));
+
+        add_jdk(&jvms_installation, temp_dir.path(), "17.0.2", "17.0.2", &["--set-default-version"]);
+        let config = jvms_installation.load_configuration().unwrap();
+        assert_eq!(config.get_version_default(17).map(|(name, _)| name), Some("17.0.2"));
+        assert!(config.get_version_default(21).is_none());
+    }
+
+    #[test]
+    fn set_default_version_is_skipped_without_a_detected_version() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        let java_home = temp_dir.path().join("custom");
+        let java_home_arg = create_jdk(&java_home, "");
+        fs::remove_file(java_home.join("release")).unwrap();
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "add", "--default-if-none", "--set-default-version", "custom", &java_home_arg]).unwrap(), 0);
+        let config = jvms_installation.load_configuration().unwrap();
+        assert!(config.has_toolchain("custom"));
+        assert!((1..=30).all(|major_version| config.get_version_default(major_version).is_none()));
+    }
+
+    #[test]
+    fn adds_aliases_for_a_new_toolchain() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        add_jdk(&jvms_installation, temp_dir.path(), "temurin-21", "21.0.1", &["--default-if-none", "--alias", "lts", "--alias", "latest"]);
+
+        let config = jvms_installation.load_configuration().unwrap();
+        assert_eq!(config.get_aliases_for("temurin-21"), vec!["latest", "lts"]);
+        assert_eq!(config.select_toolchain("lts").map(|(name, _)| name), Some("temurin-21"));
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "remove", "temurin-21", "--cascade", "--force"]).unwrap(), 0);
+        assert_eq!(jvms_installation.load_configuration().unwrap().get_alias_target("lts"), None);
+    }
+
+    #[test]
+    fn refuses_aliases_that_conflict_with_existing_names() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        add_jdk(&jvms_installation, temp_dir.path(), "temurin-21", "21.0.1", &["--default-if-none", "--alias", "lts"]);
+        let java_home = create_jdk(&temp_dir.path().join("jdk-17"), "17.0.9");
+
+        for aliases in &[&["temurin-17"][..], &["old", "old"], &["lts"], &["temurin-21"]] {
+            let mut args = vec!["toolchain", "add"];
+            for alias in *aliases {
+                args.extend(&["--alias", alias]);
+            }
+
+            args.extend(&["temurin-17", java_home.as_str()]);
+            assert!(matches!(run(&jvms_installation, &args), Err(JvmsError::InvalidArguments(_))), "aliases {:?} were accepted", aliases);
+        }
+
+        let config = jvms_installation.load_configuration().unwrap();
+        assert!(!config.has_toolchain("temurin-17"));
+        assert_eq!(config.get_alias_target("lts"), Some("temurin-21"));
+        assert_eq!(config.get_alias_target("old"), None);
+    }
+
+    #[test]
+    fn warnings_are_printed_to_stderr() {
+        const CHILD_DIR_ENV: &str = "JVMS_TEST_CHILD_DIR";
+
+        //
+        // Output of the test itself is captured, so the commands run in a child process that prints directly.
+        //
+
+        if let Some(dir) = env::var_os(CHILD_DIR_ENV) {
+            let jvms_installation = test_installation(Path::new(&dir));
+            let java_home = Path::new(&dir).join("missing");
+            run(&jvms_installation, &["toolchain", "add", "--allow-missing", "--default-if-none", "21", java_home.to_str().unwrap()]).unwrap();
+            run(&jvms_installation, &["toolchain", "remove", "--tag", "lts"]).unwrap();
+            return;
+        }
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output = process::Command::new(env::current_exe().unwrap())
+            .args(["--exact", "jvms::tests::warnings_are_printed_to_stderr", "--nocapture"])
+            .env(CHILD_DIR_ENV, temp_dir.path())
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "child failed: {}", stderr);
+        assert!(stdout.contains("Setting default installation to 21"));
+        assert!(!stderr.contains("Setting default installation"));
+
+        for warning in &["registering 21 as unverified", "No toolchains are tagged lts"] {
+            assert!(stderr.contains(warning), "{:?} is not in stderr: {}", warning, stderr);
+            assert!(!stdout.contains(warning), "{:?} is in stdout: {}", warning, stdout);
+        }
+    }
+
+    #[test]
+    fn check_duplicate_home_refuses_a_registered_java_home() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        let java_home = add_jdk(&jvms_installation, temp_dir.path(), "21", "21.0.1", &["--default-if-none"]);
+        let java_home = java_home.to_str().unwrap();
+
+        match run(&jvms_installation, &["toolchain", "add", "--check-duplicate-home", "temurin-21", java_home]) {
+            Err(JvmsError::DuplicateJavaHome { toolchain, .. }) => assert_eq!(toolchain, "21"),
+            _ => panic!("expected the java home to be refused")
+        }
+
+        assert!(!jvms_installation.load_configuration().unwrap().has_toolchain("temurin-21"));
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "add", "temurin-21", java_home]).unwrap(), 0);
+        assert!(jvms_installation.load_configuration().unwrap().has_toolchain("temurin-21"));
+    }
+
+    #[test]
+    fn install_dry_run_resolves_the_release_without_installing_it() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        let (mirror, handle) = adoptium::tests::serve_api(vec![(200, adoptium::tests::RELEASE_21), (200, adoptium::tests::RELEASE_21)]);
+        fs::write(jvms_installation.get_config_file_path(), format!("[mirrors.adoptium]\napi_url = \"{}\"\n", mirror.api_url.unwrap())).unwrap();
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "install", "21", "--arch", "aarch64", "--dry-run"]).unwrap(), 0);
+        assert_eq!(jvms_installation.load_configuration().unwrap().get_toolchains().count(), 0);
+        assert!(!jvms_installation.get_toolchains_path().exists());
+
+        //
+        // The name is only known once the release is resolved, so a taken name is reported by the dry run too.
+        //
+
+        let mut config_file = fs::OpenOptions::new().append(true).open(jvms_installation.get_config_file_path()).unwrap();
+        write!(config_file, "\n[toolchains.\"temurin-21.0.4\"]\njava_home = \"/opt/jdk-21\"\n").unwrap();
+        match run(&jvms_installation, &["toolchain", "install", "temurin-21", "--dry-run"]) {
+            Err(JvmsError::ToolchainExists(name)) => assert_eq!(name, "temurin-21.0.4"),
+            _ => panic!("expected the toolchain name to be taken")
+        }
+
+        let requests = handle.join().unwrap();
+        assert!(requests.iter().all(|request| request.starts_with("GET /v3/assets/latest/21/hotspot?")));
+        assert!(requests[0].contains("architecture=aarch64"));
+        assert!(requests[1].contains(&format!("architecture={}", adoptium::get_current_arch())));
+    }
+
+    #[test]
+    fn reports_an_unreadable_configuration_with_its_own_exit_code() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        fs::write(temp_dir.path().join("jvms.toml"), "[toolchains\n").unwrap();
+
+        assert_ne!(error::EXIT_CONFIG_ERROR, error::EXIT_USAGE_ERROR);
+        assert_eq!(run(&jvms_installation, &["config", "validate"]).unwrap(), error::EXIT_CONFIG_ERROR);
+        assert_eq!(run(&jvms_installation, &["status", "--check"]).unwrap(), error::EXIT_CONFIG_ERROR);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn exec_and_shell_suggest_a_toolchain_for_an_unknown_name() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        add_jdk(&jvms_installation, temp_dir.path(), "temurin-17", "17.0.2", &["--default-if-none"]);
+
+        let result = run(&jvms_installation, &["exec", "--toolchain", "temurn-17", "java"]);
+        assert!(matches!(result, Err(JvmsError::UnknownToolchainSuggestion { suggestion, .. }) if suggestion == "temurin-17"));
+        let result = run(&jvms_installation, &["shell", "temurn-17"]);
+        assert!(matches!(result, Err(JvmsError::UnknownToolchainSuggestion { suggestion, .. }) if suggestion == "temurin-17"));
+    }
+
+    #[test]
+    fn validate_only_takes_a_single_argument_as_the_java_home() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let jvms_installation = test_installation(temp_dir.path());
+        let java_home = create_jdk(&temp_dir.path().join("jdk"), "21.0.1");
+
+        assert_eq!(run(&jvms_installation, &["toolchain", "add", "--validate-only", &java_home]).unwrap(), 0);
+        assert_eq!(run(&jvms_installation, &["toolchain", "add", "--validate-only", "temurin-21", &java_home]).unwrap(), 0);
+        assert_eq!(jvms_installation.load_configuration().unwrap().get_toolchains().count(), 0);
+
+        let missing_home = temp_dir.path().join("missing");
+        let result = run(&jvms_installation, &["toolchain", "add", "--validate-only", missing_home.to_str().unwrap()]);
+        assert!(matches!(result, Err(JvmsError::InvalidToolchain(_))));
+    }
+
+}
@@ -0,0 +1,34 @@
+//!
+//! The library behind the jvms binary. It reads and edits the configuration of an installation, resolves the
+//! toolchain selected for a directory from overrides and project files, and discovers JDKs on the system, so that
+//! frontends and editor plugins can reuse that logic instead of running the command line.
+//!
+//! The command line itself is available as [Jvms], which the binary hands its arguments to.
+//!
+
+pub mod config;
+pub mod discovery;
+pub mod error;
+pub mod hooks;
+pub mod jdk;
+pub mod selector;
+pub mod shim;
+
+mod adoptium;
+mod archive;
+mod completions;
+mod download;
+mod jvms;
+mod maven;
+mod user_path;
+
+pub use crate::config::{
+    JavaToolchain, JvmsConfiguration, JvmsConfigurationBuilder, JvmsInstallation, JvmsOverride, ResolutionSource,
+    ResolvedHome, ResolvedToolchain
+};
+pub use crate::discovery::{DiscoveredJdk, DiscoveryFilter};
+pub use crate::error::{JvmsError, Result};
+pub use crate::jdk::{JavaVersion, JdkInfo};
+pub use crate::jvms::Jvms;
+pub use crate::selector::ToolchainSelector;
+pub use crate::shim::Shim;
@@ -1,31 +1,29 @@
-mod config;
-mod error;
-mod jvms;
-mod shim;
+use jvms::{Jvms, JvmsInstallation, Result, Shim};
+use std::process;
 
-use config::JvmsInstallation;
-use error::{JvmsError, Result};
-use jvms::Jvms;
-use shim::Shim;
+///
+/// Environment variable holding the log filter, e.g. `debug` or `jvms::config=trace`. Logging is off unless set.
+///
+const LOG_ENV: &str = "JVMS_LOG";
 
 fn main() {
+    env_logger::Builder::from_env(env_logger::Env::new().filter_or(LOG_ENV, "off")).init();
+
     match run_main() {
-        Ok(_) => {},
-        Err(JvmsError::IoError(error)) => {
-            eprintln!("IO Error has occurred: {:?}", error);
-        },
-        Err(JvmsError::InvalidConfiguration(string)) => {
-            eprintln!("Configuration error: {}", string);
-        },
-        Err(JvmsError::SerdeJsonError(error)) => {
-            eprintln!("Serde error has occurred: {:?}", error);
+        Ok(exit_code) => process::exit(exit_code),
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(error.get_exit_code());
         }
     }
 }
 
-fn run_main() -> Result<()> {
+///
+/// Runs jvms or the shim it was invoked as, returning the exit code of the process.
+///
+fn run_main() -> Result<i32> {
     let jvms_installation = JvmsInstallation::get_current_installation();
-    if let Some(shim) = Shim::get_current_shim()? {
+    if let Some(shim) = Shim::get_current_shim(&jvms_installation)? {
         shim.execute(&jvms_installation)
 
     } else {
@@ -0,0 +1,195 @@
+
+use crate::error::{JvmsError, Result};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+///
+/// Comment placed in the toolchain entries written by jvms, so that they are replaced by later exports while
+/// entries written by hand are kept.
+///
+const MANAGED_COMMENT: &str = "Managed by jvms";
+
+///
+/// A JDK toolchain entry of Maven's toolchains.xml.
+///
+pub struct MavenToolchain {
+    pub id: String,
+    pub version: Option<String>,
+    pub vendor: Option<String>,
+    pub jdk_home: PathBuf
+}
+
+///
+/// Returns the toolchains.xml of the current user, `~/.m2/toolchains.xml`.
+///
+pub fn get_user_toolchains_path() -> Option<PathBuf> {
+    Some(dirs::home_dir()?.join(".m2").join("toolchains.xml"))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn write_toolchain(output: &mut String, toolchain: &MavenToolchain) {
+    let _ = writeln!(output, "  <toolchain>");
+    let _ = writeln!(output, "    <!-- {} -->", MANAGED_COMMENT);
+    let _ = writeln!(output, "    <type>jdk</type>");
+    let _ = writeln!(output, "    <provides>");
+    let _ = writeln!(output, "      <id>{}</id>", escape(&toolchain.id));
+    if let Some(version) = toolchain.version.as_ref() {
+        let _ = writeln!(output, "      <version>{}</version>", escape(version));
+    }
+
+    if let Some(vendor) = toolchain.vendor.as_ref() {
+        let _ = writeln!(output, "      <vendor>{}</vendor>", escape(vendor));
+    }
+
+    let _ = writeln!(output, "    </provides>");
+    let _ = writeln!(output, "    <configuration>");
+    let _ = writeln!(output, "      <jdkHome>{}</jdkHome>", escape(&toolchain.jdk_home.to_string_lossy()));
+    let _ = writeln!(output, "    </configuration>");
+    let _ = writeln!(output, "  </toolchain>");
+}
+
+fn is_managed(toolchain: roxmltree::Node) -> bool {
+    toolchain.children().any(|child| child.is_comment() && child.text().is_some_and(|text| text.trim() == MANAGED_COMMENT))
+}
+
+///
+/// Merges the toolchains into the contents of an existing toolchains.xml, if any. Entries written by an earlier
+/// merge are replaced, everything else in the file is kept as it was.
+///
+pub fn merge_toolchains(existing: Option<&str>, toolchains: &[MavenToolchain]) -> Result<String> {
+    let mut entries = String::new();
+    for toolchain in toolchains {
+        write_toolchain(&mut entries, toolchain);
+    }
+
+    let existing = match existing {
+        Some(existing) if !existing.trim().is_empty() => existing,
+        _ => return Ok(format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<toolchains>\n{}</toolchains>\n", entries))
+    };
+
+    let document =
+        roxmltree::Document::parse(existing)
+            .map_err(|error| {
+                JvmsError::InvalidConfiguration(format!("Failed to parse toolchains.xml: {}", error))
+            })?;
+
+    let root = document.root_element();
+    if root.tag_name().name() != "toolchains" {
+        return Err(JvmsError::InvalidConfiguration(format!("Expected a toolchains element in toolchains.xml, found {}.", root.tag_name().name())));
+    }
+
+    //
+    // The file is edited as text so that comments and formatting outside of the managed entries survive. Managed
+    // entries are cut out along with the whitespace before them, and the new entries go before the closing tag.
+    //
+
+    let mut merged = String::new();
+    let mut position = 0;
+    for toolchain in root.children().filter(|child| child.is_element() && child.tag_name().name() == "toolchain" && is_managed(*child)) {
+        let range = toolchain.range();
+        let start = existing[..range.start].trim_end_matches([' ', '\t', '\r', '\n']).len().max(position);
+        merged.push_str(&existing[position..start]);
+        position = range.end;
+    }
+
+    let root_range = root.range();
+    if existing[root_range.clone()].ends_with("/>") {
+        merged.push_str(&existing[position..root_range.start]);
+        let _ = write!(merged, "<toolchains>\n{}</toolchains>", entries);
+
+    } else {
+        let closing_start = existing[..root_range.end].rfind("</").unwrap_or(root_range.end);
+        let content_end = existing[..closing_start].trim_end_matches([' ', '\t', '\r', '\n']).len().max(position);
+        merged.push_str(&existing[position..content_end]);
+        merged.push('\n');
+        merged.push_str(&entries);
+        merged.push_str(&existing[closing_start..root_range.end]);
+    }
+
+    merged.push_str(&existing[root_range.end..]);
+    Ok(merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+
+    const HAND_WRITTEN: &str = "  <!-- Installed by hand -->
+  <toolchain>
+    <type>jdk</type>
+    <provides>
+      <id>custom</id>
+    </provides>
+    <configuration>
+      <jdkHome>/opt/custom</jdkHome>
+    </configuration>
+  </toolchain>
+";
+
+    fn toolchain(id: &str, version: &str) -> MavenToolchain {
+        MavenToolchain {
+            id: id.to_owned(),
+            version: Some(version.to_owned()),
+            vendor: Some("Eclipse Adoptium".to_owned()),
+            jdk_home: PathBuf::from(format!("/opt/jdk-{}", version))
+        }
+    }
+
+    fn entries(toolchains: &[MavenToolchain]) -> String {
+        let mut entries = String::new();
+        for toolchain in toolchains {
+            write_toolchain(&mut entries, toolchain);
+        }
+
+        entries
+    }
+
+    #[test]
+    fn writes_a_new_toolchains_file() {
+        let toolchains = [toolchain("temurin-17", "17.0.9"), toolchain("temurin-21", "21.0.1")];
+        let expected = format!("{}<toolchains>\n{}</toolchains>\n", HEADER, entries(&toolchains));
+
+        assert_eq!(merge_toolchains(None, &toolchains).unwrap(), expected);
+        assert_eq!(merge_toolchains(Some("\n"), &toolchains).unwrap(), expected);
+        assert!(expected.contains("<vendor>Eclipse Adoptium</vendor>"));
+    }
+
+    #[test]
+    fn replaces_only_the_managed_entries_of_an_existing_file() {
+        let existing = format!(
+            "{}<!-- Local toolchains -->\n<toolchains>\n{}{}</toolchains>\n",
+            HEADER, entries(&[toolchain("temurin-11", "11.0.21")]), HAND_WRITTEN
+        );
+
+        let toolchains = [toolchain("temurin-21", "21.0.1")];
+        let merged = merge_toolchains(Some(&existing), &toolchains).unwrap();
+        assert_eq!(merged, format!("{}<!-- Local toolchains -->\n<toolchains>\n{}{}</toolchains>\n", HEADER, HAND_WRITTEN, entries(&toolchains)));
+
+        //
+        // Exporting again gives the same file, rather than adding the entries a second time.
+        //
+
+        assert_eq!(merge_toolchains(Some(&merged), &toolchains).unwrap(), merged);
+    }
+
+    #[test]
+    fn fills_a_self_closing_toolchains_element() {
+        let toolchains = [toolchain("temurin-21", "21.0.1")];
+        let existing = format!("{}<toolchains/>\n", HEADER);
+
+        assert_eq!(merge_toolchains(Some(&existing), &toolchains).unwrap(), merge_toolchains(None, &toolchains).unwrap());
+    }
+
+    #[test]
+    fn refuses_a_file_without_a_toolchains_root() {
+        let existing = format!("{}<settings>\n</settings>\n", HEADER);
+        assert!(matches!(merge_toolchains(Some(&existing), &[toolchain("temurin-21", "21.0.1")]), Err(JvmsError::InvalidConfiguration(_))));
+        assert!(matches!(merge_toolchains(Some("<toolchains>"), &[]), Err(JvmsError::InvalidConfiguration(_))));
+    }
+
+}
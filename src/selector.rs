@@ -0,0 +1,126 @@
+
+use crate::jdk::JavaVersion;
+
+pub enum ToolchainSelector {
+    Name(String),
+    Glob(String),
+    Version(VersionRequirement)
+}
+
+pub struct VersionRequirement {
+    op: VersionOp,
+    version: JavaVersion
+}
+
+enum VersionOp {
+    Equal,
+    Greater,
+    GreaterOrEqual,
+    Less,
+    LessOrEqual
+}
+
+fn glob_matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        Some(('*', rest)) => (0..=text.len()).any(|i| glob_matches(rest, &text[i..])),
+        Some(('?', rest)) => !text.is_empty() && glob_matches(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_matches(rest, &text[1..]),
+        None => text.is_empty()
+    }
+}
+
+///
+/// Matches text against a glob pattern where '*' matches any sequence of characters and '?' matches any single
+/// character.
+///
+pub fn glob_matches_str(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_matches(&pattern, &text)
+}
+
+impl ToolchainSelector {
+
+    ///
+    /// Parses a toolchain selector, which is either a glob over toolchain names (containing '*' or '?'), a version
+    /// requirement such as "17", "17+", or ">=11", or otherwise a plain toolchain name.
+    ///
+    pub fn parse(spec: &str) -> ToolchainSelector {
+        if spec.contains('*') || spec.contains('?') {
+            ToolchainSelector::Glob(spec.to_owned())
+
+        } else if let Some(requirement) = VersionRequirement::parse(spec) {
+            ToolchainSelector::Version(requirement)
+
+        } else {
+            ToolchainSelector::Name(spec.to_owned())
+        }
+    }
+
+    pub fn matches(&self, toolchain_name: &str, version: Option<&JavaVersion>) -> bool {
+        match self {
+            ToolchainSelector::Name(name) => name == toolchain_name,
+            ToolchainSelector::Glob(pattern) => glob_matches_str(pattern, toolchain_name),
+            ToolchainSelector::Version(requirement) => version.map(|v| requirement.matches(v)).unwrap_or(false)
+        }
+    }
+
+}
+
+impl VersionRequirement {
+
+    pub fn parse(spec: &str) -> Option<VersionRequirement> {
+        let spec = spec.trim();
+        let (op, version) = if let Some(version) = spec.strip_suffix('+') {
+            (VersionOp::GreaterOrEqual, version)
+
+        } else if let Some(version) = spec.strip_prefix(">=") {
+            (VersionOp::GreaterOrEqual, version)
+
+        } else if let Some(version) = spec.strip_prefix("<=") {
+            (VersionOp::LessOrEqual, version)
+
+        } else if let Some(version) = spec.strip_prefix('>') {
+            (VersionOp::Greater, version)
+
+        } else if let Some(version) = spec.strip_prefix('<') {
+            (VersionOp::Less, version)
+
+        } else if let Some(version) = spec.strip_prefix('=') {
+            (VersionOp::Equal, version)
+
+        } else {
+            (VersionOp::Equal, spec)
+        };
+
+        //
+        // Only accept plain dotted numbers so that toolchain names containing digits are not mistaken for versions.
+        //
+
+        let version = version.trim();
+        if version.is_empty() || !version.chars().all(|c| c.is_ascii_digit() || c == '.') {
+            return None;
+        }
+
+        Some(VersionRequirement {
+            op,
+            version: JavaVersion::parse(version)?
+        })
+    }
+
+    ///
+    /// Checks the version against the requirement, only comparing as many components as the requirement has so
+    /// that "17" matches any 17.x.y release and "<=17" includes them.
+    ///
+    pub fn matches(&self, version: &JavaVersion) -> bool {
+        let version = version.truncate(self.version.component_count());
+        match self.op {
+            VersionOp::Equal => version == self.version,
+            VersionOp::Greater => version > self.version,
+            VersionOp::GreaterOrEqual => version >= self.version,
+            VersionOp::Less => version < self.version,
+            VersionOp::LessOrEqual => version <= self.version
+        }
+    }
+
+}
@@ -1,93 +1,479 @@
 
-use crate::config::JvmsInstallation;
-use crate::error::{Result, JvmsError};
-use std::{env, io, process};
+use crate::config::{self, JvmsConfiguration, JvmsInstallation, ResolvedHome};
+use crate::error::{JvmsError, Result};
+use crate::hooks::{HookContext, HookEvent};
+use crate::jdk;
+use log::debug;
+use std::borrow::Cow;
+use std::{env, fs, io, process};
+use std::path::Path;
 
-static JAVA_SHIMS: [Shim; 7] = [
-    Shim {
-        name: "jar"
-    },
+///
+/// Environment variable set on shimmed tools to the name of the toolchain they were launched from.
+///
+const ACTIVE_TOOLCHAIN_ENV: &str = "JVMS_ACTIVE_TOOLCHAIN";
+
+///
+/// Argument that, when it is the first argument of a shim, is dropped and disables the configured java options for
+/// that invocation. The prefix keeps it from colliding with options of the tools themselves.
+///
+const NO_DEFAULTS_ARG: &str = "--jvms-no-defaults";
+
+///
+/// Name of the jvms binary, which is never treated as a shim.
+///
+const JVMS_BINARY_NAME: &str = "jvms";
+
+///
+/// Shims that are always installed, even before any toolchain is registered. Shims for the other tools of the
+/// registered toolchains are generated by `jvms rehash`.
+///
+static JAVA_SHIMS: [Shim; 6] = [
     Shim {
-        name: "java"
+        name: Cow::Borrowed("jar")
     },
     Shim {
-        name: "javac"
+        name: Cow::Borrowed("java")
     },
     Shim {
-        name: "javadoc"
+        name: Cow::Borrowed("javac")
     },
     Shim {
-        name: "javah"
+        name: Cow::Borrowed("javadoc")
     },
     Shim {
-        name: "javap"
+        name: Cow::Borrowed("javap")
     },
     Shim {
-        name: "javaw"
+        name: Cow::Borrowed("javaw")
     }
 ];
 
+///
+/// Tools without a console, whose shims start them and exit right away instead of waiting for them.
+///
+static DETACHED_TOOLS: [&str; 2] = ["javaw", "javaws"];
+
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Shim {
-    pub name: &'static str
+    name: Cow<'static, str>
+}
+
+///
+/// How a shim runs its tool.
+///
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LaunchMode {
+    /// Run the tool and exit with its exit code.
+    Wait,
+    /// Start the tool and exit immediately, leaving it running on its own.
+    Detach
+}
+
+#[cfg(target_os="windows")]
+extern "system" {
+    fn GetCommandLineW() -> *const u16;
+}
+
+///
+/// Forwards the arguments of the current process to the command, dropping the first one if skip_first is set.
+///
+/// On Windows the arguments are forwarded as the raw command line (minus the program name) rather than being
+/// parsed by `env::args_os` and re-quoted, so that arguments containing spaces, quotes, or backslashes reach the
+/// JVM exactly as they were written.
+///
+#[cfg(target_os="windows")]
+fn forward_args(command: &mut process::Command, skip_first: bool) {
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use std::os::windows::process::CommandExt;
+
+    let command_line = unsafe {
+        let command_line_ptr = GetCommandLineW();
+        let mut len = 0;
+        while *command_line_ptr.add(len) != 0 {
+            len += 1;
+        }
+
+        std::slice::from_raw_parts(command_line_ptr, len)
+    };
+
+    let args = OsString::from_wide(get_command_line_args(command_line, skip_first));
+    if !args.is_empty() {
+        command.raw_arg(args);
+    }
+}
+
+///
+/// Returns the arguments of a Windows command line as written, after the program name and, if skip_first is set,
+/// after the first argument.
+///
+#[cfg(any(target_os="windows", test))]
+fn get_command_line_args(command_line: &[u16], skip_first: bool) -> &[u16] {
+    //
+    // The program name is parsed differently from the other arguments: it either runs up to the next quote if
+    // it starts with one, or up to the first whitespace. Backslashes are never treated as escapes.
+    //
+
+    let is_whitespace = |c: u16| c == ' ' as u16 || c == '\t' as u16;
+    let program_end = if command_line.first() == Some(&('"' as u16)) {
+        command_line[1..]
+            .iter()
+            .position(|&c| c == '"' as u16)
+            .map(|i| i + 2)
+            .unwrap_or(command_line.len())
+
+    } else {
+        command_line
+            .iter()
+            .position(|&c| is_whitespace(c))
+            .unwrap_or(command_line.len())
+    };
+
+    let skip_whitespace = |start: usize| {
+        command_line[start..]
+            .iter()
+            .position(|&c| !is_whitespace(c))
+            .map(|i| start + i)
+            .unwrap_or(command_line.len())
+    };
+
+    let mut args_start = skip_whitespace(program_end);
+
+    //
+    // The skipped argument is only ever NO_DEFAULTS_ARG, which needs no quoting, so it ends at the next whitespace.
+    //
+
+    if skip_first {
+        let first_end = command_line[args_start..]
+            .iter()
+            .position(|&c| is_whitespace(c))
+            .map(|i| args_start + i)
+            .unwrap_or(command_line.len());
+        args_start = skip_whitespace(first_end);
+    }
+
+    &command_line[args_start..]
+}
+
+///
+/// Forwards the arguments of the current process to the command, dropping the first one if skip_first is set.
+///
+#[cfg(not(target_os="windows"))]
+fn forward_args(command: &mut process::Command, skip_first: bool) {
+    command.args(env::args_os().skip(if skip_first { 2 } else { 1 }));
+}
+
+///
+/// Lists the tools in the bin directory of a java home, by their names without extension. Only files that can be
+/// executed are included, which on Windows means .exe files.
+///
+fn list_tools(java_home: &Path) -> Vec<String> {
+    let entries = match fs::read_dir(java_home.join("bin")) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new()
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            if cfg!(target_os="windows") {
+                path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("exe")) && path.is_file()
+            } else {
+                jdk::is_executable(path)
+            }
+        })
+        .filter_map(|path| path.file_stem().and_then(|name| name.to_str()).map(str::to_owned))
+        .collect()
 }
 
 impl Shim {
 
+    pub fn new(name: String) -> Shim {
+        Shim { name: Cow::Owned(name) }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_launch_mode(&self) -> LaunchMode {
+        if DETACHED_TOOLS.contains(&self.name()) {
+            LaunchMode::Detach
+        } else {
+            LaunchMode::Wait
+        }
+    }
+
+    ///
+    /// Returns the shims that are always installed.
+    ///
     pub fn get_shims() -> &'static [Shim] {
         &JAVA_SHIMS
     }
 
-    pub fn get_current_shim() -> Result<Option<&'static Shim>> {
+    ///
+    /// Returns the always installed shims along with a shim for every tool found in the bin directory of a
+    /// registered toolchain, sorted by name.
+    ///
+    pub fn get_toolchain_shims(config: &JvmsConfiguration) -> Vec<Shim> {
+        let mut shims: Vec<Shim> = Shim::get_shims().to_vec();
+        for (_, toolchain) in config.get_toolchains() {
+            shims.extend(list_tools(&toolchain.java_home).into_iter().map(Shim::new));
+        }
+
+        shims.retain(|shim| shim.name() != JVMS_BINARY_NAME);
+        shims.sort();
+        shims.dedup();
+        shims
+    }
+
+    ///
+    /// Returns the shim the current process was invoked as, if any. Besides the always installed shims, this is any
+    /// shim listed as generated by `jvms rehash`.
+    ///
+    pub fn get_current_shim(jvms_installation: &JvmsInstallation) -> Result<Option<Shim>> {
         let current_exe_path = env::current_exe()?;
         let current_exe_name =
             current_exe_path.file_stem()
                 .ok_or(io::Error::from(io::ErrorKind::InvalidInput))?
-                .to_string_lossy();
+                .to_string_lossy()
+                .into_owned();
+
+        //
+        // current_exe resolves symlinks on some platforms, so a symlinked shim is recognized by the name it was
+        // invoked as instead.
+        //
 
-        for shim in Shim::get_shims() {
-            if *shim.name == current_exe_name {
-                return Ok(Some(shim));
+        let invoked_name = env::args_os()
+            .next()
+            .and_then(|arg0| Path::new(&arg0).file_stem().map(|name| name.to_string_lossy().into_owned()));
+
+        for name in invoked_name.into_iter().chain(Some(current_exe_name)) {
+            if name == JVMS_BINARY_NAME {
+                continue;
+            }
+
+            if Shim::get_shims().iter().any(|shim| shim.name() == name) || jvms_installation.is_generated_shim(&name)? {
+                return Ok(Some(Shim::new(name)));
             }
         }
 
         Ok(None)
     }
 
-    pub fn execute(&self, jvms_installation: &JvmsInstallation) -> Result<()> {
+    ///
+    /// Builds the command that runs the tool of the resolved toolchain from the directory, with the environment and
+    /// java options of the toolchain and of the directory's override applied. The arguments of the current process
+    /// are forwarded separately.
+    ///
+    fn build_command(&self, jvms_config: &JvmsConfiguration, current_dir: &Path, resolved: &ResolvedHome<'_>, exe_path: &Path, no_defaults: bool) -> Result<process::Command> {
+        let mut command = process::Command::new(exe_path);
+        jdk::apply_environment(&resolved.java_home, &mut command, false, jvms_config.is_dedupe_path())?;
+        match resolved.name {
+            Some(name) => command.env(ACTIVE_TOOLCHAIN_ENV, name),
+            None => command.env_remove(ACTIVE_TOOLCHAIN_ENV)
+        };
+
+        for env_var in jvms_config.get_cleared_env_vars() {
+            command.env_remove(env_var);
+        }
+
+        //
+        // Settings of the toolchain apply first, followed by those of the override for the current directory when
+        // it selected this toolchain, so that override environment variables win and override java options come
+        // later on the command line.
+        //
+
+        let jvms_override = jvms_config.get_environment_override(current_dir)
+            .filter(|o| jvms_config.select_toolchain(&o.toolchain).is_some_and(|(name, _)| Some(name) == resolved.name));
+
+        let toolchain_env = resolved.toolchain.and_then(|t| t.env.as_ref());
+        for env in toolchain_env.into_iter().chain(jvms_override.and_then(|o| o.env.as_ref())) {
+            command.envs(env);
+        }
+
+        if (self.name() == "java" || self.name() == "javaw") && !no_defaults {
+            let toolchain_java_opts = resolved.toolchain.and_then(|t| t.java_opts.as_ref());
+            for java_opts in toolchain_java_opts.into_iter().chain(jvms_override.and_then(|o| o.java_opts.as_ref())) {
+                command.args(java_opts);
+            }
+        }
+
+        //
+        // Tools that dispatch on how they were invoked should see the shim name as argv[0], not the full path of
+        // the resolved binary. Windows has no separate argv[0], the child always sees the path it was started with.
+        //
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.arg0(self.name());
+        }
+
+        Ok(command)
+    }
+
+    ///
+    /// Runs the tool of the resolved toolchain with the arguments of the current process, returning the exit code
+    /// the current process should exit with.
+    ///
+    /// On Unix the current process is replaced by the tool, so this only returns if that fails. The tool then
+    /// receives signals directly and its exit status, including death by a signal, is seen by the caller as is.
+    /// Detached tools are only started, and the shim exits successfully once they are running.
+    ///
+    pub fn execute(&self, jvms_installation: &JvmsInstallation) -> Result<i32> {
         let jvms_config = jvms_installation.load_configuration()?;
         let current_dir = env::current_dir()?;
-        let toolchain = if let Some(env_toolchain) = jvms_config.get_environment_toolchain(&current_dir) {
-            env_toolchain
+        let search_root = config::get_search_root(None);
+        let resolved = jvms_config.resolve_home_with_toolchain(&current_dir, search_root.as_deref(), None)?;
+
+        let exe_path = jdk::get_tool_path(&resolved.java_home, self.name());
+        if !exe_path.is_file() {
+            return Err(JvmsError::InvalidToolchain(format!("Toolchain {} does not provide {}.", resolved.get_display_name(), self.name())));
+        }
 
-        } else if let Some(default_toolchain) = jvms_config.get_default_toolchain() {
-            default_toolchain
+        let no_defaults = env::args_os().nth(1).is_some_and(|arg| arg == NO_DEFAULTS_ARG);
+        let mut command = self.build_command(&jvms_config, &current_dir, &resolved, &exe_path, no_defaults)?;
+        forward_args(&mut command, no_defaults);
 
-        } else {
-            return Err(JvmsError::InvalidConfiguration(format!("Failed to find toolchain for {:?} and default toolchain not configured.", current_dir)));
+        let hook_context = HookContext {
+            toolchain: resolved.name,
+            java_home: Some(&resolved.java_home),
+            directory: Some(&current_dir),
+            tool: Some(self.name()),
+            ..HookContext::default()
         };
 
-        let exe_path = {
-            let mut path = toolchain.java_home.clone();
-            path.push("bin");
-            path.push(self.name);
+        jvms_config.run_hook(HookEvent::PreLaunch, &hook_context)?;
+        debug!("Launching {:?}", command);
+
+        if self.get_launch_mode() == LaunchMode::Detach {
+            //
+            // The tool has no use for the console of the shim, which can then close as soon as the shim exits.
+            //
 
             #[cfg(target_os="windows")]
             {
-                assert!(path.set_extension("exe"));
+                use std::os::windows::process::CommandExt;
+                const DETACHED_PROCESS: u32 = 0x00000008;
+                command.creation_flags(DETACHED_PROCESS);
             }
 
-            path
-        };
+            command.spawn().map_err(|error| JvmsError::ProcessLaunch { program: exe_path, error })?;
+            return Ok(0);
+        }
 
-        let mut command = process::Command::new(exe_path);
-        command.env("JAVA_HOME", toolchain.java_home.as_os_str());
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            Err(JvmsError::ProcessLaunch { program: exe_path, error: command.exec() })
+        }
+
+        #[cfg(not(unix))]
+        {
+            let status = command.spawn().map_err(|error| JvmsError::ProcessLaunch { program: exe_path, error })?.wait()?;
+            Ok(status.code().unwrap_or(1))
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_args(command_line: &str, skip_first: bool) -> String {
+        let command_line: Vec<u16> = command_line.encode_utf16().collect();
+        String::from_utf16(get_command_line_args(&command_line, skip_first)).unwrap()
+    }
+
+    fn build_command(shim_name: &str, config: &str, no_defaults: bool) -> process::Command {
+        let jvms_config: JvmsConfiguration = toml::from_str(config).unwrap();
+        let (name, toolchain) = jvms_config.select_toolchain("21").unwrap();
+        let resolved = ResolvedHome { name: Some(name), toolchain: Some(toolchain), java_home: toolchain.java_home.clone() };
+        let exe_path = jdk::get_tool_path(&resolved.java_home, shim_name);
+        Shim::new(shim_name.to_owned()).build_command(&jvms_config, Path::new("/work"), &resolved, &exe_path, no_defaults).unwrap()
+    }
+
+    fn get_env(command: &process::Command, key: &str) -> Option<Option<String>> {
+        command.get_envs()
+            .find(|(env_key, _)| *env_key == key)
+            .map(|(_, value)| value.map(|value| value.to_string_lossy().into_owned()))
+    }
+
+    #[test]
+    fn forwards_arguments_exactly_as_written() {
+        assert_eq!(
+            get_args(r#""C:\Program Files\jvms\java.exe" -cp "C:\My Libs\*" -Dname="a \"b\"" Main"#, false),
+            r#"-cp "C:\My Libs\*" -Dname="a \"b\"" Main"#
+        );
+
+        assert_eq!(get_args(r#"java.exe   -jar  "app 1.jar""#, false), r#"-jar  "app 1.jar""#);
+        assert_eq!(get_args(r#"C:\jvms\java.exe"#, false), "");
+        assert_eq!(get_args(r#""C:\Program Files\jvms\java.exe""#, false), "");
+    }
+
+    #[test]
+    fn program_names_keep_backslashes_before_quotes() {
+        assert_eq!(get_args(r#""C:\jvms dir\"java.exe -version"#, false), "java.exe -version");
+    }
+
+    #[test]
+    fn skips_the_no_defaults_argument() {
+        assert_eq!(get_args(r#""C:\Program Files\jvms\java.exe" --jvms-no-defaults -version"#, true), "-version");
+        assert_eq!(get_args("java.exe --jvms-no-defaults", true), "");
+    }
+
+    #[test]
+    fn clears_the_configured_env_vars() {
+        let command = build_command("java", r#"
+            default = "21"
+            clear_env = [ "JAVA_TOOL_OPTIONS", "_JAVA_OPTIONS" ]
+
+            [toolchains.21]
+            java_home = "/opt/jdk-21"
+            env = { _JAVA_OPTIONS = "-Xss4m" }
+        "#, false);
+
+        assert_eq!(get_env(&command, "JAVA_TOOL_OPTIONS"), Some(None));
+        assert_eq!(get_env(&command, "_JAVA_OPTIONS").flatten().as_deref(), Some("-Xss4m"));
+        assert_eq!(get_env(&command, ACTIVE_TOOLCHAIN_ENV).flatten().as_deref(), Some("21"));
+        assert_eq!(get_env(&command, "JAVA_HOME").flatten().as_deref(), Some("/opt/jdk-21"));
+    }
+
+    #[test]
+    fn clears_nothing_by_default() {
+        let command = build_command("java", r#"
+            default = "21"
+
+            [toolchains.21]
+            java_home = "/opt/jdk-21"
+        "#, false);
+
+        assert!(command.get_envs().all(|(_, value)| value.is_some()));
+    }
+
+    #[test]
+    fn passes_the_configured_java_opts_unless_disabled() {
+        let config = r#"
+            default = "21"
+
+            [toolchains.21]
+            java_home = "/opt/jdk-21"
+            java_opts = [ "-Xmx2g" ]
 
-        env::args_os().skip(1).for_each(|arg| {
-            command.arg(arg);
-        });
+            [[overrides]]
+            path = "/work"
+            toolchain = "21"
+            java_opts = [ "-Dapp.env=work" ]
+        "#;
 
-        command.spawn()?.wait()?;
-        Ok(())
+        let command_args = |command: &process::Command| command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect::<Vec<_>>();
+        assert_eq!(command_args(&build_command("java", config, false)), vec!["-Xmx2g", "-Dapp.env=work"]);
+        assert!(command_args(&build_command("java", config, true)).is_empty());
+        assert!(command_args(&build_command("javac", config, false)).is_empty());
     }
 
 }
@@ -0,0 +1,184 @@
+
+use crate::error::{JvmsError, Result};
+use std::env;
+use std::path::Path;
+
+#[cfg(target_os="windows")]
+use std::process;
+
+#[cfg(not(target_os="windows"))]
+use std::fs;
+#[cfg(not(target_os="windows"))]
+use std::io::{self, Write};
+#[cfg(not(target_os="windows"))]
+use std::path::PathBuf;
+
+///
+/// Comment ending the lines added to shell profiles, so that they can be told apart from the user's own.
+///
+#[cfg(not(target_os="windows"))]
+const PROFILE_MARKER: &str = "# Added by jvms";
+
+///
+/// Checks whether the directory is an entry of the PATH of the current process.
+///
+pub fn is_on_path(directory: &Path) -> bool {
+    env::var_os("PATH")
+        .map(|path| env::split_paths(&path).any(|entry| entry == directory))
+        .unwrap_or(false)
+}
+
+///
+/// Runs a PowerShell script that edits the PATH of the current user with the directory available as
+/// $env:JVMS_PATH_ENTRY, returning whether the script reported a change by printing "changed". Setting the variable
+/// through [Environment] stores it in the registry and broadcasts the change, so that newly started programs see it.
+///
+#[cfg(target_os="windows")]
+fn update_user_path(directory: &Path, script: &str) -> Result<bool> {
+    //
+    // The directory is passed through the environment so it needs no quoting. Entries are compared without
+    // trailing separators, as Windows does when searching PATH.
+    //
+
+    let script = format!("
+        $dir = $env:JVMS_PATH_ENTRY.TrimEnd('\\')
+        $path = [Environment]::GetEnvironmentVariable('Path', 'User')
+        if ($path -eq $null) {{ $path = '' }}
+        $entries = @($path -split ';' | Where-Object {{ $_ -ne '' }})
+        $others = @($entries | Where-Object {{ $_.TrimEnd('\\') -ne $dir }})
+        {}
+    ", script);
+
+    let output =
+        process::Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command", &script])
+            .env("JVMS_PATH_ENTRY", directory.as_os_str())
+            .output()?;
+
+    if !output.status.success() {
+        return Err(JvmsError::InvalidConfiguration(format!("Failed to update the user PATH: {}", String::from_utf8_lossy(&output.stderr).trim())));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim() == "changed")
+}
+
+///
+/// Appends the directory to the PATH of the current user, as stored in the registry, returning whether it was
+/// missing.
+///
+#[cfg(target_os="windows")]
+pub fn add_to_user_path(directory: &Path) -> Result<bool> {
+    update_user_path(directory, "
+        if ($others.Count -eq $entries.Count) {
+            [Environment]::SetEnvironmentVariable('Path', (($entries + $env:JVMS_PATH_ENTRY) -join ';'), 'User')
+            Write-Output 'changed'
+        }
+    ")
+}
+
+///
+/// Removes the directory from the PATH of the current user, as stored in the registry, returning whether it was
+/// present.
+///
+#[cfg(target_os="windows")]
+pub fn remove_from_user_path(directory: &Path) -> Result<bool> {
+    update_user_path(directory, "
+        if ($others.Count -ne $entries.Count) {
+            [Environment]::SetEnvironmentVariable('Path', ($others -join ';'), 'User')
+            Write-Output 'changed'
+        }
+    ")
+}
+
+///
+/// Returns the startup file of the user's shell, from SHELL: `.zshrc` for zsh, `.bashrc` for bash (`.bash_profile`
+/// on macOS, where terminals start login shells), `config.fish` for fish, and `.profile` for any other shell.
+///
+#[cfg(not(target_os="windows"))]
+pub fn get_shell_profile() -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    let shell = env::var_os("SHELL").map(PathBuf::from).unwrap_or_default();
+    let non_empty_var = |name: &str| env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+
+    Some(match shell.file_name().and_then(|name| name.to_str()) {
+        Some("zsh") => non_empty_var("ZDOTDIR").unwrap_or(home).join(".zshrc"),
+        Some("bash") if cfg!(target_os="macos") => home.join(".bash_profile"),
+        Some("bash") => home.join(".bashrc"),
+        Some("fish") => non_empty_var("XDG_CONFIG_HOME").unwrap_or_else(|| home.join(".config")).join("fish").join("config.fish"),
+        _ => home.join(".profile")
+    })
+}
+
+///
+/// Builds the line that prepends the directory to PATH in the shell profile, quoting the directory for the shell.
+///
+#[cfg(not(target_os="windows"))]
+fn get_profile_line(profile: &Path, directory: &Path) -> String {
+    let directory = directory.to_string_lossy();
+    if profile.file_name().is_some_and(|name| name == "config.fish") {
+        format!("set -gx PATH '{}' $PATH {}", directory.replace('\\', "\\\\").replace('\'', "\\'"), PROFILE_MARKER)
+
+    } else {
+        format!("export PATH='{}':\"$PATH\" {}", directory.replace('\'', "'\\''"), PROFILE_MARKER)
+    }
+}
+
+#[cfg(not(target_os="windows"))]
+fn read_profile(profile: &Path) -> Result<String> {
+    match fs::read_to_string(profile) {
+        Ok(contents) => Ok(contents),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        Err(error) => Err(JvmsError::InvalidConfiguration(format!("Failed to read {:?}: {}", profile, error)))
+    }
+}
+
+///
+/// Appends a line adding the directory to PATH to the shell profile, returning whether the profile did not already
+/// have it.
+///
+#[cfg(not(target_os="windows"))]
+pub fn add_to_shell_profile(profile: &Path, directory: &Path) -> Result<bool> {
+    let line = get_profile_line(profile, directory);
+    let contents = read_profile(profile)?;
+    if contents.lines().any(|existing| existing == line) {
+        return Ok(false);
+    }
+
+    if let Some(parent) = profile.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let separator = if contents.is_empty() || contents.ends_with('\n') { "" } else { "\n" };
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(profile)?;
+    write!(file, "{}\n{}\n", separator, line)?;
+    Ok(true)
+}
+
+///
+/// Removes the line added by add_to_shell_profile for the directory, returning whether it was present.
+///
+#[cfg(not(target_os="windows"))]
+pub fn remove_from_shell_profile(profile: &Path, directory: &Path) -> Result<bool> {
+    let line = get_profile_line(profile, directory);
+    let contents = read_profile(profile)?;
+    if !contents.lines().any(|existing| existing == line) {
+        return Ok(false);
+    }
+
+    //
+    // The blank line written ahead of the line is removed along with it.
+    //
+
+    let mut kept: Vec<&str> = Vec::new();
+    for existing in contents.lines() {
+        if existing != line {
+            kept.push(existing);
+
+        } else if kept.last() == Some(&"") {
+            kept.pop();
+        }
+    }
+
+    fs::write(profile, kept.iter().map(|existing| format!("{}\n", existing)).collect::<String>())?;
+    Ok(true)
+}